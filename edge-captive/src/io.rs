@@ -2,7 +2,7 @@ use core::fmt;
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::time::Duration;
 
-use edge_nal::{UdpBind, UdpReceive, UdpSend};
+use edge_nal::{with_timeout, UdpBind, UdpReceive, UdpSend, WithTimeoutError};
 
 use super::*;
 
@@ -63,6 +63,42 @@ where
 
 impl<E> core::error::Error for DnsIoError<E> where E: core::error::Error {}
 
+/// The options of a captive portal DNS responder
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CaptiveOptions<'a> {
+    /// The IP address returned for all A queries not in `allowlist` (i.e. the IP address of the captive portal)
+    pub ip: Ipv4Addr,
+    /// The IPv6 address returned for all AAAA queries not in `allowlist`
+    ///
+    /// When `None`, AAAA queries are not answered, which confuses the captive portal detection of some dual-stack clients.
+    pub ipv6: Option<Ipv6Addr>,
+    /// The time-to-live of the answers
+    pub ttl: Duration,
+    /// Domain suffixes which are not redirected to the captive portal, but resolved by `upstream` instead
+    pub allowlist: &'a [&'a str],
+    /// The upstream DNS server resolving the domains in `allowlist`
+    ///
+    /// When `None`, the queries for domains in `allowlist` are redirected to the captive portal too.
+    pub upstream: Option<SocketAddr>,
+    /// The time to wait for a reply from `upstream`
+    pub upstream_timeout_ms: u32,
+}
+
+impl<'a> CaptiveOptions<'a> {
+    /// Create options redirecting all queries to the provided IP address
+    pub const fn new(ip: Ipv4Addr, ttl: Duration) -> Self {
+        Self {
+            ip,
+            ipv6: None,
+            ttl,
+            allowlist: &[],
+            upstream: None,
+            upstream_timeout_ms: 3000,
+        }
+    }
+}
+
 pub async fn run<S>(
     stack: &S,
     local_addr: SocketAddr,
@@ -71,11 +107,52 @@ pub async fn run<S>(
     ip: Ipv4Addr,
     ttl: Duration,
 ) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+{
+    run_with_options(
+        stack,
+        local_addr,
+        tx_buf,
+        rx_buf,
+        &CaptiveOptions::new(ip, ttl),
+    )
+    .await
+}
+
+/// Run a captive portal DNS responder with the provided options
+///
+/// Queries for domains in the allowlist of the options are forwarded to the upstream DNS server
+/// and its replies are relayed back, while all other queries are answered with the IP of the captive portal.
+///
+/// Note that the forwarding is sequential, so other queries are not processed while waiting for the upstream DNS server.
+pub async fn run_with_options<S>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    options: &CaptiveOptions<'_>,
+) -> Result<(), DnsIoError<S::Error>>
 where
     S: UdpBind,
 {
     let mut udp = stack.bind(local_addr).await.map_err(DnsIoError::IoError)?;
 
+    let mut upstream_udp = match options.upstream {
+        Some(upstream) if !options.allowlist.is_empty() => {
+            let local_addr = match upstream {
+                SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+            };
+
+            Some((
+                upstream,
+                stack.bind(local_addr).await.map_err(DnsIoError::IoError)?,
+            ))
+        }
+        _ => None,
+    };
+
     loop {
         debug!("Waiting for data");
 
@@ -85,7 +162,43 @@ where
 
         debug!("Received {} bytes from {}", request.len(), remote);
 
-        let len = match crate::reply(request, &ip.octets(), ttl, tx_buf) {
+        if let Some((upstream, upstream_udp)) = upstream_udp.as_mut() {
+            match crate::is_allowed(request, options.allowlist) {
+                Ok(true) => {
+                    if let Some(len) = forward(
+                        upstream_udp,
+                        *upstream,
+                        request,
+                        tx_buf,
+                        options.upstream_timeout_ms,
+                    )
+                    .await?
+                    {
+                        udp.send(remote, &tx_buf[..len])
+                            .await
+                            .map_err(DnsIoError::IoError)?;
+
+                        debug!("Relayed {} bytes from {} to {}", len, upstream, remote);
+                    }
+
+                    continue;
+                }
+                Ok(false) => (),
+                Err(DnsError::InvalidMessage) => {
+                    warn!("Got invalid message from {}, skipping", remote);
+                    continue;
+                }
+                Err(other) => Err(other)?,
+            }
+        }
+
+        let len = match crate::reply_with_ipv6(
+            request,
+            &options.ip.octets(),
+            options.ipv6.map(|ipv6| ipv6.octets()).as_ref(),
+            options.ttl,
+            tx_buf,
+        ) {
             Ok(len) => len,
             Err(err) => match err {
                 DnsError::InvalidMessage => {
@@ -103,3 +216,41 @@ where
         debug!("Sent {} bytes to {}", len, remote);
     }
 }
+
+async fn forward<U>(
+    udp: &mut U,
+    upstream: SocketAddr,
+    request: &[u8],
+    buf: &mut [u8],
+    timeout_ms: u32,
+) -> Result<Option<usize>, DnsIoError<U::Error>>
+where
+    U: UdpReceive + UdpSend,
+{
+    debug!("Forwarding {} bytes to {}", request.len(), upstream);
+
+    udp.send(upstream, request)
+        .await
+        .map_err(DnsIoError::IoError)?;
+
+    let result = with_timeout(timeout_ms, async {
+        loop {
+            let (len, remote) = udp.receive(buf).await?;
+
+            // Skip stale replies to previous (timed out) requests
+            if remote == upstream && len >= 2 && buf[..2] == request[..2] {
+                break Ok(len);
+            }
+        }
+    })
+    .await;
+
+    match result {
+        Ok(len) => Ok(Some(len)),
+        Err(WithTimeoutError::Timeout) => {
+            warn!("No reply from upstream DNS server {}, skipping", upstream);
+            Ok(None)
+        }
+        Err(WithTimeoutError::Error(err)) => Err(DnsIoError::IoError(err)),
+    }
+}
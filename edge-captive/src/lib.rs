@@ -6,6 +6,7 @@
 use core::fmt::Display;
 use core::time::Duration;
 
+use domain::base::name::ToLabelIter;
 use domain::base::wire::Composer;
 use domain::dep::octseq::{OctetsBuilder, Truncate};
 
@@ -19,7 +20,7 @@ use domain::{
         Record, Rtype,
     },
     dep::octseq::ShortBuf,
-    rdata::A,
+    rdata::{Aaaa, A},
 };
 
 // This mod MUST go first, so that the others see its macros.
@@ -79,11 +80,87 @@ impl From<ParseError> for DnsError {
     }
 }
 
+/// Return `true` if any of the questions in the request is for a domain in the allowlist
+///
+/// The allowlist contains domain suffixes, i.e. `example.com` allows both `example.com`
+/// and all of its subdomains, like `update.example.com`. Matching is case-insensitive.
+/// Empty suffixes (i.e. `""` or `"."`) match no domain rather than all of them.
+pub fn is_allowed(request: &[u8], allowlist: &[&str]) -> Result<bool, DnsError> {
+    if allowlist.is_empty() {
+        return Ok(false);
+    }
+
+    let message = domain::base::Message::from_octets(request)?;
+
+    for question in message.question() {
+        let question = question?;
+
+        if allowlist
+            .iter()
+            .any(|suffix| is_subdomain(question.qname(), suffix))
+        {
+            debug!(
+                "Question {:?} is for an allowed domain",
+                debug2format!(question)
+            );
+
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn is_subdomain<N: ToLabelIter>(name: N, suffix: &str) -> bool {
+    let mut labels = name.iter_labels().rev().filter(|label| !label.is_root());
+    let mut suffix_labels = suffix
+        .rsplit('.')
+        .filter(|label| !label.is_empty())
+        .peekable();
+
+    // An empty suffix would otherwise be a suffix of every name
+    if suffix_labels.peek().is_none() {
+        return false;
+    }
+
+    loop {
+        match (labels.next(), suffix_labels.next()) {
+            (Some(label), Some(suffix_label)) => {
+                if !label
+                    .as_slice()
+                    .eq_ignore_ascii_case(suffix_label.as_bytes())
+                {
+                    return false;
+                }
+            }
+            (_, None) => return true,
+            (None, Some(_)) => return false,
+        }
+    }
+}
+
 pub fn reply(
     request: &[u8],
     ip: &[u8; 4],
     ttl: Duration,
     buf: &mut [u8],
+) -> Result<usize, DnsError> {
+    reply_with_ipv6(request, ip, None, ttl, buf)
+}
+
+/// Reply to the questions of the request with the IP addresses of the captive portal
+///
+/// A questions are answered with `ip`, and AAAA questions with `ipv6`. Dual-stack clients
+/// often query AAAA for the captive portal detection URLs too, and some of them fail to
+/// detect the portal when the AAAA query has no answer, so set `ipv6` when the portal is reachable over IPv6.
+/// When `ipv6` is `None`, AAAA questions get no answer in a successful (NOERROR) reply, i.e. the name exists
+/// but has no IPv6 address, so that clients fall back to IPv4 right away.
+pub fn reply_with_ipv6(
+    request: &[u8],
+    ip: &[u8; 4],
+    ipv6: Option<&[u8; 16]>,
+    ttl: Duration,
+    buf: &mut [u8],
 ) -> Result<usize, DnsError> {
     let buf = Buf(buf, 0);
 
@@ -116,9 +193,31 @@ pub fn reply(
                     debug2format!(record)
                 );
                 answerb.push(record)?;
+            } else if matches!(question.qtype(), Rtype::AAAA)
+                && matches!(question.qclass(), Class::IN)
+            {
+                if let Some(ipv6) = ipv6 {
+                    let record = Record::new(
+                        question.qname(),
+                        Class::IN,
+                        Ttl::from_duration_lossy(ttl),
+                        Aaaa::new((*ipv6).into()),
+                    );
+                    debug!(
+                        "Answering {:?} with {:?}",
+                        debug2format!(question),
+                        debug2format!(record)
+                    );
+                    answerb.push(record)?;
+                } else {
+                    debug!(
+                        "Question {:?} is of type AAAA, but there is no IPv6 address to answer with",
+                        debug2format!(question)
+                    );
+                }
             } else {
                 debug!(
-                    "Question {:?} is not of type A, not answering",
+                    "Question {:?} is not of type A or AAAA, not answering",
                     debug2format!(question)
                 );
             }
@@ -178,3 +277,114 @@ impl AsRef<[u8]> for Buf<'_> {
         &self.0[..self.1]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use domain::base::iana::{Class, Rcode};
+    use domain::base::{Message, MessageBuilder, Name, Rtype};
+    use domain::rdata::{Aaaa, A};
+
+    use super::{is_allowed, is_subdomain, reply_with_ipv6, Buf};
+
+    const IP: [u8; 4] = [192, 168, 71, 1];
+    const IPV6: [u8; 16] = [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+
+    /// Build a query for `name` (in wire format) into `buf`
+    fn query<'a>(buf: &'a mut [u8], name: &[u8], qtype: Rtype) -> &'a [u8] {
+        let mut builder = MessageBuilder::from_target(Buf(buf, 0)).unwrap().question();
+        builder.header_mut().set_id(42);
+        builder.header_mut().set_rd(true);
+        builder
+            .push((Name::from_slice(name).unwrap(), qtype, Class::IN))
+            .unwrap();
+
+        let len = builder.finish().1;
+
+        &buf[..len]
+    }
+
+    /// Reply to a query for `captive.example` of type `qtype`
+    fn reply<'a>(buf: &'a mut [u8], qtype: Rtype, ipv6: Option<&[u8; 16]>) -> Message<&'a [u8]> {
+        let mut request = [0; 512];
+        let request = query(&mut request, b"\x07captive\x07example\x00", qtype);
+
+        let len = reply_with_ipv6(request, &IP, ipv6, Duration::from_secs(60), buf).unwrap();
+
+        let message = Message::from_octets(&buf[..len]).unwrap();
+        assert_eq!(message.header().id(), 42);
+        assert!(message.header().qr());
+
+        message
+    }
+
+    #[test]
+    fn test_is_subdomain() {
+        let name = Name::from_slice(b"\x06update\x07Example\x03com\x00").unwrap();
+
+        assert!(is_subdomain(name, "update.example.com"));
+        assert!(is_subdomain(name, "example.com"));
+        assert!(is_subdomain(name, "EXAMPLE.COM."));
+        assert!(is_subdomain(name, "com"));
+
+        assert!(!is_subdomain(name, "pdate.example.com"));
+        assert!(!is_subdomain(name, "www.update.example.com"));
+        assert!(!is_subdomain(name, "example.org"));
+
+        // Empty suffixes match nothing
+        assert!(!is_subdomain(name, ""));
+        assert!(!is_subdomain(name, "."));
+        assert!(!is_subdomain(name, "..."));
+        assert!(!is_subdomain(Name::root_slice(), ""));
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let mut buf = [0; 512];
+        let request = query(&mut buf, b"\x07captive\x07example\x00", Rtype::A);
+
+        assert!(is_allowed(request, &["example"]).unwrap());
+        assert!(is_allowed(request, &["other", "captive.example"]).unwrap());
+
+        assert!(!is_allowed(request, &[]).unwrap());
+        assert!(!is_allowed(request, &["", "."]).unwrap());
+    }
+
+    #[test]
+    fn test_reply_a() {
+        let mut buf = [0; 512];
+        let message = reply(&mut buf, Rtype::A, None);
+
+        assert_eq!(message.header().rcode(), Rcode::NOERROR);
+
+        let answers = message
+            .answer()
+            .unwrap()
+            .limit_to::<A>()
+            .map(|record| record.unwrap().data().addr().octets());
+        assert!(answers.eq([IP]));
+    }
+
+    #[test]
+    fn test_reply_aaaa() {
+        let mut buf = [0; 512];
+        let message = reply(&mut buf, Rtype::AAAA, Some(&IPV6));
+
+        assert_eq!(message.header().rcode(), Rcode::NOERROR);
+
+        let answers = message
+            .answer()
+            .unwrap()
+            .limit_to::<Aaaa>()
+            .map(|record| record.unwrap().data().addr().octets());
+        assert!(answers.eq([IPV6]));
+
+        // Without an IPv6 address, the name exists but has no AAAA record
+        let mut buf = [0; 512];
+        let message = reply(&mut buf, Rtype::AAAA, None);
+
+        assert_eq!(message.header().rcode(), Rcode::NOERROR);
+        assert_eq!(message.header_counts().ancount(), 0);
+    }
+}
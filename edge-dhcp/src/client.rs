@@ -39,6 +39,18 @@ where
         self.bootp_request(secs, None, broadcast, Options::request(ip, opt_buf))
     }
 
+    /// Create a request renewing (`broadcast` = false) or rebinding (`broadcast` = true)
+    /// the lease of the provided IP.
+    pub fn renew<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        secs: u16,
+        ip: Ipv4Addr,
+        broadcast: bool,
+    ) -> (Packet<'o>, u32) {
+        self.bootp_request(secs, Some(ip), broadcast, Options::renew(opt_buf))
+    }
+
     pub fn release<'o>(
         &mut self,
         opt_buf: &'o mut [DhcpOption<'o>],
@@ -24,6 +24,20 @@ pub struct NetworkInfo<'a> {
     pub captive_url: Option<&'a str>,
 }
 
+/// The state of a DHCP IP lease, as per RFC 2131.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LeaseState {
+    /// The lease is valid and does not need to be renewed yet (before T1)
+    Bound,
+    /// The lease is being renewed with the DHCP server which granted it (between T1 and T2)
+    Renewing,
+    /// The lease is being rebound with any DHCP server, as the one which granted it did not reply (between T2 and the lease expiry)
+    Rebinding,
+    /// The lease had expired or was revoked by the DHCP server, so the IP should no longer be used
+    Expired,
+}
+
 /// Represents a DHCP IP lease.
 ///
 /// This structure has a set of asynchronous methods that can utilize a supplied DHCP client instance and UDP socket to
@@ -99,6 +113,9 @@ impl Lease {
     }
 
     /// Keeps the DHCP lease up to date by renewing it when necessary using the supplied DHCP client instance and UDP socket.
+    ///
+    /// Returns once the lease had expired or was revoked by the DHCP server, so that a new one could be acquired with `Lease::new`.
+    /// Use `maintain` instead if the intermediate lease states are of interest.
     pub async fn keep<T, S>(
         &mut self,
         client: &mut dhcp::client::Client<T>,
@@ -109,23 +126,100 @@ impl Lease {
         T: Rng,
         S: UdpReceive + UdpSend,
     {
+        while self.maintain(client, socket, buf).await? != LeaseState::Expired {}
+
+        Ok(())
+    }
+
+    /// Drives the DHCP lease until its next state transition using the supplied DHCP client instance and UDP socket,
+    /// and returns the new state.
+    ///
+    /// - In the `Bound` state, waits until the renewal time (T1)
+    /// - In the `Renewing` state, sends requests to the DHCP server which granted the lease until the lease
+    ///   is renewed, or until the rebinding time (T2)
+    /// - In the `Rebinding` state, broadcasts requests until any DHCP server renews the lease, or until the lease expires
+    ///
+    /// As per RFC 2131, unanswered requests are retransmitted after half of the remaining time until T2
+    /// (or until the lease expiry), but no sooner than 60 seconds.
+    ///
+    /// The method is meant to be called in a loop, and once it returns `LeaseState::Expired`,
+    /// a new lease should be acquired with `Lease::new`.
+    pub async fn maintain<T, S>(
+        &mut self,
+        client: &mut dhcp::client::Client<T>,
+        socket: &mut S,
+        buf: &mut [u8],
+    ) -> Result<LeaseState, Error<S::Error>>
+    where
+        T: Rng,
+        S: UdpReceive + UdpSend,
+    {
+        let state = self.state();
+
+        let deadline = match state {
+            LeaseState::Bound => {
+                Timer::at(self.renewal_time()).await;
+
+                return Ok(self.transition(state));
+            }
+            LeaseState::Renewing => self.rebinding_time(),
+            LeaseState::Rebinding => self.expiry_time(),
+            LeaseState::Expired => return Ok(state),
+        };
+
         loop {
-            let now = Instant::now();
+            if self
+                .refresh(client, socket, buf, state == LeaseState::Rebinding)
+                .await?
+                .is_some()
+            {
+                return Ok(self.transition(state));
+            }
 
-            if now - self.acquired >= self.duration / 3 {
-                if !self.renew(client, socket, buf).await? {
-                    // Lease was not renewed; let the user know
-                    break;
-                }
-            } else {
-                Timer::after(Duration::from_secs(60)).await;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(self.transition(state));
             }
+
+            let retransmit = now + ((deadline - now) / 2).max(Duration::from_secs(60));
+
+            Timer::at(retransmit.min(deadline)).await;
         }
+    }
 
-        Ok(())
+    /// Returns the current state of the lease.
+    pub fn state(&self) -> LeaseState {
+        let now = Instant::now();
+
+        if now < self.renewal_time() {
+            LeaseState::Bound
+        } else if now < self.rebinding_time() {
+            LeaseState::Renewing
+        } else if now < self.expiry_time() {
+            LeaseState::Rebinding
+        } else {
+            LeaseState::Expired
+        }
+    }
+
+    /// Returns the instant when the lease should be renewed with the DHCP server which granted it (T1, 50% of the lease duration).
+    pub fn renewal_time(&self) -> Instant {
+        self.acquired + self.duration / 2
+    }
+
+    /// Returns the instant when the lease should be rebound with any DHCP server (T2, 87.5% of the lease duration).
+    pub fn rebinding_time(&self) -> Instant {
+        self.acquired + self.duration / 8 * 7
+    }
+
+    /// Returns the instant when the lease expires.
+    pub fn expiry_time(&self) -> Instant {
+        self.acquired + self.duration
     }
 
     /// Renews the DHCP lease by utilizing the supplied DHCP client instance and UDP socket.
+    ///
+    /// Returns `false` if the DHCP server did not reply, or if it revoked the lease.
     pub async fn renew<T, S>(
         &mut self,
         client: &mut dhcp::client::Client<T>,
@@ -136,30 +230,15 @@ impl Lease {
         T: Rng,
         S: UdpReceive + UdpSend,
     {
-        info!("Renewing DHCP lease...");
-
-        let now = Instant::now();
-        let settings = Self::request(
-            client,
-            socket,
-            buf,
-            self.server_ip,
-            self.ip,
-            false,
-            Duration::from_secs(3),
-            3,
-        )
-        .await?;
-
-        if let Some(settings) = settings.as_ref() {
-            self.duration = settings
-                .lease_time_secs
-                .map(|lt| Duration::from_secs(lt as _))
-                .unwrap_or(self.duration);
-            self.acquired = now;
+        for _ in 0..3 {
+            if let Some(renewed) = self.refresh(client, socket, buf, false).await? {
+                return Ok(renewed);
+            }
         }
 
-        Ok(settings.is_some())
+        warn!("Lease renewal was not replied");
+
+        Ok(false)
     }
 
     /// Releases the DHCP lease by utilizing the supplied DHCP client instance and UDP socket.
@@ -316,6 +395,105 @@ impl Lease {
         Ok(None)
     }
 
+    /// Sends a single request renewing (`broadcast` = false) or rebinding (`broadcast` = true) the lease,
+    /// and waits up to 3 seconds for its reply, skipping any other packets received in the meantime.
+    ///
+    /// Returns `Some(true)` if the lease was renewed, `Some(false)` if it was revoked
+    /// and `None` if no reply was received.
+    async fn refresh<T, S>(
+        &mut self,
+        client: &mut dhcp::client::Client<T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        broadcast: bool,
+    ) -> Result<Option<bool>, Error<S::Error>>
+    where
+        T: Rng,
+        S: UdpReceive + UdpSend,
+    {
+        if broadcast {
+            info!("Rebinding DHCP lease of IP {}...", self.ip);
+        } else {
+            info!(
+                "Renewing DHCP lease of IP {} with DHCP server {}...",
+                self.ip, self.server_ip
+            );
+        }
+
+        let now = Instant::now();
+
+        let mut opt_buf = Options::buf();
+        let (request, xid) = client.renew(&mut opt_buf, 0, self.ip, broadcast);
+
+        socket
+            .send(
+                SocketAddr::V4(SocketAddrV4::new(
+                    if broadcast {
+                        Ipv4Addr::BROADCAST
+                    } else {
+                        self.server_ip
+                    },
+                    DEFAULT_SERVER_PORT,
+                )),
+                request.encode(buf)?,
+            )
+            .await
+            .map_err(Error::Io)?;
+
+        let deadline = now + Duration::from_secs(3);
+
+        loop {
+            let Either::First(result) = select(socket.receive(buf), Timer::at(deadline)).await
+            else {
+                return Ok(None);
+            };
+
+            let (len, _remote) = result.map_err(Error::Io)?;
+
+            // Replies to other clients (or to an earlier request of ours, with another XID) and stray packets
+            // may arrive before the reply, so keep waiting for it
+            let reply = match Packet::decode(&buf[..len]) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    debug!("Ignoring a malformed DHCP packet: {}", e);
+                    continue;
+                }
+            };
+
+            if client.is_ack(&reply, xid) {
+                let settings = Settings::new(&reply);
+
+                self.server_ip = settings.server_ip.unwrap_or(self.server_ip);
+                self.duration = settings
+                    .lease_time_secs
+                    .map(|lt| Duration::from_secs(lt as _))
+                    .unwrap_or(self.duration);
+                self.acquired = now;
+
+                info!("DHCP lease of IP {} renewed", self.ip);
+
+                return Ok(Some(true));
+            } else if client.is_nak(&reply, xid) {
+                // Revoke the lease by expiring it immediately
+                self.duration = Duration::MIN;
+
+                warn!("DHCP lease of IP {} revoked", self.ip);
+
+                return Ok(Some(false));
+            }
+        }
+    }
+
+    fn transition(&self, from: LeaseState) -> LeaseState {
+        let state = self.state();
+
+        if state != from {
+            info!("DHCP lease of IP {} is now {:?}", self.ip, state);
+        }
+
+        state
+    }
+
     // Useful when Rust's borrow-checker still cannot handle some NLLs
     // https://rust-lang.github.io/rfcs/2094-nll.html
     unsafe fn unsafe_reborrow<'a>(buf: &mut [u8]) -> &'a mut [u8] {
@@ -323,3 +501,149 @@ impl Lease {
         unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr(), len) }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+    use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use core::pin::pin;
+
+    use edge_nal::io::ErrorType;
+    use edge_nal::{UdpReceive, UdpSend};
+
+    use embassy_time::{Duration, Instant};
+
+    use crate::{MessageType, Options, Packet};
+
+    use super::{dhcp, Lease, LeaseState, DEFAULT_SERVER_PORT};
+
+    const MAC: [u8; 6] = [2, 0, 0, 0, 0, 1];
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 10);
+    const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+
+    struct Counter(u32);
+
+    impl rand_core::TryRng for Counter {
+        type Error = Infallible;
+
+        fn try_next_u32(&mut self) -> Result<u32, Self::Error> {
+            self.0 += 1;
+
+            Ok(self.0)
+        }
+
+        fn try_next_u64(&mut self) -> Result<u64, Self::Error> {
+            self.try_next_u32().map(u64::from)
+        }
+
+        fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), Self::Error> {
+            dst.fill(0);
+
+            Ok(())
+        }
+    }
+
+    /// A socket answering each request with a stray packet, a reply to another transaction,
+    /// and only then with the reply to the request
+    #[derive(Default)]
+    struct StubSocket {
+        replies: heapless::Deque<heapless::Vec<u8, 576>, 4>,
+        requests: usize,
+    }
+
+    impl ErrorType for StubSocket {
+        type Error = Infallible;
+    }
+
+    impl UdpSend for StubSocket {
+        async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+            assert_eq!(
+                remote,
+                SocketAddr::V4(SocketAddrV4::new(SERVER_IP, DEFAULT_SERVER_PORT))
+            );
+
+            self.requests += 1;
+
+            let request = unwrap!(Packet::decode(data));
+
+            let mut opt_buf = Options::buf();
+            let mut reply = request.new_reply(
+                Some(IP),
+                request.options.reply(
+                    MessageType::Ack,
+                    SERVER_IP,
+                    7200,
+                    &[],
+                    None,
+                    &[],
+                    None,
+                    &mut opt_buf,
+                ),
+            );
+
+            let mut push = |packet: &[u8]| {
+                unwrap!(self
+                    .replies
+                    .push_back(unwrap!(heapless::Vec::from_slice(packet))));
+            };
+
+            let mut buf = [0; 576];
+
+            push(b"not a DHCP packet");
+
+            reply.xid = request.xid.wrapping_add(1);
+            push(unwrap!(reply.encode(&mut buf)));
+
+            reply.xid = request.xid;
+            push(unwrap!(reply.encode(&mut buf)));
+
+            Ok(())
+        }
+    }
+
+    impl UdpReceive for StubSocket {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            let Some(packet) = self.replies.pop_front() else {
+                return core::future::pending().await;
+            };
+
+            buffer[..packet.len()].copy_from_slice(&packet);
+
+            Ok((
+                packet.len(),
+                SocketAddr::V4(SocketAddrV4::new(SERVER_IP, DEFAULT_SERVER_PORT)),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_maintain_skips_other_packets() {
+        embassy_futures::block_on(async {
+            let mut client = dhcp::client::Client::new(Counter(0), MAC);
+            let mut socket = StubSocket::default();
+            let mut buf = [0; 576];
+
+            let mut lease = Lease {
+                ip: IP,
+                server_ip: SERVER_IP,
+                duration: Duration::from_millis(200),
+                acquired: Instant::now(),
+            };
+
+            assert_eq!(
+                unwrap!(pin!(lease.maintain(&mut client, &mut socket, &mut buf)).await),
+                LeaseState::Renewing
+            );
+            assert_eq!(socket.requests, 0);
+
+            // The lease is renewed by the reply to the request, even though it arrives last
+            assert_eq!(
+                unwrap!(pin!(lease.maintain(&mut client, &mut socket, &mut buf)).await),
+                LeaseState::Bound
+            );
+            assert_eq!(socket.requests, 1);
+            assert!(socket.replies.is_empty());
+            assert_eq!(lease.duration, Duration::from_secs(7200));
+        })
+    }
+}
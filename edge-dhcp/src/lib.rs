@@ -336,10 +336,21 @@ pub struct Settings<'a> {
     pub dns1: Option<Ipv4Addr>,
     pub dns2: Option<Ipv4Addr>,
     pub captive_url: Option<&'a str>,
+    /// All routers offered by the server, in order of preference (`gateway` is the first one)
+    pub routers: Ipv4Addrs<'a>,
+    /// All DNS servers offered by the server, in order of preference (`dns1` and `dns2` are the first two)
+    pub dns: Ipv4Addrs<'a>,
+    /// The NTP servers offered by the server, in order of preference
+    pub ntp: Ipv4Addrs<'a>,
+    /// The domain name of the network, to be used when resolving hostnames via DNS
+    pub domain_name: Option<&'a str>,
 }
 
 impl<'a> Settings<'a> {
     pub fn new(packet: &Packet<'a>) -> Self {
+        let routers = Self::ipv4_addrs(packet, DhcpOption::CODE_ROUTER);
+        let dns = Self::ipv4_addrs(packet, DhcpOption::CODE_DNS);
+
         Self {
             ip: packet.yiaddr,
             server_ip: packet.options.iter().find_map(|option| {
@@ -356,13 +367,7 @@ impl<'a> Settings<'a> {
                     None
                 }
             }),
-            gateway: packet.options.iter().find_map(|option| {
-                if let DhcpOption::Router(ips) = option {
-                    ips.iter().next()
-                } else {
-                    None
-                }
-            }),
+            gateway: routers.iter().next(),
             subnet: packet.options.iter().find_map(|option| {
                 if let DhcpOption::SubnetMask(subnet) = option {
                     Some(subnet)
@@ -370,29 +375,46 @@ impl<'a> Settings<'a> {
                     None
                 }
             }),
-            dns1: packet.options.iter().find_map(|option| {
-                if let DhcpOption::DomainNameServer(ips) = option {
-                    ips.iter().next()
-                } else {
-                    None
-                }
-            }),
-            dns2: packet.options.iter().find_map(|option| {
-                if let DhcpOption::DomainNameServer(ips) = option {
-                    ips.iter().nth(1)
+            dns1: dns.iter().next(),
+            dns2: dns.iter().nth(1),
+            captive_url: packet.options.iter().find_map(|option| {
+                if let DhcpOption::CaptiveUrl(url) = option {
+                    Some(url)
                 } else {
                     None
                 }
             }),
-            captive_url: packet.options.iter().find_map(|option| {
-                if let DhcpOption::CaptiveUrl(url) = option {
-                    Some(url)
+            routers,
+            dns,
+            ntp: Self::ipv4_addrs(packet, DhcpOption::CODE_NTP),
+            domain_name: packet.options.iter().find_map(|option| {
+                if let DhcpOption::DomainName(name) = option {
+                    Some(name)
                 } else {
                     None
                 }
             }),
         }
     }
+
+    /// Return the addresses of the first option with the provided code, among the options
+    /// carrying a list of addresses, or an empty list if the packet has no such option
+    fn ipv4_addrs(packet: &Packet<'a>, code: u8) -> Ipv4Addrs<'a> {
+        packet
+            .options
+            .iter()
+            .find_map(|option| match option {
+                DhcpOption::Router(ips)
+                | DhcpOption::DomainNameServer(ips)
+                | DhcpOption::NtpServer(ips)
+                    if option.code() == code =>
+                {
+                    Some(ips)
+                }
+                _ => None,
+            })
+            .unwrap_or(Ipv4Addrs::new(&[]))
+    }
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -404,6 +426,8 @@ impl<'a> Options<'a> {
         DhcpOption::CODE_ROUTER,
         DhcpOption::CODE_SUBNET,
         DhcpOption::CODE_DNS,
+        DhcpOption::CODE_DOMAIN_NAME,
+        DhcpOption::CODE_NTP,
     ];
 
     pub const fn new(options: &'a [DhcpOption<'a>]) -> Self {
@@ -436,6 +460,16 @@ impl<'a> Options<'a> {
         Self::new(&buf[..3])
     }
 
+    /// Options of a request renewing or rebinding an existing lease
+    ///
+    /// As per RFC 2131, these do not contain the requested IP address, as the leased IP is in `ciaddr`.
+    pub fn renew(buf: &'a mut [DhcpOption<'a>]) -> Self {
+        buf[0] = DhcpOption::MessageType(MessageType::Request);
+        buf[1] = DhcpOption::ParameterRequestList(Self::REQUEST_PARAMS);
+
+        Self::new(&buf[..2])
+    }
+
     pub fn release(buf: &'a mut [DhcpOption<'a>]) -> Self {
         buf[0] = DhcpOption::MessageType(MessageType::Release);
 
@@ -615,6 +649,10 @@ pub enum DhcpOption<'a> {
     Router(Ipv4Addrs<'a>),
     /// 6: Domain Name Server Option
     DomainNameServer(Ipv4Addrs<'a>),
+    /// 15: Domain Name
+    DomainName(&'a str),
+    /// 42: Network Time Protocol Servers Option
+    NtpServer(Ipv4Addrs<'a>),
     /// 51: IP Address Lease Time
     IpAddressLeaseTime(u32),
     /// 1: Subnet Mask
@@ -636,6 +674,8 @@ impl DhcpOption<'_> {
     pub const CODE_DNS: u8 = DhcpOption::DomainNameServer(Ipv4Addrs::new(&[])).code();
     pub const CODE_SUBNET: u8 = DhcpOption::SubnetMask(Ipv4Addr::new(0, 0, 0, 0)).code();
     pub const CODE_CAPTIVE_URL: u8 = DhcpOption::CaptiveUrl("").code();
+    pub const CODE_DOMAIN_NAME: u8 = DhcpOption::DomainName("").code();
+    pub const CODE_NTP: u8 = DhcpOption::NtpServer(Ipv4Addrs::new(&[])).code();
 
     fn decode<'o>(bytes: &mut BytesIn<'o>) -> Result<Option<DhcpOption<'o>>, Error> {
         let code = bytes.byte()?;
@@ -663,12 +703,19 @@ impl DhcpOption<'_> {
                 MAXIMUM_DHCP_MESSAGE_SIZE => {
                     DhcpOption::MaximumMessageSize(u16::from_be_bytes(bytes.remaining_arr()?))
                 }
-                ROUTER => {
-                    DhcpOption::Router(Ipv4Addrs(Ipv4AddrsInner::ByteSlice(bytes.remaining())))
+                ROUTER => Ipv4Addrs::decode(code, bytes.remaining(), DhcpOption::Router),
+                DOMAIN_NAME_SERVER => {
+                    Ipv4Addrs::decode(code, bytes.remaining(), DhcpOption::DomainNameServer)
+                }
+                // A domain name which is not UTF-8 is skipped rather than the whole packet rejected
+                DOMAIN_NAME => {
+                    let data = bytes.remaining();
+
+                    core::str::from_utf8(data)
+                        .map(DhcpOption::DomainName)
+                        .unwrap_or(DhcpOption::Unrecognized(code, data))
                 }
-                DOMAIN_NAME_SERVER => DhcpOption::DomainNameServer(Ipv4Addrs(
-                    Ipv4AddrsInner::ByteSlice(bytes.remaining()),
-                )),
+                NTP_SERVERS => Ipv4Addrs::decode(code, bytes.remaining(), DhcpOption::NtpServer),
                 IP_ADDRESS_LEASE_TIME => {
                     DhcpOption::IpAddressLeaseTime(u32::from_be_bytes(bytes.remaining_arr()?))
                 }
@@ -683,7 +730,7 @@ impl DhcpOption<'_> {
 
                     DhcpOption::ClientIdentifier(bytes.remaining())
                 }
-                CAPTIVE_URL => DhcpOption::HostName(
+                CAPTIVE_URL => DhcpOption::CaptiveUrl(
                     core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
                 ),
                 _ => DhcpOption::Unrecognized(code, bytes.remaining()),
@@ -696,8 +743,17 @@ impl DhcpOption<'_> {
     fn encode(&self, out: &mut BytesOut) -> Result<(), Error> {
         out.byte(self.code())?;
 
+        // The data of an option carrying a list of addresses comes in several pieces
+        let mut len = 0;
+        self.data(|data| {
+            len += data.len();
+
+            Ok(())
+        })?;
+
+        out.byte(len as _)?;
+
         self.data(|data| {
-            out.byte(data.len() as _)?;
             out.push(data)?;
 
             Ok(())
@@ -713,6 +769,8 @@ impl DhcpOption<'_> {
             Self::HostName(_) => HOST_NAME,
             Self::Router(_) => ROUTER,
             Self::DomainNameServer(_) => DOMAIN_NAME_SERVER,
+            Self::DomainName(_) => DOMAIN_NAME,
+            Self::NtpServer(_) => NTP_SERVERS,
             Self::IpAddressLeaseTime(_) => IP_ADDRESS_LEASE_TIME,
             Self::SubnetMask(_) => SUBNET_MASK,
             Self::MaximumMessageSize(_) => MAXIMUM_DHCP_MESSAGE_SIZE,
@@ -730,7 +788,8 @@ impl DhcpOption<'_> {
             Self::ParameterRequestList(prl) => f(prl),
             Self::RequestedIpAddress(addr) => f(&addr.octets()),
             Self::HostName(name) => f(name.as_bytes()),
-            Self::Router(addrs) | Self::DomainNameServer(addrs) => {
+            Self::DomainName(name) => f(name.as_bytes()),
+            Self::Router(addrs) | Self::DomainNameServer(addrs) | Self::NtpServer(addrs) => {
                 for addr in addrs.iter() {
                     f(&addr.octets())?;
                 }
@@ -760,6 +819,22 @@ impl<'a> Ipv4Addrs<'a> {
     pub fn iter(&self) -> impl Iterator<Item = Ipv4Addr> + 'a {
         self.0.iter()
     }
+
+    /// Decode the data of an option carrying a list of addresses
+    ///
+    /// A malformed list (i.e. with a length which is not a multiple of 4) is returned as
+    /// `DhcpOption::Unrecognized`, so that the option is skipped rather than the whole packet rejected.
+    fn decode(
+        code: u8,
+        data: &'a [u8],
+        option: impl FnOnce(Self) -> DhcpOption<'a>,
+    ) -> DhcpOption<'a> {
+        if data.len().is_multiple_of(4) {
+            option(Self(Ipv4AddrsInner::ByteSlice(data)))
+        } else {
+            DhcpOption::Unrecognized(code, data)
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -809,6 +884,8 @@ const SUBNET_MASK: u8 = 1;
 const ROUTER: u8 = 3;
 const DOMAIN_NAME_SERVER: u8 = 6;
 const HOST_NAME: u8 = 12;
+const DOMAIN_NAME: u8 = 15;
+const NTP_SERVERS: u8 = 42;
 
 // DHCP Extensions
 const REQUESTED_IP_ADDRESS: u8 = 50;
@@ -820,3 +897,133 @@ const MESSAGE: u8 = 56;
 const MAXIMUM_DHCP_MESSAGE_SIZE: u8 = 57;
 const CLIENT_IDENTIFIER: u8 = 61;
 const CAPTIVE_URL: u8 = 114;
+
+#[cfg(test)]
+mod test {
+    use core::net::Ipv4Addr;
+
+    use super::*;
+
+    const MAC: [u8; 6] = [2, 0, 0, 0, 0, 1];
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 10);
+
+    /// Encode a reply with the provided options, and decode it back
+    fn roundtrip<'a>(options: &[DhcpOption<'_>], buf: &'a mut [u8]) -> Packet<'a> {
+        let mut packet = Packet::new_request(MAC, 1, 0, None, false, Options::new(options));
+        packet.reply = true;
+        packet.yiaddr = IP;
+
+        let len = unwrap!(packet.encode(buf)).len();
+
+        unwrap!(Packet::decode(&buf[..len]))
+    }
+
+    #[test]
+    fn test_decode_options() {
+        let routers = [Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2)];
+        let ntp = [Ipv4Addr::new(10, 0, 0, 123)];
+
+        let mut buf = [0; 576];
+        let packet = roundtrip(
+            &[
+                DhcpOption::MessageType(MessageType::Ack),
+                DhcpOption::Router(Ipv4Addrs::new(&routers)),
+                DhcpOption::NtpServer(Ipv4Addrs::new(&ntp)),
+                DhcpOption::DomainName("example.com"),
+            ],
+            &mut buf,
+        );
+
+        let mut options = packet.options.iter();
+
+        assert_eq!(
+            options.next(),
+            Some(DhcpOption::MessageType(MessageType::Ack))
+        );
+        assert!(matches!(
+            options.next(),
+            Some(DhcpOption::Router(ips)) if ips.iter().eq(routers)
+        ));
+        assert!(matches!(
+            options.next(),
+            Some(DhcpOption::NtpServer(ips)) if ips.iter().eq(ntp)
+        ));
+        assert_eq!(options.next(), Some(DhcpOption::DomainName("example.com")));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_decode_malformed_options() {
+        let mut buf = [0; 576];
+        let packet = roundtrip(
+            &[
+                DhcpOption::MessageType(MessageType::Ack),
+                DhcpOption::Unrecognized(DhcpOption::CODE_ROUTER, &[192, 168, 0]),
+                DhcpOption::Unrecognized(DhcpOption::CODE_DOMAIN_NAME, b"exa\xffmple"),
+            ],
+            &mut buf,
+        );
+
+        // The malformed options are skipped, rather than the whole packet rejected
+        assert!(packet.options.iter().eq([
+            DhcpOption::MessageType(MessageType::Ack),
+            DhcpOption::Unrecognized(DhcpOption::CODE_ROUTER, &[192, 168, 0]),
+            DhcpOption::Unrecognized(DhcpOption::CODE_DOMAIN_NAME, b"exa\xffmple"),
+        ]));
+
+        let settings = Settings::new(&packet);
+
+        assert_eq!(settings.gateway, None);
+        assert_eq!(settings.routers.iter().next(), None);
+        assert_eq!(settings.domain_name, None);
+    }
+
+    #[test]
+    fn test_settings() {
+        let routers = [Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2)];
+        let dns = [
+            Ipv4Addr::new(1, 1, 1, 1),
+            Ipv4Addr::new(8, 8, 8, 8),
+            Ipv4Addr::new(9, 9, 9, 9),
+        ];
+        let ntp = [Ipv4Addr::new(10, 0, 0, 123)];
+
+        let mut buf = [0; 576];
+        let packet = roundtrip(
+            &[
+                DhcpOption::MessageType(MessageType::Ack),
+                DhcpOption::Router(Ipv4Addrs::new(&routers)),
+                DhcpOption::DomainNameServer(Ipv4Addrs::new(&dns)),
+                DhcpOption::NtpServer(Ipv4Addrs::new(&ntp)),
+                DhcpOption::DomainName("example.com"),
+                DhcpOption::CaptiveUrl("http://portal.example.com"),
+            ],
+            &mut buf,
+        );
+
+        let settings = Settings::new(&packet);
+
+        assert_eq!(settings.ip, IP);
+        assert_eq!(settings.gateway, Some(routers[0]));
+        assert!(settings.routers.iter().eq(routers));
+        assert_eq!(settings.dns1, Some(dns[0]));
+        assert_eq!(settings.dns2, Some(dns[1]));
+        assert!(settings.dns.iter().eq(dns));
+        assert!(settings.ntp.iter().eq(ntp));
+        assert_eq!(settings.domain_name, Some("example.com"));
+        assert_eq!(settings.captive_url, Some("http://portal.example.com"));
+
+        // Without the options, the settings are empty
+        let mut buf = [0; 576];
+        let packet = roundtrip(&[DhcpOption::MessageType(MessageType::Ack)], &mut buf);
+
+        let settings = Settings::new(&packet);
+
+        assert_eq!(settings.gateway, None);
+        assert_eq!(settings.dns1, None);
+        assert_eq!(settings.routers.iter().next(), None);
+        assert_eq!(settings.dns.iter().next(), None);
+        assert_eq!(settings.ntp.iter().next(), None);
+        assert_eq!(settings.domain_name, None);
+    }
+}
@@ -13,7 +13,12 @@ use crate::{
 };
 
 pub mod client;
+#[cfg(feature = "compression")]
+pub mod inflate;
+pub mod multipart;
 pub mod server;
+#[cfg(test)]
+mod testing;
 
 /// An error in parsing the headers or the body.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -144,16 +149,33 @@ impl<E> core::error::Error for Error<E> where E: core::error::Error {}
 impl<'b, const N: usize> RequestHeaders<'b, N> {
     /// Parse the headers from the input stream
     pub async fn receive<R>(
+        &mut self,
+        buf: &'b mut [u8],
+        input: R,
+        exact: bool,
+    ) -> Result<(&'b mut [u8], usize), Error<R::Error>>
+    where
+        R: Read,
+    {
+        self.receive_limited(buf, input, exact, usize::MAX).await
+    }
+
+    /// Same as `receive`, but fails with `Error::TooLongHeaders` when the headers section
+    /// (the request line included) is longer than `max_len` bytes, even if it would fit in `buf`
+    pub(crate) async fn receive_limited<R>(
         &mut self,
         buf: &'b mut [u8],
         mut input: R,
         exact: bool,
+        max_len: usize,
     ) -> Result<(&'b mut [u8], usize), Error<R::Error>>
     where
         R: Read,
     {
+        let limit = max_len.min(buf.len());
+
         let (read_len, headers_len) =
-            match raw::read_reply_buf::<N, _>(&mut input, buf, true, exact).await {
+            match raw::read_reply_buf::<N, _>(&mut input, &mut buf[..limit], true, exact).await {
                 Ok(read_len) => read_len,
                 Err(e) => return Err(e),
             };
@@ -259,8 +281,17 @@ impl<'b, const N: usize> ResponseHeaders<'b, N> {
         &self,
         request_connection_type: ConnectionType,
     ) -> Result<(ConnectionType, BodyType), Error<E>> {
+        // An HTTP/1.0 server closes the connection unless it explicitly says otherwise,
+        // even if the request asked for Keep-Alive
+        let carry_over_connection_type =
+            if self.http11 || ConnectionType::from_headers(self.headers.iter()).is_some() {
+                request_connection_type
+            } else {
+                ConnectionType::Close
+            };
+
         self.headers
-            .resolve::<E>(Some(request_connection_type), false, self.http11)
+            .resolve::<E>(Some(carry_over_connection_type), false, self.http11)
     }
 
     /// Send the headers to the output stream, returning the connection type and body type
@@ -470,6 +501,10 @@ pub enum Body<'b, R> {
     /// The body is raw and should be read as is (only possible for HTTP responses with connection = Close)
     Raw(PartiallyRead<'b, R>),
     /// The body is of a known length (Content-Length)
+    ///
+    /// Reads return EOF exactly at the declared length, and never consume bytes past it,
+    /// as these belong to the next request of a keep-alive connection.
+    /// If the peer closes the connection before sending the whole body, reads fail with `Error::IncompleteBody`.
     ContentLen(ContentLenRead<PartiallyRead<'b, R>>),
     /// The body is chunked (Transfer-Encoding: chunked)
     Chunked(ChunkedRead<'b, PartiallyRead<'b, R>>),
@@ -650,6 +685,11 @@ where
                 .read(&mut buf[..len as _])
                 .await
                 .map_err(Error::Io)?;
+            if read == 0 {
+                // The peer closed the connection before sending the whole body
+                Err(Error::IncompleteBody)?;
+            }
+
             self.read_len += read as u64;
 
             Ok(read)
@@ -929,6 +969,9 @@ where
     }
 
     /// Finish writing the body (necessary for chunked encoding)
+    ///
+    /// For chunked bodies, this emits the terminating zero-length chunk. Note that `flush` does NOT
+    /// do that, as flushing is also useful in the middle of a streamed body.
     pub async fn finish(&mut self) -> Result<(), Error<W::Error>>
     where
         W: Write,
@@ -1303,26 +1346,12 @@ mod raw {
 
 #[cfg(test)]
 mod test {
-    use embedded_io_async::{ErrorType, Read};
-
-    use super::*;
+    use core::pin::pin;
 
-    struct SliceRead<'a>(&'a [u8]);
-
-    impl<'a> ErrorType for SliceRead<'a> {
-        type Error = core::convert::Infallible;
-    }
-
-    impl<'a> Read for SliceRead<'a> {
-        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-            let len = core::cmp::min(buf.len(), self.0.len());
-            buf[..len].copy_from_slice(&self.0[..len]);
-
-            self.0 = &self.0[len..];
+    use embedded_io_async::{Read, Write};
 
-            Ok(len)
-        }
-    }
+    use super::testing::{SliceRead, VecWrite};
+    use super::*;
 
     #[test]
     fn test_chunked_bytes() {
@@ -1344,6 +1373,28 @@ mod test {
         expect(b"4\r\nabcdefg", None);
     }
 
+    #[test]
+    fn test_chunked_write() {
+        embassy_futures::block_on(async move {
+            let mut w = SendBody::new(BodyType::Chunked, VecWrite(heapless::Vec::new()));
+
+            unwrap!(w.write_all(b"abcdefghij").await);
+            unwrap!(w.write_all(b"").await);
+            unwrap!(w.write_all(b"42").await);
+            unwrap!(w.flush().await);
+            unwrap!(w.finish().await);
+            unwrap!(w.finish().await);
+
+            assert!(w.write_all(b"x").await.is_err());
+            assert!(!w.needs_close());
+
+            assert_eq!(
+                w.release().0.as_slice(),
+                b"a\r\nabcdefghij\r\n2\r\n42\r\n0\r\n\r\n"
+            );
+        })
+    }
+
     fn expect(input: &[u8], expected: Option<&[u8]>) {
         embassy_futures::block_on(async move {
             let mut buf1 = [0; 64];
@@ -1353,16 +1404,18 @@ mod test {
             let mut r = ChunkedRead::new(stream, &mut buf1, 0);
 
             if let Some(expected) = expected {
-                assert!(r.read_exact(&mut buf2[..expected.len()]).await.is_ok());
+                assert!(pin!(r.read_exact(&mut buf2[..expected.len()]))
+                    .await
+                    .is_ok());
 
                 assert_eq!(&buf2[..expected.len()], expected);
 
-                let len = r.read(&mut buf2).await;
+                let len = pin!(r.read(&mut buf2)).await;
                 assert!(len.is_ok());
 
                 assert_eq!(unwrap!(len), 0);
             } else {
-                assert!(r.read(&mut buf2).await.is_err());
+                assert!(pin!(r.read(&mut buf2)).await.is_err());
             }
         })
     }
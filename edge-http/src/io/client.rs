@@ -1,10 +1,11 @@
+use core::fmt;
 use core::mem;
-use core::net::SocketAddr;
+use core::net::{IpAddr, SocketAddr};
 use core::str;
 
 use embedded_io_async::{ErrorType, Read, Write};
 
-use edge_nal::{Close, TcpConnect, TcpShutdown};
+use edge_nal::{AddrType, Close, Dns, TcpConnect, TcpShutdown};
 
 use crate::{
     ws::{upgrade_request_headers, MAX_BASE64_KEY_LEN, MAX_BASE64_KEY_RESPONSE_LEN, NONCE_LEN},
@@ -17,6 +18,21 @@ use super::Method;
 
 const COMPLETION_BUF_SIZE: usize = 64;
 
+/// The maximum length of a `Location` header value which can be followed when redirected
+pub const MAX_REDIRECT_LOCATION_LEN: usize = 256;
+
+/// The request headers carrying credentials, which are not sent anymore once redirected to another host
+const CREDENTIAL_HEADERS: &[&str] = &["Authorization", "Proxy-Authorization", "Cookie"];
+
+/// The request headers describing the request body, which are not sent anymore once a redirect
+/// switches the method to `GET`
+const BODY_HEADERS: &[&str] = &[
+    "Content-Length",
+    "Content-Type",
+    "Content-Encoding",
+    "Transfer-Encoding",
+];
+
 /// A client connection that can be used to send HTTP requests and receive responses.
 #[allow(private_interfaces)]
 pub enum Connection<'b, T, const N: usize = DEFAULT_MAX_HEADERS_COUNT>
@@ -40,8 +56,21 @@ where
     /// - To add a global request-response timeout, wrap your complete request-response processing
     ///   logic with the `edge_nal::with_timeout` function.
     ///
+    /// The connection does all of its work in the provided buffer, so the memory used by the client
+    /// is fully accounted for by the caller:
+    /// - The response headers section is received and parsed in the buffer, and the headers returned by
+    ///   `headers` borrow from it, so the buffer must fit the largest expected response headers section
+    ///   (the response fails with `Error::TooLongHeaders` otherwise)
+    /// - The remainder of the buffer is used for decoding chunked response bodies, and `post_stream`
+    ///   reads the request body into the whole buffer
+    /// - The request line and headers are written to the socket directly, without being buffered
+    ///
+    /// Apart from the buffer, only small, fixed amounts of memory are used in the futures of the methods,
+    /// i.e. 64 bytes for discarding the unread part of a response body on completion, and two
+    /// `MAX_REDIRECT_LOCATION_LEN` strings as well as `N` header references for following redirects.
+    ///
     /// Parameters:
-    /// - `buf`: A buffer to use for reading and writing data.
+    /// - `buf`: A buffer to use for reading and writing data. It is returned by `release`.
     /// - `socket`: The TCP stack to use for the connection.
     /// - `addr`: The address of the server to connect to.
     pub fn new(buf: &'b mut [u8], socket: &'b T, addr: SocketAddr) -> Self {
@@ -54,9 +83,22 @@ where
     }
 
     /// Reinitialize the connection with a new address.
+    ///
+    /// If the new address is different from the current one, the existing keep-alive
+    /// connection to the old address (if any) is closed.
     pub async fn reinitialize(&mut self, addr: SocketAddr) -> Result<(), Error<T::Error>> {
         let _ = self.complete().await;
-        unwrap!(self.unbound_mut(), "Unreachable").addr = addr;
+
+        let state = unwrap!(self.unbound_mut(), "Unreachable");
+
+        if state.addr != addr {
+            if let Some(mut io) = state.io.take() {
+                let _ = io.close(Close::Both).await;
+                let _ = io.abort().await;
+            }
+
+            state.addr = addr;
+        }
 
         Ok(())
     }
@@ -72,6 +114,227 @@ where
         self.start_request(http11, method, uri, headers).await
     }
 
+    /// Send a `POST` request whose body is streamed from `body`, and wait for its response.
+    ///
+    /// The body is read in pieces into the buffer of the connection, so it never needs to fit in memory
+    /// as a whole (i.e. a firmware image read from flash). If `headers` has a `Content-Length`, the body
+    /// must have exactly that length, otherwise it is sent with chunked encoding.
+    ///
+    /// If reading from `body` or sending the request fails, the connection to the server is aborted,
+    /// so that the server does not take a truncated body for a complete one.
+    ///
+    /// Once the method returns, the connection is in response mode.
+    ///
+    /// Parameters:
+    /// - `uri`: The URI of the request
+    /// - `headers`: The request headers
+    /// - `body`: The source of the request body, read until it returns EOF
+    pub async fn post_stream<R>(
+        &mut self,
+        uri: &str,
+        headers: &[(&str, &str)],
+        mut body: R,
+    ) -> Result<(), StreamError<T::Error, R::Error>>
+    where
+        R: Read,
+    {
+        self.initiate_request(true, Method::Post, uri, headers)
+            .await?;
+
+        let result = async {
+            let request = self.request_mut()?;
+
+            loop {
+                let read = body.read(request.buf).await.map_err(StreamError::Read)?;
+                if read == 0 {
+                    break;
+                }
+
+                request.io.write_all(&request.buf[..read]).await?;
+            }
+
+            request.io.finish().await?;
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            let mut state = self.unbind();
+
+            if let Some(mut io) = state.io.take() {
+                let _ = io.abort().await;
+            }
+
+            *self = Self::Unbound(state);
+
+            return Err(e);
+        }
+
+        self.initiate_response().await?;
+
+        Ok(())
+    }
+
+    /// Initiate an HTTP request without a body and wait for its response, following `3xx` redirects.
+    ///
+    /// Up to `max_redirects` redirects are followed, which also guards against redirect loops.
+    /// The method of the request is preserved for `307` and `308` redirects, switched to `GET` for `303`
+    /// redirects (unless it is `HEAD`), and switched from `POST` to `GET` for `301` and `302` redirects,
+    /// as browsers do.
+    ///
+    /// The `Location` of a redirect can either be an absolute path on the same server, or an absolute
+    /// `http://` URL, in which case its host is resolved with `dns` (unless it is an IP address),
+    /// the connection is re-established to the new server and the `Host` header is replaced.
+    ///
+    /// The credentials of the request (the `Authorization`, `Proxy-Authorization` and `Cookie` headers)
+    /// are not sent anymore once redirected to another host, and the headers describing the request body
+    /// (i.e. `Content-Length` and `Content-Type`) once the method is switched to `GET`.
+    ///
+    /// Once the method returns, the connection is in response mode, with the response which was not
+    /// followed - either because it is not a redirect, because `max_redirects` was reached, or because
+    /// its `Location` cannot be followed (i.e. it is an `https://` URL or its host cannot be resolved).
+    ///
+    /// Parameters:
+    /// - `http11`: Whether to use HTTP/1.1
+    /// - `method`: The HTTP method of the initial request
+    /// - `uri`: The URI of the initial request
+    /// - `headers`: The request headers
+    /// - `max_redirects`: The maximum number of redirects to follow
+    /// - `dns`: The DNS resolver used for the hosts of absolute `Location` URLs
+    pub async fn initiate_request_with_redirects<D>(
+        &mut self,
+        http11: bool,
+        method: Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+        max_redirects: usize,
+        dns: &D,
+    ) -> Result<(), Error<T::Error>>
+    where
+        D: Dns,
+    {
+        self.initiate_request(http11, method, uri, headers).await?;
+        self.initiate_response().await?;
+
+        let mut method = method;
+        let mut location = heapless::String::<MAX_REDIRECT_LOCATION_LEN>::new();
+        let mut host_location = heapless::String::<MAX_REDIRECT_LOCATION_LEN>::new();
+        let mut strip_credentials = false;
+        let mut strip_body = false;
+
+        for _ in 0..max_redirects {
+            let response = self.headers()?;
+
+            let code = response.code;
+            if !matches!(code, 301 | 302 | 303 | 307 | 308) {
+                break;
+            }
+
+            let Some(new_location) = response.headers.get("Location") else {
+                warn!("Redirect response {} without a Location header", code);
+                break;
+            };
+
+            location.clear();
+            if location.push_str(new_location.trim()).is_err() {
+                warn!("Redirect Location is too long: {}", new_location);
+                break;
+            }
+
+            let Some((authority, path)) = split_location(&location) else {
+                warn!("Unsupported redirect Location: {}", location.as_str());
+                break;
+            };
+
+            if let Some(authority) = authority {
+                let Some((host, port)) = split_authority(authority) else {
+                    warn!("Invalid redirect Location: {}", location.as_str());
+                    break;
+                };
+
+                let ip = if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+                    ip
+                } else {
+                    match dns.get_host_by_name(host, AddrType::Either).await {
+                        Ok(ip) => ip,
+                        Err(e) => {
+                            warn!(
+                                "Cannot resolve redirect host {}: {:?}",
+                                host,
+                                debug2format!(e)
+                            );
+                            break;
+                        }
+                    }
+                };
+
+                self.reinitialize(SocketAddr::new(ip, port)).await?;
+
+                let host = if host_location.is_empty() {
+                    headers
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case("Host"))
+                        .map(|(_, value)| *value)
+                } else {
+                    Some(host_location.as_str())
+                };
+
+                // Do not leak the credentials meant for one server to another one
+                strip_credentials |= !host.is_some_and(|host| host.eq_ignore_ascii_case(authority));
+
+                host_location.clear();
+                unwrap!(host_location.push_str(authority));
+            } else {
+                self.complete().await?;
+            }
+
+            let redirect_method = match (code, method) {
+                (303, Method::Head) => Method::Head,
+                (303, _) | (301 | 302, Method::Post) => Method::Get,
+                (_, method) => method,
+            };
+
+            // The body of the request is not sent anymore
+            strip_body |= redirect_method != method && redirect_method == Method::Get;
+            method = redirect_method;
+
+            debug!("Following redirect {} to {}", code, location.as_str());
+
+            if host_location.is_empty() && !strip_body {
+                self.initiate_request(http11, method, path, headers).await?;
+            } else {
+                let is_any = |name: &str, names: &[&str]| {
+                    names.iter().any(|other| name.eq_ignore_ascii_case(other))
+                };
+
+                let mut redirect_headers = heapless::Vec::<_, N>::new();
+
+                for header in headers
+                    .iter()
+                    .copied()
+                    .filter(|(name, _)| {
+                        !(!host_location.is_empty() && name.eq_ignore_ascii_case("Host")
+                            || strip_credentials && is_any(name, CREDENTIAL_HEADERS)
+                            || strip_body && is_any(name, BODY_HEADERS))
+                    })
+                    .chain((!host_location.is_empty()).then_some(("Host", host_location.as_str())))
+                {
+                    redirect_headers
+                        .push(header)
+                        .map_err(|_| Error::TooManyHeaders)?;
+                }
+
+                self.initiate_request(http11, method, path, &redirect_headers)
+                    .await?;
+            }
+
+            self.initiate_response().await?;
+        }
+
+        Ok(())
+    }
+
     /// A utility method to initiate a WebSocket upgrade request.
     pub async fn initiate_ws_upgrade_request(
         &mut self,
@@ -133,6 +396,29 @@ where
         Ok(&response.response)
     }
 
+    /// Return a reader which decompresses the response body, as per its `Content-Encoding` header.
+    ///
+    /// The client does not advertise any compression support by itself: for the server to compress
+    /// the response, the request should contain an `Accept-Encoding` header, e.g.
+    /// `("Accept-Encoding", inflate::ACCEPT_ENCODING)`, and the response body should then be read
+    /// via this method rather than from the connection directly.
+    ///
+    /// Responses without a `Content-Encoding` are read as-is.
+    ///
+    /// The connection must be in response mode.
+    #[cfg(feature = "compression")]
+    pub fn inflate<'a>(
+        &'a mut self,
+        buffers: &'a mut super::inflate::InflateBuffers,
+    ) -> Result<super::inflate::InflateRead<'a, &'a mut Self>, Error<T::Error>> {
+        use super::inflate::{ContentEncoding, InflateRead};
+
+        let encoding = ContentEncoding::from_header(self.headers()?.headers.content_encoding())
+            .ok_or(Error::InvalidBody)?;
+
+        Ok(InflateRead::new(encoding, self, buffers))
+    }
+
     /// Get a mutable reference to the raw connection.
     ///
     /// This can be used to send raw data over the connection.
@@ -140,6 +426,16 @@ where
         Ok(self.io_mut())
     }
 
+    /// Get the address of the server the connection is (or will be) connected to.
+    pub fn addr(&self) -> SocketAddr {
+        match self {
+            Self::Unbound(unbound) => unbound.addr,
+            Self::Request(request) => request.addr,
+            Self::Response(response) => response.addr,
+            _ => unreachable!(),
+        }
+    }
+
     /// Release the connection, returning the raw connection and the buffer.
     pub fn release(mut self) -> (T::Socket<'b>, &'b mut [u8]) {
         let mut state = self.unbind();
@@ -186,7 +482,7 @@ where
 
             let io = unwrap!(state.io.as_mut());
 
-            send_headers(headers, None, true, http11, true, &mut *io).await
+            send_headers(headers.iter(), None, true, http11, true, &mut *io).await
         }
         .await;
 
@@ -430,6 +726,187 @@ where
     }
 }
 
+/// The error returned by `Connection::post_stream`
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StreamError<T, R> {
+    /// Sending the request or receiving the response failed
+    Connection(Error<T>),
+    /// Reading the request body from its source failed
+    Read(R),
+}
+
+impl<T, R> From<Error<T>> for StreamError<T, R> {
+    fn from(e: Error<T>) -> Self {
+        Self::Connection(e)
+    }
+}
+
+impl<T, R> fmt::Display for StreamError<T, R>
+where
+    T: fmt::Display,
+    R: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connection(e) => write!(f, "Connection error: {}", e),
+            Self::Read(e) => write!(f, "Body read error: {}", e),
+        }
+    }
+}
+
+impl<T, R> core::error::Error for StreamError<T, R>
+where
+    T: core::error::Error,
+    R: core::error::Error,
+{
+}
+
+/// A pool of up to `P` client connections, keyed by the address of the server.
+///
+/// Connections are kept open (if the server allows keep-alive) after a request-response cycle completes,
+/// so that the next request to the same server reuses the existing TCP connection.
+/// If the cached TCP connection turns out to be dead, a new one is established transparently on the next request.
+///
+/// When all `P` connections are in use and a connection to a new server is requested, the least recently
+/// used connection is closed and reused for the new server.
+pub struct ConnectionPool<'b, T, const P: usize, const N: usize = DEFAULT_MAX_HEADERS_COUNT>
+where
+    T: TcpConnect,
+{
+    socket: &'b T,
+    bufs: heapless::Vec<&'b mut [u8], P>,
+    connections: heapless::Vec<Connection<'b, T, N>, P>,
+}
+
+impl<'b, T, const P: usize, const N: usize> ConnectionPool<'b, T, P, N>
+where
+    T: TcpConnect,
+{
+    /// Create a new connection pool.
+    ///
+    /// Parameters:
+    /// - `socket`: The TCP stack to use for the connections.
+    /// - `bufs`: The buffers of the connections, one per connection.
+    pub fn new<const B: usize>(socket: &'b T, bufs: &'b mut [[u8; B]; P]) -> Self {
+        Self {
+            socket,
+            bufs: bufs.iter_mut().map(|buf| buf.as_mut_slice()).collect(),
+            connections: heapless::Vec::new(),
+        }
+    }
+
+    /// Get a connection to the server with the provided address.
+    ///
+    /// An existing connection to the same server is returned if available, otherwise a new one is created,
+    /// possibly by closing the least recently used connection to another server.
+    pub async fn connection(
+        &mut self,
+        addr: SocketAddr,
+    ) -> Result<&mut Connection<'b, T, N>, Error<T::Error>> {
+        let index = if let Some(index) = self.connections.iter().position(|c| c.addr() == addr) {
+            index
+        } else if let Some(buf) = self.bufs.pop() {
+            unwrap!(self
+                .connections
+                .push(Connection::new(buf, self.socket, addr))
+                .map_err(|_| ()));
+
+            self.connections.len() - 1
+        } else {
+            // Evict the least recently used connection
+            self.connections[0].reinitialize(addr).await?;
+
+            0
+        };
+
+        // Keep the connections ordered from the least to the most recently used one
+        let connection = self.connections.remove(index);
+        unwrap!(self.connections.push(connection).map_err(|_| ()));
+
+        Ok(unwrap!(self.connections.last_mut()))
+    }
+
+    /// Close all connections in the pool.
+    pub async fn close(&mut self) -> Result<(), Error<T::Error>> {
+        let mut result = Ok(());
+
+        while let Some(mut connection) = self.connections.pop() {
+            let _ = connection.complete().await;
+
+            let mut state = connection.unbind();
+
+            if let Some(mut io) = state.io.take() {
+                if let Err(e) = io.close(Close::Both).await.map_err(Error::Io) {
+                    warn!(
+                        "Error when closing a pooled connection: {:?}",
+                        debug2format!(e)
+                    );
+                    result = Err(e);
+                }
+
+                let _ = io.abort().await;
+            }
+
+            unwrap!(self.bufs.push(state.buf).map_err(|_| ()));
+        }
+
+        result
+    }
+}
+
+/// Split a redirect `Location` into an optional authority (for absolute `http://` URLs) and a path
+fn split_location(location: &str) -> Option<(Option<&str>, &str)> {
+    if location.starts_with('/') && !location.starts_with("//") {
+        Some((None, location))
+    } else {
+        let scheme_len = if location.starts_with("//") {
+            2
+        } else if location
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+        {
+            7
+        } else {
+            return None;
+        };
+
+        let location = &location[scheme_len..];
+
+        let (authority, path) = match location.find('/') {
+            Some(index) => (&location[..index], &location[index..]),
+            None => (location, "/"),
+        };
+
+        (!authority.is_empty()).then_some((Some(authority), path))
+    }
+}
+
+/// Split an authority into a host and a port, defaulting to port 80
+fn split_authority(authority: &str) -> Option<(&str, u16)> {
+    let (host, port) = if authority.starts_with('[') {
+        // An IPv6 literal, which is only followed by the port, if any
+        let end = authority.find(']')? + 1;
+
+        match &authority[end..] {
+            "" => (&authority[..end], None),
+            rest => (&authority[..end], Some(rest.strip_prefix(':')?)),
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => 80,
+    };
+
+    (!host.is_empty()).then_some((host, port))
+}
+
 struct TransitionState(());
 
 struct UnboundState<'b, T, const N: usize>
@@ -473,3 +950,463 @@ where
         matches!(self.connection_type, ConnectionType::Close) || self.io.needs_close()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use embedded_io_async::{ErrorType, Read};
+
+    use crate::io::testing::{Connector, Peer, SliceRead};
+    use crate::io::Error;
+    use crate::Method;
+
+    use super::{split_authority, split_location, ConnectionPool};
+
+    #[test]
+    fn test_split_location() {
+        assert_eq!(split_location("/"), Some((None, "/")));
+        assert_eq!(split_location("/a/b?c=d"), Some((None, "/a/b?c=d")));
+
+        assert_eq!(
+            split_location("http://example.com/a?b"),
+            Some((Some("example.com"), "/a?b"))
+        );
+        assert_eq!(
+            split_location("HTTP://example.com:8080"),
+            Some((Some("example.com:8080"), "/"))
+        );
+        assert_eq!(
+            split_location("//[::1]:8080/a"),
+            Some((Some("[::1]:8080"), "/a"))
+        );
+
+        // Only paths and `http` URLs are followed
+        assert_eq!(split_location("a/b"), None);
+        assert_eq!(split_location("../a"), None);
+        assert_eq!(split_location("https://example.com/"), None);
+        assert_eq!(split_location(""), None);
+
+        // An URL without an authority
+        assert_eq!(split_location("http:///a"), None);
+        assert_eq!(split_location("//"), None);
+    }
+
+    #[test]
+    fn test_split_authority() {
+        assert_eq!(split_authority("example.com"), Some(("example.com", 80)));
+        assert_eq!(
+            split_authority("example.com:8080"),
+            Some(("example.com", 8080))
+        );
+        assert_eq!(split_authority("192.168.1.1:81"), Some(("192.168.1.1", 81)));
+
+        assert_eq!(split_authority("[::1]"), Some(("[::1]", 80)));
+        assert_eq!(split_authority("[fe80::1]:8080"), Some(("[fe80::1]", 8080)));
+
+        // Bad ports
+        assert_eq!(split_authority("example.com:"), None);
+        assert_eq!(split_authority("example.com:http"), None);
+        assert_eq!(split_authority("example.com:65536"), None);
+        assert_eq!(split_authority("[::1]:"), None);
+        assert_eq!(split_authority("[::1]:-1"), None);
+
+        // Malformed IPv6 literals and missing hosts
+        assert_eq!(split_authority("[::1"), None);
+        assert_eq!(split_authority("[::1]8080"), None);
+        assert_eq!(split_authority(":8080"), None);
+        assert_eq!(split_authority(""), None);
+    }
+
+    #[test]
+    fn test_post_stream() {
+        use super::{Connection, StreamError};
+
+        /// A body source failing after the first piece of the body
+        struct FailingRead(bool);
+
+        impl ErrorType for FailingRead {
+            type Error = embedded_io_async::ErrorKind;
+        }
+
+        impl Read for FailingRead {
+            async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                if core::mem::replace(&mut self.0, true) {
+                    Err(embedded_io_async::ErrorKind::Other)
+                } else {
+                    buf[..5].copy_from_slice(b"hello");
+
+                    Ok(5)
+                }
+            }
+        }
+
+        /// Post the body, and read the response when sending the request succeeds
+        async fn post<R>(
+            peer: &Peer,
+            headers: &[(&str, &str)],
+            body: R,
+        ) -> Result<(), StreamError<embedded_io_async::ErrorKind, R::Error>>
+        where
+            R: Read,
+        {
+            let connections: [(&[u8], _); 1] =
+                [(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok", peer)];
+            let connector = Connector::new(&connections);
+
+            let mut buf = [0; 512];
+            let mut connection =
+                Connection::<_, 4>::new(&mut buf, &connector, unwrap!("127.0.0.1:80".parse()));
+
+            pin!(connection.post_stream("/upload", headers, body)).await?;
+
+            assert_eq!(connection.headers()?.code, 200);
+
+            let mut body = [0; 2];
+            pin!(connection.read_exact(&mut body))
+                .await
+                .map_err(|e| match e {
+                    embedded_io_async::ReadExactError::Other(e) => e,
+                    embedded_io_async::ReadExactError::UnexpectedEof => Error::IncompleteBody,
+                })?;
+            assert_eq!(&body, b"ok");
+
+            Ok(())
+        }
+
+        // Without a `Content-Length`, the body is streamed with chunked encoding
+        let peer = Peer::default();
+
+        unwrap!(embassy_futures::block_on(pin!(post(
+            &peer,
+            &[],
+            SliceRead(b"hello world")
+        ))));
+
+        let written = peer.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert!(written.starts_with("POST /upload HTTP/1.1\r\n"));
+        assert!(written.contains("Transfer-Encoding: Chunked\r\n"));
+        assert!(written.ends_with("\r\n\r\nb\r\nhello world\r\n0\r\n\r\n"));
+        assert!(!peer.aborted.get());
+
+        // With a `Content-Length`, the body is sent as is
+        let peer = Peer::default();
+
+        unwrap!(embassy_futures::block_on(pin!(post(
+            &peer,
+            &[("Content-Length", "11")],
+            SliceRead(b"hello world")
+        ))));
+
+        let written = peer.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert!(written.contains("Content-Length: 11\r\n"));
+        assert!(written.ends_with("\r\n\r\nhello world"));
+
+        // A failing body source aborts the connection, so that the server does not take
+        // the truncated body for a complete one
+        let peer = Peer::default();
+
+        let result = embassy_futures::block_on(pin!(post(&peer, &[], FailingRead(false))));
+
+        assert!(matches!(
+            result,
+            Err(StreamError::Read(embedded_io_async::ErrorKind::Other))
+        ));
+        assert!(peer.aborted.get());
+        assert!(!peer.written.borrow().ends_with(b"0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_redirect_to_other_host() {
+        use super::Connection;
+
+        let first = Peer::default();
+        let second = Peer::default();
+
+        let connections: [(&[u8], _); 2] = [
+            (
+                b"HTTP/1.1 302 Found\r\nLocation: http://10.0.0.2:8080/b\r\nContent-Length: 0\r\n\r\n",
+                &first,
+            ),
+            (b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n", &second),
+        ];
+        let connector = Connector::new(&connections);
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 512];
+            let mut connection =
+                Connection::<_, 8>::new(&mut buf, &connector, unwrap!("10.0.0.1:80".parse()));
+
+            unwrap!(
+                pin!(connection.initiate_request_with_redirects(
+                    true,
+                    Method::Get,
+                    "/a",
+                    &[
+                        ("Host", "10.0.0.1"),
+                        ("Authorization", "Basic dXNlcjpwYXNz"),
+                        ("proxy-authorization", "Basic dXNlcjpwYXNz"),
+                        ("Cookie", "session=42"),
+                        ("X-Custom", "kept"),
+                    ],
+                    2,
+                    &edge_nal::NoopNet,
+                ))
+                .await
+            );
+
+            assert_eq!(unwrap!(connection.headers()).code, 200);
+        });
+
+        assert_eq!(
+            connector.remotes.borrow().as_slice(),
+            &[
+                unwrap!("10.0.0.1:80".parse()),
+                unwrap!("10.0.0.2:8080".parse())
+            ]
+        );
+
+        let written = first.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert!(written.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert!(written.contains("Cookie: session=42\r\n"));
+
+        // The credentials are not sent to the other host
+        let written = second.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert!(written.starts_with("GET /b HTTP/1.1\r\n"));
+        assert!(written.contains("Host: 10.0.0.2:8080\r\n"));
+        assert!(written.contains("X-Custom: kept\r\n"));
+        assert!(!written.contains("Host: 10.0.0.1\r\n"));
+        assert!(!written.to_ascii_lowercase().contains("authorization"));
+        assert!(!written.contains("Cookie"));
+    }
+
+    #[test]
+    fn test_redirect_post_to_get() {
+        use super::Connection;
+
+        let peer = Peer::default();
+
+        let connections: [(&[u8], _); 1] = [(
+            b"HTTP/1.1 303 See Other\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            &peer,
+        )];
+        let connector = Connector::new(&connections);
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 512];
+            let mut connection =
+                Connection::<_, 8>::new(&mut buf, &connector, unwrap!("10.0.0.1:80".parse()));
+
+            unwrap!(
+                pin!(connection.initiate_request_with_redirects(
+                    true,
+                    Method::Post,
+                    "/a",
+                    &[
+                        ("Host", "10.0.0.1"),
+                        ("Content-Type", "text/plain"),
+                        ("Content-Length", "0"),
+                        ("Authorization", "Basic dXNlcjpwYXNz"),
+                    ],
+                    2,
+                    &edge_nal::NoopNet,
+                ))
+                .await
+            );
+
+            assert_eq!(unwrap!(connection.headers()).code, 200);
+        });
+
+        assert_eq!(connector.remotes.borrow().len(), 1);
+
+        let written = peer.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        let (post, get) = unwrap!(written.split_once("GET "));
+
+        assert!(post.starts_with("POST /a HTTP/1.1\r\n"));
+        assert!(post.contains("Content-Type: text/plain\r\n"));
+        assert!(post.contains("Content-Length: 0\r\n"));
+
+        // The body headers are dropped with the body, the credentials are kept on the same host
+        assert!(get.starts_with("/b HTTP/1.1\r\n"));
+        assert!(get.contains("Host: 10.0.0.1\r\n"));
+        assert!(get.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert!(!get.contains("Content-Type"));
+        assert!(!get.contains("Content-Length"));
+    }
+
+    /// Send a `GET` request with a connection of the pool, and read the response
+    async fn pool_get<const P: usize>(
+        pool: &mut ConnectionPool<'_, Connector<'_>, P, 4>,
+        addr: &str,
+    ) -> Result<(), Error<embedded_io_async::ErrorKind>> {
+        let connection = pin!(pool.connection(unwrap!(addr.parse()))).await?;
+
+        pin!(connection.initiate_request(true, Method::Get, "/", &[])).await?;
+        pin!(connection.initiate_response()).await?;
+
+        assert_eq!(connection.headers()?.code, 200);
+
+        pin!(connection.complete()).await
+    }
+
+    const OK: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+
+    #[test]
+    fn test_connection_pool_reuse() {
+        let peer = Peer::default();
+
+        let responses = [OK, OK].concat();
+        let connections: [(&[u8], _); 1] = [(&responses, &peer)];
+        let connector = Connector::new(&connections);
+
+        let mut bufs = [[0; 256]; 2];
+
+        embassy_futures::block_on(async {
+            let mut pool = ConnectionPool::<_, 2, 4>::new(&connector, &mut bufs);
+
+            // Both requests go over the same connection
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+        });
+
+        assert_eq!(
+            connector.remotes.borrow().as_slice(),
+            &[unwrap!("10.0.0.1:80".parse())]
+        );
+
+        let written = peer.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert_eq!(written.matches("GET / HTTP/1.1\r\n").count(), 2);
+        assert!(!peer.is_done());
+    }
+
+    #[test]
+    fn test_connection_pool_eviction() {
+        let peers = [(); 3].map(|_| Peer::default());
+
+        let responses = [OK, OK, OK].concat();
+        let connections: [(&[u8], _); 3] =
+            [(&responses, &peers[0]), (OK, &peers[1]), (OK, &peers[2])];
+        let connector = Connector::new(&connections);
+
+        let mut bufs = [[0; 256]; 2];
+
+        embassy_futures::block_on(async {
+            let mut pool = ConnectionPool::<_, 2, 4>::new(&connector, &mut bufs);
+
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.2:80")).await);
+
+            // Using the first connection again makes the second one the least recently used...
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+
+            // ... so it is the one closed to connect to a third server, past the size of the pool
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.3:80")).await);
+
+            // ... and the first connection is still there
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+        });
+
+        assert_eq!(
+            connector.remotes.borrow().as_slice(),
+            &[
+                unwrap!("10.0.0.1:80".parse()),
+                unwrap!("10.0.0.2:80".parse()),
+                unwrap!("10.0.0.3:80".parse())
+            ]
+        );
+
+        assert!(!peers[0].is_done());
+        assert!(peers[1].is_done());
+        assert!(!peers[2].is_done());
+    }
+
+    #[test]
+    fn test_connection_pool_dead_connection() {
+        let peers = [(); 2].map(|_| Peer::default());
+
+        let connections: [(&[u8], _); 2] = [(OK, &peers[0]), (OK, &peers[1])];
+        let connector = Connector::new(&connections);
+
+        let mut bufs = [[0; 256]; 2];
+
+        embassy_futures::block_on(async {
+            let mut pool = ConnectionPool::<_, 2, 4>::new(&connector, &mut bufs);
+
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+
+            // The server drops the idle connection, so the next request is sent over a new one
+            peers[0].reset.set(true);
+
+            unwrap!(pin!(pool_get(&mut pool, "10.0.0.1:80")).await);
+        });
+
+        assert_eq!(
+            connector.remotes.borrow().as_slice(),
+            &[
+                unwrap!("10.0.0.1:80".parse()),
+                unwrap!("10.0.0.1:80".parse())
+            ]
+        );
+
+        assert!(peers[1].written.borrow().starts_with(b"GET / HTTP/1.1\r\n"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_accept_encoding_opt_in() {
+        use super::Connection;
+        use crate::io::inflate::ACCEPT_ENCODING;
+
+        /// Send a `GET` request with the provided headers
+        fn get(peer: &Peer, headers: &[(&str, &str)]) {
+            let connections: [(&[u8], _); 1] =
+                [(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n", peer)];
+            let connector = Connector::new(&connections);
+
+            embassy_futures::block_on(async {
+                let mut buf = [0; 512];
+                let mut connection =
+                    Connection::<_, 4>::new(&mut buf, &connector, unwrap!("127.0.0.1:80".parse()));
+
+                unwrap!(pin!(connection.initiate_request(true, Method::Get, "/", headers)).await);
+                unwrap!(pin!(connection.initiate_response()).await);
+            });
+        }
+
+        // No compression is advertised unless asked for
+        let peer = Peer::default();
+        get(&peer, &[]);
+
+        assert!(!peer
+            .written
+            .borrow()
+            .windows(15)
+            .any(|w| w == b"Accept-Encoding"));
+
+        let peer = Peer::default();
+        get(&peer, &[("Accept-Encoding", ACCEPT_ENCODING)]);
+
+        let written = peer.written.borrow();
+        let written = unwrap!(core::str::from_utf8(&written));
+
+        assert_eq!(
+            written
+                .matches("Accept-Encoding: gzip, deflate\r\n")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Streaming decompression of `gzip` and `deflate` encoded bodies.
+
+use embedded_io_async::{ErrorType, Read};
+
+use miniz_oxide::inflate::core::inflate_flags::{
+    TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_PARSE_ZLIB_HEADER,
+};
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide, TINFL_LZ_DICT_SIZE};
+use miniz_oxide::inflate::TINFLStatus;
+
+use super::Error;
+
+/// The size of the buffer used for reading the compressed input
+pub const INFLATE_INPUT_BUF_SIZE: usize = 512;
+
+/// The value of the `Accept-Encoding` request header advertising all encodings supported by `InflateRead`
+pub const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+
+/// The encoding of a body, as advertised by the `Content-Encoding` header
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ContentEncoding {
+    /// The body is not encoded
+    Identity,
+    /// The body is encoded with `gzip` (RFC 1952)
+    Gzip,
+    /// The body is encoded with `deflate` (RFC 1950 zlib stream, or - for broken servers - raw RFC 1951 deflate)
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Create a content encoding from the value of a `Content-Encoding` header
+    ///
+    /// Returns `None` if the encoding is not supported
+    pub fn from_header(value: Option<&str>) -> Option<Self> {
+        match value.map(str::trim) {
+            None | Some("") => Some(Self::Identity),
+            Some(value) if value.eq_ignore_ascii_case("identity") => Some(Self::Identity),
+            Some(value)
+                if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") =>
+            {
+                Some(Self::Gzip)
+            }
+            Some(value) if value.eq_ignore_ascii_case("deflate") => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// The buffers necessary for decompressing a body
+///
+/// These are relatively large (~ 44KB), as `deflate` needs a 32KB window of previously decompressed data,
+/// so consider allocating them statically.
+pub struct InflateBuffers {
+    decompressor: DecompressorOxide,
+    dict: [u8; TINFL_LZ_DICT_SIZE],
+    input: [u8; INFLATE_INPUT_BUF_SIZE],
+}
+
+impl InflateBuffers {
+    /// Create a new set of buffers
+    pub fn new() -> Self {
+        Self {
+            decompressor: DecompressorOxide::new(),
+            dict: [0; TINFL_LZ_DICT_SIZE],
+            input: [0; INFLATE_INPUT_BUF_SIZE],
+        }
+    }
+}
+
+impl Default for InflateBuffers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reader which decompresses a `gzip` or `deflate` encoded body on the fly
+///
+/// Note that the `gzip` CRC32 trailer is not verified.
+pub struct InflateRead<'a, R> {
+    input: R,
+    encoding: ContentEncoding,
+    buffers: &'a mut InflateBuffers,
+    flags: u32,
+    header_parsed: bool,
+    input_pos: usize,
+    input_len: usize,
+    input_eof: bool,
+    dict_pos: usize,
+    output_pos: usize,
+    output_len: usize,
+    done: bool,
+}
+
+impl<'a, R> InflateRead<'a, R> {
+    /// Create a new decompressing reader
+    ///
+    /// Parameters:
+    /// - `encoding`: The encoding of the body
+    /// - `input`: The reader of the encoded body
+    /// - `buffers`: The buffers used for decompression
+    pub fn new(encoding: ContentEncoding, input: R, buffers: &'a mut InflateBuffers) -> Self {
+        buffers.decompressor.init();
+
+        Self {
+            input,
+            encoding,
+            buffers,
+            flags: 0,
+            header_parsed: false,
+            input_pos: 0,
+            input_len: 0,
+            input_eof: false,
+            dict_pos: 0,
+            output_pos: 0,
+            output_len: 0,
+            done: false,
+        }
+    }
+
+    /// Return the encoding of the body
+    pub fn encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    /// Release the reader, returning the underlying reader of the encoded body
+    pub fn release(self) -> R {
+        self.input
+    }
+}
+
+impl<R, E> InflateRead<'_, R>
+where
+    R: Read<Error = Error<E>>,
+{
+    async fn fill(&mut self) -> Result<bool, Error<E>> {
+        if self.input_pos == self.input_len && !self.input_eof {
+            self.input_pos = 0;
+            self.input_len = self.input.read(&mut self.buffers.input).await?;
+            self.input_eof = self.input_len == 0;
+        }
+
+        Ok(self.input_pos < self.input_len)
+    }
+
+    async fn next_byte(&mut self) -> Result<u8, Error<E>> {
+        if self.fill().await? {
+            let byte = self.buffers.input[self.input_pos];
+            self.input_pos += 1;
+
+            Ok(byte)
+        } else {
+            Err(Error::IncompleteBody)
+        }
+    }
+
+    async fn skip_bytes(&mut self, len: usize) -> Result<(), Error<E>> {
+        for _ in 0..len {
+            self.next_byte().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn skip_zero_terminated(&mut self) -> Result<(), Error<E>> {
+        while self.next_byte().await? != 0 {}
+
+        Ok(())
+    }
+
+    async fn parse_header(&mut self) -> Result<(), Error<E>> {
+        match self.encoding {
+            ContentEncoding::Gzip => {
+                let mut header = [0; 10];
+                for byte in &mut header {
+                    *byte = self.next_byte().await?;
+                }
+
+                if header[0] != 0x1f || header[1] != 0x8b || header[2] != 8 {
+                    return Err(Error::InvalidBody);
+                }
+
+                let flags = header[3];
+
+                if flags & GZIP_FEXTRA != 0 {
+                    let len =
+                        u16::from_le_bytes([self.next_byte().await?, self.next_byte().await?]);
+                    self.skip_bytes(len as _).await?;
+                }
+
+                if flags & GZIP_FNAME != 0 {
+                    self.skip_zero_terminated().await?;
+                }
+
+                if flags & GZIP_FCOMMENT != 0 {
+                    self.skip_zero_terminated().await?;
+                }
+
+                if flags & GZIP_FHCRC != 0 {
+                    self.skip_bytes(2).await?;
+                }
+            }
+            ContentEncoding::Deflate => {
+                // Many servers send raw deflate data rather than a zlib stream, so detect the zlib header
+                if self.fill().await? && self.input_len - self.input_pos >= 2 {
+                    let cmf = self.buffers.input[self.input_pos];
+                    let flg = self.buffers.input[self.input_pos + 1];
+
+                    if cmf & 0x0f == 8 && (((cmf as u16) << 8) | flg as u16).is_multiple_of(31) {
+                        self.flags |= TINFL_FLAG_PARSE_ZLIB_HEADER;
+                    }
+                }
+            }
+            ContentEncoding::Identity => (),
+        }
+
+        self.header_parsed = true;
+
+        Ok(())
+    }
+}
+
+impl<R, E> ErrorType for InflateRead<'_, R>
+where
+    R: ErrorType<Error = Error<E>>,
+    E: embedded_io_async::Error,
+{
+    type Error = Error<E>;
+}
+
+impl<R, E> Read for InflateRead<'_, R>
+where
+    R: Read<Error = Error<E>>,
+    E: embedded_io_async::Error,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.encoding == ContentEncoding::Identity {
+            return self.input.read(buf).await;
+        }
+
+        if !self.header_parsed {
+            self.parse_header().await?;
+        }
+
+        loop {
+            if self.output_len > 0 {
+                let len = self.output_len.min(buf.len());
+
+                buf[..len]
+                    .copy_from_slice(&self.buffers.dict[self.output_pos..self.output_pos + len]);
+
+                self.output_pos += len;
+                self.output_len -= len;
+
+                return Ok(len);
+            }
+
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+
+            self.fill().await?;
+
+            let flags = if self.input_eof {
+                self.flags
+            } else {
+                self.flags | TINFL_FLAG_HAS_MORE_INPUT
+            };
+
+            let (status, consumed, produced) = decompress(
+                &mut self.buffers.decompressor,
+                &self.buffers.input[self.input_pos..self.input_len],
+                &mut self.buffers.dict,
+                self.dict_pos,
+                flags,
+            );
+
+            self.input_pos += consumed;
+            self.output_pos = self.dict_pos;
+            self.output_len = produced;
+            self.dict_pos = (self.dict_pos + produced) & (TINFL_LZ_DICT_SIZE - 1);
+
+            match status {
+                TINFLStatus::Done => self.done = true,
+                TINFLStatus::NeedsMoreInput | TINFLStatus::HasMoreOutput => (),
+                TINFLStatus::FailedCannotMakeProgress => Err(Error::IncompleteBody)?,
+                _ => Err(Error::InvalidBody)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use embedded_io_async::Read;
+
+    use crate::io::testing::SliceRead;
+    use crate::io::Body;
+    use crate::BodyType;
+
+    #[test]
+    fn test_inflate() {
+        use super::{ContentEncoding, InflateBuffers, InflateRead};
+
+        const GZIP: &[u8] = &[
+            0x1f, 0x8b, 0x8, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, 0x3, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7,
+            0x51, 0x48, 0xce, 0xcf, 0x2d, 0x28, 0x4a, 0x2d, 0x2e, 0x4e, 0x4d, 0x51, 0x28, 0xcf,
+            0x2f, 0xca, 0x49, 0x51, 0x54, 0xf0, 0x18, 0x5a, 0x12, 0x0, 0x6e, 0x83, 0xac, 0xb3,
+            0xc8, 0x0, 0x0, 0x0,
+        ];
+        const ZLIB: &[u8] = &[
+            0x78, 0x9c, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0x48, 0xce, 0xcf, 0x2d, 0x28,
+            0x4a, 0x2d, 0x2e, 0x4e, 0x4d, 0x51, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x51, 0x54, 0xf0,
+            0x18, 0x5a, 0x12, 0x0, 0x55, 0xac, 0x47, 0xf1,
+        ];
+
+        let mut buffers = InflateBuffers::new();
+
+        for (encoding, input) in [
+            (ContentEncoding::Gzip, GZIP),
+            (ContentEncoding::Deflate, ZLIB),
+            (ContentEncoding::Deflate, &ZLIB[2..]),
+        ] {
+            embassy_futures::block_on(async {
+                let mut buf = [0; 16];
+                let body = Body::new(BodyType::Raw, &mut buf, 0, SliceRead(input));
+
+                let mut r = InflateRead::new(encoding, body, &mut buffers);
+
+                let mut out = [0; 256];
+                let mut len = 0;
+                loop {
+                    let read = unwrap!(pin!(r.read(&mut out[len..len + 7])).await);
+                    if read == 0 {
+                        break;
+                    }
+
+                    len += read;
+                }
+
+                assert_eq!(len, 200);
+                for chunk in out[..len].chunks(25) {
+                    assert_eq!(chunk, b"Hello, compressed world! ");
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! A streaming writer of `multipart/form-data` bodies (RFC 7578).
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{ErrorType, Write};
+
+use super::Error;
+
+/// The maximum length of a multipart boundary, as per RFC 2046
+pub const MAX_BOUNDARY_LEN: usize = 70;
+
+/// The maximum length of the `Content-Type` header value of a multipart body
+pub const MAX_CONTENT_TYPE_LEN: usize = CONTENT_TYPE_PREFIX.len() + MAX_BOUNDARY_LEN + 2;
+
+const CONTENT_TYPE_PREFIX: &str = "multipart/form-data; boundary=";
+const CONTENT_DISPOSITION: &str = "Content-Disposition: form-data; name=\"";
+const FILENAME: &str = "\"; filename=\"";
+const CONTENT_TYPE: &str = "Content-Type: ";
+
+/// Return `true` if the provided boundary is valid as per RFC 2046, i.e. it is 1 to `MAX_BOUNDARY_LEN`
+/// characters long, only contains digits, letters, spaces and `'()+_,-./:=?`, and does not end with a space
+pub fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=MAX_BOUNDARY_LEN).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b" '()+_,-./:=?".contains(&b))
+}
+
+/// Return the value of the `Content-Type` header for a multipart body with the provided boundary,
+/// or `None` if the boundary is not valid (see `is_valid_boundary`)
+pub fn content_type(boundary: &str) -> Option<heapless::String<MAX_CONTENT_TYPE_LEN>> {
+    if !is_valid_boundary(boundary) {
+        return None;
+    }
+
+    let mut content_type = heapless::String::new();
+
+    // Boundaries with characters special to the header syntax (RFC 2045) have to be quoted
+    if boundary.contains([' ', '(', ')', ',', '/', ':', '=', '?']) {
+        write_unwrap!(&mut content_type, "{}\"{}\"", CONTENT_TYPE_PREFIX, boundary);
+    } else {
+        write_unwrap!(&mut content_type, "{}{}", CONTENT_TYPE_PREFIX, boundary);
+    }
+
+    Some(content_type)
+}
+
+/// Return the length of a complete part with the provided headers and content length,
+/// as written by `MultipartWrite::field`, or by `MultipartWrite::start_part` followed by the content
+pub fn part_len(
+    boundary: &str,
+    name: &str,
+    filename: Option<&str>,
+    content_type: Option<&str>,
+    content_len: usize,
+) -> usize {
+    let mut len = 2 + boundary.len() + 2 + CONTENT_DISPOSITION.len() + name.len() + 1;
+
+    if let Some(filename) = filename {
+        len += FILENAME.len() + filename.len();
+    }
+
+    len += 2;
+
+    if let Some(content_type) = content_type {
+        len += CONTENT_TYPE.len() + content_type.len() + 2;
+    }
+
+    len + 2 + content_len + 2
+}
+
+/// Return the length of the terminating boundary, as written by `MultipartWrite::finish`
+pub fn end_len(boundary: &str) -> usize {
+    2 + boundary.len() + 4
+}
+
+/// A writer of `multipart/form-data` bodies
+///
+/// The writer emits the boundaries and the headers of each part, while the content of each part
+/// is streamed by the caller via the `Write` implementation of the writer (or with `field` for small values).
+///
+/// The body can be sent either with chunked encoding (the default for HTTP/1.1 client requests
+/// without a `Content-Length` header), or with a `Content-Length` computed upfront with
+/// `part_len` and `end_len`.
+///
+/// Typical usage with the HTTP client:
+/// - Initiate the request with the `Content-Type` header returned by `content_type`
+/// - Wrap the connection with `MultipartWrite::new`
+/// - Call `field` and/or `start_part` + `write_all` for each part
+/// - Call `finish` to emit the terminating boundary
+pub struct MultipartWrite<'a, W> {
+    boundary: &'a str,
+    output: W,
+    in_part: bool,
+    finished: bool,
+}
+
+impl<W> MultipartWrite<'_, W> {
+    /// Release the writer, returning the underlying writer of the request body
+    pub fn release(self) -> W {
+        self.output
+    }
+}
+
+impl<'a, W, E> MultipartWrite<'a, W>
+where
+    W: Write<Error = Error<E>>,
+{
+    /// Create a new multipart writer
+    ///
+    /// Return `Error::InvalidHeaders` if the boundary is not valid (see `is_valid_boundary`).
+    ///
+    /// Parameters:
+    /// - `boundary`: The boundary separating the parts; it must not appear in the content of any of the parts
+    /// - `output`: The writer of the request body
+    pub fn new(boundary: &'a str, output: W) -> Result<Self, Error<E>> {
+        if !is_valid_boundary(boundary) {
+            return Err(Error::InvalidHeaders);
+        }
+
+        Ok(Self {
+            boundary,
+            output,
+            in_part: false,
+            finished: false,
+        })
+    }
+
+    /// Write a complete part with the provided name and value
+    pub async fn field(&mut self, name: &str, value: &[u8]) -> Result<(), Error<E>> {
+        self.start_part(name, None, None).await?;
+
+        self.output.write_all(value).await
+    }
+
+    /// Start a new part, completing the previous one (if any)
+    ///
+    /// The content of the part should then be written with the `Write` implementation of the writer.
+    ///
+    /// Parameters:
+    /// - `name`: The name of the form field
+    /// - `filename`: The file name, for file uploads
+    /// - `content_type`: The content type of the part, if not `text/plain`
+    pub async fn start_part(
+        &mut self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+    ) -> Result<(), Error<E>> {
+        if self.finished {
+            return Err(Error::InvalidState);
+        }
+
+        if [Some(name), filename, content_type]
+            .into_iter()
+            .flatten()
+            .any(|value| value.contains(['"', '\r', '\n']))
+        {
+            return Err(Error::InvalidHeaders);
+        }
+
+        self.end_part().await?;
+
+        self.output.write_all(b"--").await?;
+        self.output.write_all(self.boundary.as_bytes()).await?;
+        self.output.write_all(b"\r\n").await?;
+
+        self.output
+            .write_all(CONTENT_DISPOSITION.as_bytes())
+            .await?;
+        self.output.write_all(name.as_bytes()).await?;
+
+        if let Some(filename) = filename {
+            self.output.write_all(FILENAME.as_bytes()).await?;
+            self.output.write_all(filename.as_bytes()).await?;
+        }
+
+        self.output.write_all(b"\"\r\n").await?;
+
+        if let Some(content_type) = content_type {
+            self.output.write_all(CONTENT_TYPE.as_bytes()).await?;
+            self.output.write_all(content_type.as_bytes()).await?;
+            self.output.write_all(b"\r\n").await?;
+        }
+
+        self.output.write_all(b"\r\n").await?;
+
+        self.in_part = true;
+
+        Ok(())
+    }
+
+    /// Complete the last part (if any) and write the terminating boundary
+    ///
+    /// Note that this does not finish the request body itself.
+    pub async fn finish(&mut self) -> Result<(), Error<E>> {
+        if !self.finished {
+            self.end_part().await?;
+
+            self.output.write_all(b"--").await?;
+            self.output.write_all(self.boundary.as_bytes()).await?;
+            self.output.write_all(b"--\r\n").await?;
+
+            self.finished = true;
+        }
+
+        self.output.flush().await
+    }
+
+    async fn end_part(&mut self) -> Result<(), Error<E>> {
+        if self.in_part {
+            self.output.write_all(b"\r\n").await?;
+            self.in_part = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W, E> ErrorType for MultipartWrite<'_, W>
+where
+    W: ErrorType<Error = Error<E>>,
+    E: embedded_io_async::Error,
+{
+    type Error = Error<E>;
+}
+
+impl<W, E> Write for MultipartWrite<'_, W>
+where
+    W: Write<Error = Error<E>>,
+    E: embedded_io_async::Error,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.in_part {
+            return Err(Error::InvalidState);
+        }
+
+        self.output.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.output.flush().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use embedded_io_async::Write;
+
+    use crate::io::testing::VecWrite;
+    use crate::io::{Error, SendBody};
+    use crate::BodyType;
+
+    #[test]
+    fn test_multipart_write() {
+        use super::{content_type, end_len, part_len, MultipartWrite, MAX_BOUNDARY_LEN};
+
+        embassy_futures::block_on(async move {
+            let output = SendBody::new(BodyType::Raw, VecWrite(heapless::Vec::new()));
+            let mut w = unwrap!(MultipartWrite::new("XyZ", output));
+
+            unwrap!(pin!(w.field("a", b"1")).await);
+            unwrap!(pin!(w.start_part("f", Some("f.bin"), Some("application/octet-stream"))).await);
+            unwrap!(w.write_all(b"abc").await);
+            unwrap!(w.finish().await);
+
+            assert!(w.write_all(b"x").await.is_err());
+            assert!(pin!(w.field("a\"", b"1")).await.is_err());
+
+            let expected: &[u8] =
+                b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n\
+                --XyZ\r\nContent-Disposition: form-data; name=\"f\"; filename=\"f.bin\"\r\n\
+                Content-Type: application/octet-stream\r\n\r\nabc\r\n\
+                --XyZ--\r\n";
+
+            assert_eq!(w.release().release().0.as_slice(), expected);
+            assert_eq!(
+                part_len("XyZ", "a", None, None, 1)
+                    + part_len(
+                        "XyZ",
+                        "f",
+                        Some("f.bin"),
+                        Some("application/octet-stream"),
+                        3
+                    )
+                    + end_len("XyZ"),
+                expected.len()
+            );
+            assert_eq!(
+                content_type("XyZ").as_deref(),
+                Some("multipart/form-data; boundary=XyZ")
+            );
+            assert_eq!(
+                content_type("a:b").as_deref(),
+                Some("multipart/form-data; boundary=\"a:b\"")
+            );
+
+            let long = [b'x'; MAX_BOUNDARY_LEN + 1];
+            let long = unwrap!(core::str::from_utf8(&long));
+
+            for boundary in ["", "a ", "a\"b", "a\r\nb", &long[..MAX_BOUNDARY_LEN + 1]] {
+                assert!(content_type(boundary).is_none());
+                assert!(matches!(
+                    MultipartWrite::new(
+                        boundary,
+                        SendBody::new(BodyType::Raw, VecWrite(heapless::Vec::new()))
+                    ),
+                    Err(Error::InvalidHeaders)
+                ));
+            }
+
+            assert!(content_type(&long[..MAX_BOUNDARY_LEN]).is_some());
+        })
+    }
+}
@@ -1,5 +1,6 @@
 use core::fmt::{self, Debug, Display};
 use core::mem::{self, MaybeUninit};
+use core::ops::DerefMut;
 use core::pin::pin;
 
 use edge_nal::{
@@ -11,13 +12,130 @@ use embedded_io_async::{ErrorType, Read, Write};
 use super::{send_headers, send_status, Body, Error, RequestHeaders, SendBody};
 
 use crate::ws::{upgrade_response_headers, MAX_BASE64_KEY_RESPONSE_LEN};
-use crate::{ConnectionType, DEFAULT_MAX_HEADERS_COUNT};
+use crate::{
+    is_valid_status, reason_phrase, ByteRange, Clock, ConnectionType, HttpDate, PathParams,
+    RouteError, Router, DEFAULT_MAX_HEADERS_COUNT, DEFAULT_SERVER_HEADER, HTTP_DATE_LEN,
+};
 
 pub const DEFAULT_HANDLER_TASKS_COUNT: usize = 4;
 pub const DEFAULT_BUF_SIZE: usize = 2048;
 
+/// The maximum number of times headers can be added to a response with `Connection::add_response_headers`
+pub const MAX_ADDED_RESPONSE_HEADERS: usize = 4;
+
 const COMPLETION_BUF_SIZE: usize = 64;
 
+/// The time for which the acceptors back off when the acceptor reports backpressure
+/// (see `TcpAccept::is_backpressure`), i.e. the TCP stack is out of sockets.
+const ACCEPT_BACKOFF_MS: u32 = 100;
+
+/// The maximum time for which a connection closed by the server is drained before closing it, see `reject_connection`
+const DRAIN_TIMEOUT_MS: u32 = 1000;
+
+/// The maximum number of bytes drained from a connection closed by the server, see `reject_connection`
+const DRAIN_MAX_LEN: usize = 4096;
+
+/// The headers added automatically to each response, unless the handler provides them itself
+///
+/// By default, only the `Server` header is added. The `Date` header needs a `Clock` and is omitted
+/// while the clock does not know the time, as a server without a reliable clock must not send it.
+#[derive(Copy, Clone)]
+pub struct DefaultHeaders<'a> {
+    server: Option<&'a str>,
+    clock: Option<&'a dyn Clock>,
+}
+
+impl<'a> DefaultHeaders<'a> {
+    /// Create the default set of automatic headers, i.e. `Server: edge-http` and no `Date`
+    pub const fn new() -> Self {
+        Self {
+            server: Some(DEFAULT_SERVER_HEADER),
+            clock: None,
+        }
+    }
+
+    /// Create an empty set of automatic headers
+    pub const fn none() -> Self {
+        Self {
+            server: None,
+            clock: None,
+        }
+    }
+
+    /// Set the value of the `Server` header, or `None` to not send the header
+    pub const fn with_server(mut self, server: Option<&'a str>) -> Self {
+        self.server = server;
+        self
+    }
+
+    /// Set the clock providing the value of the `Date` header, or `None` to not send the header
+    pub const fn with_clock(mut self, clock: Option<&'a dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Default for DefaultHeaders<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for DefaultHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefaultHeaders")
+            .field("server", &self.server)
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+/// The limits on the headers section of the requests received by the server
+///
+/// A request exceeding the limits is answered with `431 Request Header Fields Too Large`,
+/// and its connection is closed.
+///
+/// Regardless of the limits, the headers section cannot be longer than the buffer of the connection,
+/// nor contain more headers than the `N` const parameter of the connection (or the server), so the
+/// defaults - no limits beyond these - are already safe; the limits allow for being stricter,
+/// i.e. when the buffer is sized for large bodies rather than for large headers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HeaderLimits {
+    /// The maximum length in bytes of the headers section, the request line included
+    pub max_len: usize,
+    /// The maximum number of headers
+    pub max_count: usize,
+}
+
+impl HeaderLimits {
+    /// Create the default limits, i.e. the size of the buffer and the `N` const parameter of the connection
+    pub const fn new() -> Self {
+        Self {
+            max_len: usize::MAX,
+            max_count: usize::MAX,
+        }
+    }
+
+    /// Set the maximum length in bytes of the headers section, the request line included
+    pub const fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Set the maximum number of headers
+    pub const fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = max_count;
+        self
+    }
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A connection state machine for handling HTTP server requests-response cycles.
 #[allow(private_interfaces)]
 pub enum Connection<'b, T, const N: usize = DEFAULT_MAX_HEADERS_COUNT> {
@@ -41,30 +159,106 @@ where
     /// Parameters:
     /// - `buf`: A buffer to store the request headers
     /// - `io`: A socket stream
-    pub async fn new(
+    pub async fn new(buf: &'b mut [u8], io: T) -> Result<Connection<'b, T, N>, Error<T::Error>> {
+        Self::new_with_header_limits(buf, io, HeaderLimits::new()).await
+    }
+
+    /// Same as `new`, but fails with `Error::TooLongHeaders` or `Error::TooManyHeaders`
+    /// when the headers section of the request exceeds the provided limits
+    pub async fn new_with_header_limits(
         buf: &'b mut [u8],
         mut io: T,
+        header_limits: HeaderLimits,
     ) -> Result<Connection<'b, T, N>, Error<T::Error>> {
         let mut request = RequestHeaders::new();
 
-        let (buf, read_len) = request.receive(buf, &mut io, true).await?;
+        let (buf, read_len) = request
+            .receive_limited(buf, &mut io, true, header_limits.max_len)
+            .await?;
+
+        if request.headers.iter_raw().count() > header_limits.max_count {
+            Err(Error::TooManyHeaders)?;
+        }
 
         let (connection_type, body_type) = request.resolve::<T::Error>()?;
 
         let io = Body::new(body_type, buf, read_len, io);
 
+        let continue_pending = request.http11
+            && request
+                .headers
+                .get("Expect")
+                .map(|value| value.eq_ignore_ascii_case("100-continue"))
+                .unwrap_or(false);
+
         Ok(Self::Request(RequestState {
             request,
             io,
             connection_type,
+            continue_pending,
+            default_headers: DefaultHeaders::none(),
+            added_headers: heapless::Vec::new(),
         }))
     }
 
+    /// Set the headers to be added automatically to the response, unless the response already contains them
+    ///
+    /// A connection created with `new` does not add any headers on its own.
+    pub fn set_default_headers(
+        &mut self,
+        default_headers: DefaultHeaders<'b>,
+    ) -> Result<(), Error<T::Error>> {
+        self.request_mut()?.default_headers = default_headers;
+
+        Ok(())
+    }
+
+    /// Add headers to the response, except for those also passed when initiating the response
+    ///
+    /// Allows a `Middleware` to contribute response headers (i.e. the CORS ones) before delegating the request
+    /// to the handler, as the handler sends the response headers itself.
+    ///
+    /// Fails with `Error::TooManyHeaders` if called more than `MAX_ADDED_RESPONSE_HEADERS` times,
+    /// and with `Error::InvalidState` if the response is already initiated.
+    pub fn add_response_headers(
+        &mut self,
+        headers: &'b [(&'b str, &'b str)],
+    ) -> Result<(), Error<T::Error>> {
+        self.request_mut()?
+            .added_headers
+            .push(headers)
+            .map_err(|_| Error::TooManyHeaders)
+    }
+
     /// Return `true` of the connection is in request state (i.e. the initial state upon calling `new`)
     pub fn is_request_initiated(&self) -> bool {
         matches!(self, Self::Request(_))
     }
 
+    /// Send an interim `100 Continue` response, if the client requested one with `Expect: 100-continue`
+    /// and it was not sent yet
+    ///
+    /// This is done automatically on the first read of the request body via the connection itself,
+    /// but needs to be called explicitly before reading the body obtained with `split`.
+    ///
+    /// To reject the request body instead, just initiate a response (i.e. with status `417`) without
+    /// reading the body. The connection will then be closed after the response, as the body was never sent.
+    pub async fn send_continue(&mut self) -> Result<(), Error<T::Error>> {
+        let request = self.request_mut()?;
+
+        if request.continue_pending {
+            let io = request.io.as_raw_reader();
+
+            send_status(true, 100, Some("Continue"), &mut *io).await?;
+            io.write_all(b"\r\n").await.map_err(Error::Io)?;
+            io.flush().await.map_err(Error::Io)?;
+
+            request.continue_pending = false;
+        }
+
+        Ok(())
+    }
+
     /// Split the connection into request headers and body
     pub fn split(&mut self) -> (&RequestHeaders<'b, N>, &mut Body<'b, T>) {
         let req = self.request_mut().expect("Not in request mode");
@@ -85,8 +279,9 @@ where
     /// Switch the connection into a response state
     ///
     /// Parameters:
-    /// - `status`: The HTTP status code
-    /// - `message`: An optional HTTP status message
+    /// - `status`: The HTTP status code, which can be any three-digit code, including unregistered ones
+    /// - `message`: An optional HTTP status message (reason phrase). If `None`, the reason phrase registered
+    ///   for `status` is used (see `reason_phrase`), or an empty one for codes without a registered phrase
     /// - `headers`: An array of HTTP response headers.
     ///   Note that if no `Content-Length` or `Transfer-Encoding` headers are provided,
     ///   the body will be send with chunked encoding (for HTTP1.1 only and if the connection is not Close)
@@ -99,6 +294,79 @@ where
         self.complete_request(status, message, headers).await
     }
 
+    /// A convenience method to initiate a response for content of a known length, honoring `Range` requests
+    ///
+    /// Depending on the `Range` header of the request (if any), this sends either:
+    /// - A `200 OK` response for the whole content
+    /// - A `206 Partial Content` response with the appropriate `Content-Range` header, for a single satisfiable byte range
+    /// - A `416 Range Not Satisfiable` response, for unsatisfiable byte ranges
+    ///
+    /// The `Content-Length` and `Accept-Ranges` headers are added automatically, so `headers`
+    /// should not contain body-type headers.
+    ///
+    /// Returns the range of the content which should be written as the response body,
+    /// or `None` if the range is not satisfiable and nothing should be written.
+    ///
+    /// Parameters:
+    /// - `len`: The length of the complete content
+    /// - `headers`: Additional HTTP response headers (i.e. `Content-Type`)
+    pub async fn initiate_range_response(
+        &mut self,
+        len: u64,
+        headers: &[(&str, &str)],
+    ) -> Result<Option<core::ops::Range<u64>>, Error<T::Error>> {
+        use core::fmt::Write as _;
+
+        let range = ByteRange::resolve(self.headers()?.headers.get("Range"), len);
+
+        let mut content_len = heapless::String::<20>::new();
+        let mut content_range = heapless::String::<64>::new();
+
+        let (status, reason, body) = match range {
+            ByteRange::All => {
+                write_unwrap!(&mut content_len, "{}", len);
+
+                (200, "OK", Some(0..len))
+            }
+            ByteRange::Partial { start, end } => {
+                write_unwrap!(&mut content_len, "{}", end - start + 1);
+                write_unwrap!(&mut content_range, "bytes {}-{}/{}", start, end, len);
+
+                (206, "Partial Content", Some(start..end + 1))
+            }
+            ByteRange::Unsatisfiable => {
+                write_unwrap!(&mut content_len, "0");
+                write_unwrap!(&mut content_range, "bytes */{}", len);
+
+                (416, "Range Not Satisfiable", None)
+            }
+        };
+
+        let mut range_headers = heapless::Vec::<_, N>::new();
+
+        for header in headers
+            .iter()
+            .chain(&[
+                ("Content-Length", content_len.as_str()),
+                ("Accept-Ranges", "bytes"),
+            ])
+            .chain(
+                (!content_range.is_empty())
+                    .then_some(("Content-Range", content_range.as_str()))
+                    .as_ref(),
+            )
+        {
+            range_headers
+                .push(*header)
+                .map_err(|_| Error::TooManyHeaders)?;
+        }
+
+        self.initiate_response(status, Some(reason), &range_headers)
+            .await?;
+
+        Ok(body)
+    }
+
     /// A convenience method to initiate a WebSocket upgrade response
     pub async fn initiate_ws_upgrade_response(
         &mut self,
@@ -109,6 +377,22 @@ where
         self.initiate_response(101, None, &headers).await
     }
 
+    /// A convenience method to accept a WebSocket upgrade request and hand over the raw socket stream
+    ///
+    /// Validates the WebSocket handshake headers of the request, sends the `101 Switching Protocols`
+    /// response with the computed `Sec-WebSocket-Accept` key, completes the response and switches
+    /// the connection to unbound state.
+    ///
+    /// The returned socket stream can then be operated as a WebSocket connection, i.e. with `edge-ws`.
+    pub async fn upgrade_to_ws(&mut self) -> Result<&mut T, Error<T::Error>> {
+        let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
+
+        self.initiate_ws_upgrade_response(&mut buf).await?;
+        self.complete().await?;
+
+        self.unbind()
+    }
+
     /// Return `true` if the connection is in response state
     pub fn is_response_initiated(&self) -> bool {
         matches!(self, Self::Response(_))
@@ -178,13 +462,72 @@ where
         reason: Option<&str>,
         headers: &[(&str, &str)],
     ) -> Result<(), Error<T::Error>> {
+        use core::fmt::Write as _;
+
+        // Check before touching the connection, so that the handler can still respond with a valid status
+        if !is_valid_status(status, reason) {
+            return Err(Error::InvalidHeaders);
+        }
+
+        let reason = reason.or(reason_phrase(status));
+
         let request = self.request_mut()?;
 
-        let mut buf = [0; COMPLETION_BUF_SIZE];
-        while request.io.read(&mut buf).await? > 0 {}
+        // If the client is still waiting for a `100 Continue`, it would not send the body,
+        // so rather than draining it, close the connection after the response
+        let request_connection_type = if request.continue_pending && !request.io.is_complete() {
+            ConnectionType::Close
+        } else {
+            // Drain the part of the body not read by the handler, so that it is not parsed
+            // as the next request of a keep-alive connection
+            let mut buf = [0; COMPLETION_BUF_SIZE];
+            let drained = loop {
+                match request.io.read(&mut buf).await {
+                    Ok(0) => break true,
+                    Ok(_) => (),
+                    Err(Error::IncompleteBody) => break false,
+                    Err(e) => Err(e)?,
+                }
+            };
+
+            if drained {
+                request.connection_type
+            } else {
+                // The peer closed the connection mid-body, so there is no next request to wait for
+                ConnectionType::Close
+            }
+        };
 
         let http11 = request.request.http11;
-        let request_connection_type = request.connection_type;
+        let default_headers = request.default_headers;
+        let added_headers = request.added_headers.clone();
+
+        let contains = |headers: &[(&str, &str)], name: &str| {
+            headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+        };
+        let has_header = |name: &str| {
+            contains(headers, name) || added_headers.iter().any(|added| contains(added, name))
+        };
+
+        let mut date = heapless::String::<HTTP_DATE_LEN>::new();
+        if !has_header("Date") {
+            if let Some(now) = default_headers.clock.and_then(|clock| clock.now()) {
+                // A clock too far off to be formatted is treated like a clock not knowing the time
+                if write!(&mut date, "{}", HttpDate(now)).is_err() {
+                    date.clear();
+                }
+            }
+        }
+
+        let added_headers = added_headers
+            .iter()
+            .flat_map(|added| added.iter())
+            .filter(|(name, _)| !contains(headers, name));
+        let date_header = (!date.is_empty()).then_some(("Date", date.as_str()));
+        let server_header = default_headers
+            .server
+            .filter(|_| !has_header("Server"))
+            .map(|server| ("Server", server));
 
         let mut io = self.unbind_mut();
 
@@ -192,7 +535,11 @@ where
             send_status(http11, status, reason, &mut io).await?;
 
             let (connection_type, body_type) = send_headers(
-                headers.iter(),
+                headers
+                    .iter()
+                    .chain(added_headers)
+                    .chain(date_header.as_ref())
+                    .chain(server_header.as_ref()),
                 Some(request_connection_type),
                 false,
                 http11,
@@ -285,6 +632,8 @@ where
     T: Read + Write,
 {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.send_continue().await?;
+
         self.request_mut()?.io.read(buf).await
     }
 }
@@ -308,6 +657,9 @@ struct RequestState<'b, T, const N: usize> {
     request: RequestHeaders<'b, N>,
     io: Body<'b, T>,
     connection_type: ConnectionType,
+    continue_pending: bool,
+    default_headers: DefaultHeaders<'b>,
+    added_headers: heapless::Vec<&'b [(&'b str, &'b str)], MAX_ADDED_RESPONSE_HEADERS>,
 }
 
 struct ResponseState<T> {
@@ -425,12 +777,249 @@ where
     }
 }
 
+/// A trait (async callback) for handling the incoming HTTP requests matching a route of a `Router`
+///
+/// Implemented by the values registered with the routes, i.e. an enum of the endpoints of an API,
+/// so that `Router` dispatches requests without any allocation or dynamic dispatch.
+pub trait RouteHandler {
+    type Error<E>: Debug
+    where
+        E: Debug;
+
+    /// Handle an incoming HTTP request matching the route
+    ///
+    /// Parameters:
+    /// - `task_id`: An identifier for the task, that can be used by the handler for logging purposes
+    /// - `params`: The path parameters captured by the pattern of the route
+    /// - `connection`: A connection state machine for the request-response cycle
+    async fn handle<T, const N: usize, const P: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        params: &PathParams<'_, '_, P>,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit;
+}
+
+impl<H> RouteHandler for &H
+where
+    H: RouteHandler,
+{
+    type Error<E>
+        = H::Error<E>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize, const P: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        params: &PathParams<'_, '_, P>,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit,
+    {
+        (**self).handle(task_id, params, connection).await
+    }
+}
+
+/// A router is a handler dispatching each request to the `RouteHandler` of the route matching it.
+///
+/// Requests matching no route get a `404 Not Found` response, and requests matching the path
+/// but not the method of any route get a `405 Method Not Allowed` response.
+impl<R, const M: usize, const P: usize> Handler for Router<'_, R, M, P>
+where
+    R: RouteHandler,
+{
+    type Error<E>
+        = HandlerError<E, R::Error<E>>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit,
+    {
+        let headers = connection.headers()?;
+        let (method, path) = (headers.method, headers.path);
+
+        match self.route(method, path) {
+            Ok((route, params)) => route
+                .handle(task_id, &params, connection)
+                .await
+                .map_err(HandlerError::Handler)?,
+            Err(RouteError::NotFound) => {
+                debug!(
+                    "Handler task {}: No route for {} {}",
+                    display2format!(task_id),
+                    method,
+                    path
+                );
+
+                connection.initiate_response(404, None, &[]).await?;
+            }
+            Err(RouteError::MethodNotAllowed) => {
+                debug!(
+                    "Handler task {}: Method {} not allowed for {}",
+                    display2format!(task_id),
+                    method,
+                    path
+                );
+
+                let mut allow = heapless::String::<ALLOW_HEADER_LEN>::new();
+
+                for method in self.allowed_methods(path) {
+                    let method = method.as_str();
+
+                    if !allow.split(", ").any(|allowed| allowed == method) {
+                        if !allow.is_empty() {
+                            let _ = allow.push_str(", ");
+                        }
+
+                        let _ = allow.push_str(method);
+                    }
+                }
+
+                connection
+                    .initiate_response(405, None, &[("Allow", &allow)])
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The maximum length of the `Allow` header of the `405 Method Not Allowed` responses of `Router`
+const ALLOW_HEADER_LEN: usize = 128;
+
+/// A trait (async callback) for wrapping a `Handler` with cross-cutting logic, i.e. authentication,
+/// request logging or CORS preflight responses
+///
+/// A middleware gets the request before the handler does, and either short-circuits it by sending
+/// a response itself (i.e. `401 Unauthorized`), or delegates it to the handler, after which it can run
+/// further logic (i.e. log the outcome). Note that the handler sends the response headers as part of
+/// handling the request, so they cannot be altered after delegating; a middleware contributes its response
+/// headers by adding them with `Connection::add_response_headers` before delegating instead.
+///
+/// Use `Middleware::compose` to wrap a handler (or another composed middleware) with a middleware.
+pub trait Middleware {
+    /// Handle an incoming HTTP request, possibly delegating it to `handler`
+    ///
+    /// Parameters:
+    /// - `task_id`: An identifier for the task, that can be used by the middleware for logging purposes
+    /// - `connection`: A connection state machine for the request-response cycle
+    /// - `handler`: The wrapped handler
+    async fn handle<H, T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+        handler: &H,
+    ) -> Result<(), HandlerError<T::Error, H::Error<T::Error>>>
+    where
+        H: Handler,
+        T: Read + Write + TcpSplit;
+
+    /// Wrap the provided handler with this middleware
+    fn compose<H>(self, handler: H) -> MiddlewareHandler<Self, H>
+    where
+        Self: Sized,
+        H: Handler,
+    {
+        MiddlewareHandler::new(self, handler)
+    }
+}
+
+impl<M> Middleware for &M
+where
+    M: Middleware,
+{
+    async fn handle<H, T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+        handler: &H,
+    ) -> Result<(), HandlerError<T::Error, H::Error<T::Error>>>
+    where
+        H: Handler,
+        T: Read + Write + TcpSplit,
+    {
+        (**self).handle(task_id, connection, handler).await
+    }
+}
+
+/// A handler wrapped with a middleware (see `Middleware::compose`)
+pub struct MiddlewareHandler<M, H> {
+    middleware: M,
+    handler: H,
+}
+
+impl<M, H> MiddlewareHandler<M, H> {
+    /// Create a new handler, which passes the requests to `middleware`, delegating to `handler`
+    pub const fn new(middleware: M, handler: H) -> Self {
+        Self {
+            middleware,
+            handler,
+        }
+    }
+
+    /// Return a reference to the middleware
+    pub fn middleware(&self) -> &M {
+        &self.middleware
+    }
+
+    /// Return a reference to the wrapped handler
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<M, H> Handler for MiddlewareHandler<M, H>
+where
+    M: Middleware,
+    H: Handler,
+{
+    type Error<E>
+        = HandlerError<E, H::Error<E>>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit,
+    {
+        self.middleware
+            .handle(task_id, connection, &self.handler)
+            .await
+    }
+}
+
 /// A convenience function to handle multiple HTTP requests over a single socket stream,
 /// using the specified handler.
 ///
 /// The socket stream will be closed only in case of error, or until the client explicitly requests that
 /// either with a hard socket close, or with a `Connection: Close` header.
 ///
+/// A note on keep-alive:
+/// - The connection is kept open and the next request is read from the same socket stream when the
+///   request is HTTP/1.1 (or HTTP/1.0 with `Connection: Keep-Alive`) and the response body is framed
+///   either with `Content-Length` or with chunked transfer-encoding.
+/// - Any request body not consumed by the handler is drained before the response is sent, so that the
+///   next request on the connection is read from a clean state.
+/// - Responses with a raw (unframed) body, responses with an incomplete `Content-Length` body and
+///   responses to requests which were not properly completed always close the connection.
+/// - When the server closes the connection, it closes its write half first and drains whatever the client
+///   still sends (for up to a second), so that the client gets the last response rather than a reset.
+///
 /// A note on timeouts:
 /// - The function does NOT - by default - establish any timeouts on the IO operations _except_
 ///   an optional timeout for detecting idle connections, so that they can be closed and thus make
@@ -450,7 +1039,7 @@ where
 /// - `task_id`: An identifier for the task, used for logging purposes
 /// - `handler`: An implementation of `Handler` to handle incoming requests
 pub async fn handle_connection<H, T, const N: usize>(
-    mut io: T,
+    io: T,
     buf: &mut [u8],
     keepalive_timeout_ms: Option<u32>,
     task_id: impl Display + Copy,
@@ -458,6 +1047,95 @@ pub async fn handle_connection<H, T, const N: usize>(
 ) where
     H: Handler,
     T: Read + Write + Readable + TcpSplit + TcpShutdown,
+{
+    let config = ConnectionConfig {
+        keepalive_timeout_ms,
+        ..ConnectionConfig::new()
+    };
+
+    handle_connection_with_config::<_, _, N>(io, buf, config, task_id, handler).await
+}
+
+/// The configuration of the connections of the server, see `handle_connection_with_config`
+#[derive(Copy, Clone, Debug)]
+pub struct ConnectionConfig<'a> {
+    /// An optional timeout in milliseconds for detecting an idle keepalive connection that should be closed.
+    /// If not provided, idle connections are not closed.
+    pub keepalive_timeout_ms: Option<u32>,
+    /// An optional timeout in milliseconds for receiving the headers of a request.
+    ///
+    /// If a client does not send the complete headers of a request within the timeout (i.e. because it
+    /// stalled mid-request), a `408 Request Timeout` response is sent and the connection is closed,
+    /// so that slow or stalled clients cannot occupy the buffer of the connection indefinitely.
+    /// When `keepalive_timeout_ms` is provided, the request timeout starts once the connection becomes readable,
+    /// otherwise it also covers the time waiting for the request to arrive.
+    /// If not provided, the request headers are waited for indefinitely.
+    pub request_timeout_ms: Option<u32>,
+    /// The headers to be added to each response, unless the handler provides them
+    pub default_headers: DefaultHeaders<'a>,
+    /// The limits on the headers section of each request
+    pub header_limits: HeaderLimits,
+}
+
+impl<'a> ConnectionConfig<'a> {
+    /// Create the default configuration: no timeouts, the `DefaultHeaders::new()` headers and no header limits
+    pub const fn new() -> Self {
+        Self {
+            keepalive_timeout_ms: None,
+            request_timeout_ms: None,
+            default_headers: DefaultHeaders::new(),
+            header_limits: HeaderLimits::new(),
+        }
+    }
+
+    /// Set the timeout in milliseconds for detecting an idle keepalive connection
+    pub const fn with_keepalive_timeout(mut self, keepalive_timeout_ms: u32) -> Self {
+        self.keepalive_timeout_ms = Some(keepalive_timeout_ms);
+        self
+    }
+
+    /// Set the timeout in milliseconds for receiving the headers of a request
+    pub const fn with_request_timeout(mut self, request_timeout_ms: u32) -> Self {
+        self.request_timeout_ms = Some(request_timeout_ms);
+        self
+    }
+
+    /// Set the headers to be added to each response, unless the handler provides them
+    pub const fn with_default_headers(mut self, default_headers: DefaultHeaders<'a>) -> Self {
+        self.default_headers = default_headers;
+        self
+    }
+
+    /// Set the limits on the headers section of each request
+    pub const fn with_header_limits(mut self, header_limits: HeaderLimits) -> Self {
+        self.header_limits = header_limits;
+        self
+    }
+}
+
+impl Default for ConnectionConfig<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Same as `handle_connection`, but with the provided configuration (see `ConnectionConfig`).
+///
+/// Parameters:
+/// - `io`: A socket stream
+/// - `buf`: A work-area buffer used by the implementation
+/// - `config`: The timeouts, the default response headers and the request header limits of the connection
+/// - `task_id`: An identifier for the task, used for logging purposes
+/// - `handler`: An implementation of `Handler` to handle incoming requests
+pub async fn handle_connection_with_config<H, T, const N: usize>(
+    mut io: T,
+    buf: &mut [u8],
+    config: ConnectionConfig<'_>,
+    task_id: impl Display + Copy,
+    handler: H,
+) where
+    H: Handler,
+    T: Read + Write + Readable + TcpSplit + TcpShutdown,
 {
     let close = loop {
         debug!(
@@ -465,7 +1143,7 @@ pub async fn handle_connection<H, T, const N: usize>(
             display2format!(task_id)
         );
 
-        if let Some(keepalive_timeout_ms) = keepalive_timeout_ms {
+        if let Some(keepalive_timeout_ms) = config.keepalive_timeout_ms {
             let wait_data = with_timeout(keepalive_timeout_ms, io.readable()).await;
             match wait_data {
                 Err(WithTimeoutError::Timeout) => {
@@ -487,7 +1165,7 @@ pub async fn handle_connection<H, T, const N: usize>(
             }
         }
 
-        let result = handle_request::<_, _, N>(buf, &mut io, task_id, &handler).await;
+        let result = handle_timed_request::<_, _, N>(buf, &mut io, config, task_id, &handler).await;
 
         match result {
             Err(HandlerError::Connection(Error::ConnectionClosed)) => {
@@ -523,18 +1201,112 @@ pub async fn handle_connection<H, T, const N: usize>(
     };
 
     if close {
-        if let Err(e) = io.close(Close::Both).await {
-            warn!(
-                "Handler task {}: Error when closing the socket: {:?}",
-                display2format!(task_id),
-                debug2format!(e)
-            );
-        }
+        // The rest of a request answered early (i.e. with a 408 or a 431) might still be unread
+        drain_and_close(&mut io, task_id).await;
     } else {
         let _ = io.abort().await;
     }
 }
 
+/// A utility method to reject an incoming connection with a `503 Service Unavailable` response.
+///
+/// The response is sent without parsing the request, and the connection is closed afterwards.
+/// Useful when the server is at capacity, so that the peer gets a proper HTTP reply rather than
+/// a connection which is never accepted (or reset by the TCP stack).
+///
+/// As closing a socket with unread data resets the connection (and the peer might then discard
+/// the response), the write half is closed first and the request is drained - for up to
+/// `DRAIN_TIMEOUT_MS` milliseconds and `DRAIN_MAX_LEN` bytes - before closing the connection.
+pub async fn reject_connection<T>(mut io: T, task_id: impl Display + Copy)
+where
+    T: Read + Write + TcpShutdown,
+{
+    debug!(
+        "Handler task {}: Rejecting connection, server at capacity",
+        display2format!(task_id)
+    );
+
+    let result = send_close_response(503, "Service Unavailable", &mut io).await;
+
+    if let Err(e) = result {
+        warn!(
+            "Handler task {}: Error when rejecting connection: {:?}",
+            display2format!(task_id),
+            debug2format!(e)
+        );
+    }
+
+    drain_and_close(io, task_id).await;
+}
+
+/// Close the write half of the socket, then read and discard whatever the peer still sends
+/// until it closes its write half too (bounded by `DRAIN_TIMEOUT_MS` and `DRAIN_MAX_LEN`),
+/// and finally close the socket
+async fn drain_and_close<T>(mut io: T, task_id: impl Display + Copy)
+where
+    T: Read + TcpShutdown,
+{
+    let result = async {
+        io.close(Close::Write).await?;
+
+        let mut buf = [0; COMPLETION_BUF_SIZE];
+        let mut drained = 0;
+
+        while drained < DRAIN_MAX_LEN {
+            match io.read(&mut buf).await? {
+                0 => break,
+                len => drained += len,
+            }
+        }
+
+        Ok::<_, T::Error>(())
+    };
+
+    if let Err(WithTimeoutError::Error(e)) = with_timeout(DRAIN_TIMEOUT_MS, result).await {
+        warn!(
+            "Handler task {}: Error when draining the socket: {:?}",
+            display2format!(task_id),
+            debug2format!(e)
+        );
+    }
+
+    if let Err(e) = io.close(Close::Both).await {
+        warn!(
+            "Handler task {}: Error when closing the socket: {:?}",
+            display2format!(task_id),
+            debug2format!(e)
+        );
+    }
+}
+
+async fn accept_backoff() {
+    let _ = with_timeout(
+        ACCEPT_BACKOFF_MS,
+        core::future::pending::<Result<(), core::convert::Infallible>>(),
+    )
+    .await;
+}
+
+/// Send an empty response with the provided status, which also tells the client that the connection
+/// is about to be closed
+async fn send_close_response<T>(status: u16, reason: &str, mut io: T) -> Result<(), Error<T::Error>>
+where
+    T: Write,
+{
+    send_status(true, status, Some(reason), &mut io).await?;
+    send_headers(
+        &[("Content-Length", "0"), ("Connection", "close")],
+        None,
+        false,
+        true,
+        false,
+        &mut io,
+    )
+    .await?;
+
+    io.flush().await.map_err(Error::Io)
+}
+
 /// The error type for handling HTTP requests
 #[derive(Debug)]
 pub enum HandleRequestError<C, E> {
@@ -620,7 +1392,81 @@ where
     H: Handler,
     T: Read + Write + TcpSplit,
 {
-    let mut connection = Connection::<_, N>::new(buf, io).await?;
+    handle_request_with_config::<_, _, N>(buf, io, ConnectionConfig::new(), task_id, handler).await
+}
+
+/// Same as `handle_request`, but with the provided configuration (see `ConnectionConfig`).
+///
+/// Since a single request is handled, the `keepalive_timeout_ms` of the configuration is not used.
+///
+/// Parameters:
+/// - `buf`: A work-area buffer used by the implementation
+/// - `io`: A socket stream
+/// - `config`: The request timeout, the default response headers and the request header limits
+/// - `task_id`: An identifier for the task, used for logging purposes
+/// - `handler`: An implementation of `Handler` to handle incoming requests
+pub async fn handle_request_with_config<H, T, const N: usize>(
+    buf: &mut [u8],
+    io: T,
+    config: ConnectionConfig<'_>,
+    task_id: impl Display + Copy,
+    handler: H,
+) -> Result<bool, HandlerError<T::Error, H::Error<T::Error>>>
+where
+    H: Handler,
+    T: Read + Write + TcpSplit,
+{
+    handle_timed_request::<_, _, N>(buf, io, config, task_id, handler).await
+}
+
+async fn handle_timed_request<'b, H, T, const N: usize>(
+    buf: &'b mut [u8],
+    mut io: T,
+    config: ConnectionConfig<'b>,
+    task_id: impl Display + Copy,
+    handler: H,
+) -> Result<bool, HandlerError<T::Error, H::Error<T::Error>>>
+where
+    H: Handler,
+    T: Read + Write + TcpSplit,
+{
+    let connection = Connection::<_, N>::new_with_header_limits(buf, &mut io, config.header_limits);
+
+    let connection = if let Some(request_timeout_ms) = config.request_timeout_ms {
+        match with_timeout(request_timeout_ms, connection).await {
+            Ok(connection) => Ok(connection),
+            Err(WithTimeoutError::Error(e)) => Err(e),
+            Err(WithTimeoutError::Timeout) => {
+                info!(
+                    "Handler task {}: Timeout when receiving request",
+                    display2format!(task_id)
+                );
+
+                send_close_response(408, "Request Timeout", &mut io).await?;
+
+                return Ok(true);
+            }
+        }
+    } else {
+        connection.await
+    };
+
+    let mut connection = match connection {
+        Ok(connection) => connection,
+        Err(Error::TooLongHeaders | Error::TooManyHeaders) => {
+            info!(
+                "Handler task {}: Request headers too large",
+                display2format!(task_id)
+            );
+
+            send_close_response(431, "Request Header Fields Too Large", &mut io).await?;
+
+            return Ok(true);
+        }
+        Err(e) => Err(e)?,
+    };
+
+    connection.set_default_headers(config.default_headers)?;
 
     let result = handler.handle(task_id, &mut connection).await;
 
@@ -645,25 +1491,61 @@ pub type ServerBuffers<const P: usize, const B: usize> = MaybeUninit<[[u8; B]; P
 /// An HTTP server that can handle multiple requests concurrently.
 ///
 /// The server needs an implementation of `edge_nal::TcpAccept` to accept incoming connections.
-#[repr(transparent)]
 pub struct Server<
     const P: usize = DEFAULT_HANDLER_TASKS_COUNT,
     const B: usize = DEFAULT_BUF_SIZE,
     const N: usize = DEFAULT_MAX_HEADERS_COUNT,
->(ServerBuffers<P, B>);
+> {
+    buffers: ServerBuffers<P, B>,
+    config: ConnectionConfig<'static>,
+}
 
 impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
     /// Create a new HTTP server
     #[inline(always)]
     pub const fn new() -> Self {
-        Self(MaybeUninit::uninit())
+        Self {
+            buffers: MaybeUninit::uninit(),
+            config: ConnectionConfig::new(),
+        }
+    }
+
+    /// Set a timeout in milliseconds for receiving the headers of each incoming request
+    ///
+    /// A client which stalls mid-request for longer than the timeout gets a `408 Request Timeout`
+    /// response and its connection is closed, without affecting the other connections of the server.
+    ///
+    /// By default, no such timeout is set.
+    pub const fn with_request_timeout(mut self, request_timeout_ms: u32) -> Self {
+        self.config = self.config.with_request_timeout(request_timeout_ms);
+        self
+    }
+
+    /// Set the headers to be added automatically to each response, unless the handler provides them
+    ///
+    /// By default, only `Server: edge-http` is added. Use `DefaultHeaders::with_clock` to also add
+    /// a `Date` header, and `DefaultHeaders::none()` to opt out of the automatic headers altogether.
+    pub const fn with_default_headers(mut self, default_headers: DefaultHeaders<'static>) -> Self {
+        self.config = self.config.with_default_headers(default_headers);
+        self
+    }
+
+    /// Set the limits on the headers section of each incoming request
+    ///
+    /// A client exceeding the limits gets a `431 Request Header Fields Too Large` response and its
+    /// connection is closed. By default, the headers section is only limited by the size `B` of the buffers
+    /// and by the maximum number of headers `N` (see `HeaderLimits`).
+    pub const fn with_header_limits(mut self, header_limits: HeaderLimits) -> Self {
+        self.config = self.config.with_header_limits(header_limits);
+        self
     }
 
     /// Run the server with the specified acceptor and handler
     ///
     /// A note on timeouts:
     /// - The function does NOT - by default - establish any timeouts on the IO operations _except_
-    ///   an optional timeout on idle connections, so that they can be closed.
+    ///   an optional timeout on idle connections, so that they can be closed, and the optional
+    ///   request timeout configured with `with_request_timeout`.
     ///   It is up to the caller to wrap the acceptor type with `edge_nal::WithTimeout` to establish
     ///   timeouts on the socket produced by the acceptor.
     /// - Similarly, the function does NOT establish any timeouts on the complete request-response cycle.
@@ -685,7 +1567,8 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
     /// Consider using `run_with_socket_queue()` instead for better connection handling
     /// with TCP stacks that lack accept queues (e.g., smoltcp/embassy-net). The socket queue architecture
     /// decouples connection acceptance from HTTP processing, allowing connections to be accepted even when
-    /// all worker tasks are busy.
+    /// all worker tasks are busy. Use `run_with_socket_queue_and_reject()` if connections arriving
+    /// while the server is at capacity should be answered with `503 Service Unavailable`.
     ///
     /// Parameters:
     /// - `keepalive_timeout_ms`: An optional timeout in milliseconds for detecting an idle keepalive
@@ -706,59 +1589,21 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
         A: edge_nal::TcpAccept,
         H: Handler,
     {
-        let mut tasks = heapless::Vec::<_, P>::new();
-
-        info!(
-            "Creating {} handler tasks, memory: {}B",
-            P,
-            core::mem::size_of_val(&tasks)
-        );
-
-        for index in 0..P {
-            let acceptor = &acceptor;
-            let task_id = index;
-            let handler = &handler;
-            let buf: *mut [u8; B] = &mut unsafe { self.0.assume_init_mut() }[index];
-
-            unwrap!(tasks
-                .push(async move {
-                    loop {
-                        debug!(
-                            "Handler task {}: Waiting for connection",
-                            display2format!(task_id)
-                        );
-
-                        let io = acceptor.accept().await.map_err(Error::Io)?.1;
-
-                        debug!(
-                            "Handler task {}: Got connection request",
-                            display2format!(task_id)
-                        );
-
-                        handle_connection::<_, _, N>(
-                            io,
-                            unwrap!(unsafe { buf.as_mut() }),
-                            keepalive_timeout_ms,
-                            task_id,
-                            handler,
-                        )
-                        .await;
-                    }
-                })
-                .map_err(|_| ()));
-        }
-
-        let tasks = pin!(tasks);
-
-        let tasks = unsafe { tasks.map_unchecked_mut(|t| t.as_mut_slice()) };
-        let (result, _) = embassy_futures::select::select_slice(tasks).await;
-
-        warn!(
-            "Server processing loop quit abruptly: {:?}",
-            debug2format!(result)
-        );
-
-        result
+        let config = ConnectionConfig {
+            keepalive_timeout_ms,
+            ..self.config
+        };
+        // Derive the pointer to the buffer of each task once, so that only the buffer of one task
+        // is ever reborrowed, and never the whole array, whose other buffers might be in use
+        let buffers = self.buffers.as_mut_ptr().cast::<[u8; B]>();
+        let buffers: [*mut [u8; B]; P] =
+            core::array::from_fn(|task_id| unsafe { buffers.add(task_id) });
+
+        // Each task has its own buffer, so the buffer of a task is never handed out twice at the same time
+        run_tasks::<_, _, _, _, P, N>(acceptor, handler, config, |task_id| {
+            Some(unwrap!(unsafe { buffers[task_id].as_mut() }).as_mut_slice())
+        })
+        .await
     }
 
     /// Run the server with a socket queue architecture (recommended for smoltcp/embassy-net)
@@ -804,6 +1649,59 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
         A: edge_nal::TcpAccept,
         H: Handler,
     {
+        self.run_queued::<A, H, Q>(keepalive_timeout_ms, acceptor, handler, false)
+            .await
+    }
+
+    /// Run the server with a socket queue architecture, rejecting connections when at capacity
+    ///
+    /// Same as `run_with_socket_queue()`, except that when all `Q` sockets are in use (being processed
+    /// or waiting in the queue for a free worker), the server keeps accepting incoming connections
+    /// and replies to them with `503 Service Unavailable`, closing them immediately afterwards.
+    /// Without this, new TCP connects are not accepted at all while the server is at capacity,
+    /// which - for TCP stacks without accept queues - results in the connect being reset or timing out.
+    ///
+    /// # Important Constraints
+    ///
+    /// The rejection is done by an additional acceptor task, so the smoltcp/embassy-net socket pool
+    /// needs to have room for **`Q + 1`** sockets.
+    ///
+    /// # Parameters
+    ///
+    /// - `keepalive_timeout_ms`: Optional timeout in milliseconds for idle keepalive connections
+    /// - `acceptor`: An implementation of `edge_nal::TcpAccept` to accept incoming connections
+    /// - `handler`: An implementation of `Handler` to handle incoming requests
+    #[cfg(feature = "io")]
+    #[inline(never)]
+    #[cold]
+    pub async fn run_with_socket_queue_and_reject<A, H, const Q: usize>(
+        &mut self,
+        keepalive_timeout_ms: Option<u32>,
+        acceptor: A,
+        handler: H,
+    ) -> Result<(), Error<A::Error>>
+    where
+        A: edge_nal::TcpAccept,
+        H: Handler,
+    {
+        self.run_queued::<A, H, Q>(keepalive_timeout_ms, acceptor, handler, true)
+            .await
+    }
+
+    #[cfg(feature = "io")]
+    async fn run_queued<A, H, const Q: usize>(
+        &mut self,
+        keepalive_timeout_ms: Option<u32>,
+        acceptor: A,
+        handler: H,
+        reject_when_full: bool,
+    ) -> Result<(), Error<A::Error>>
+    where
+        A: edge_nal::TcpAccept,
+        H: Handler,
+    {
+        use core::cell::Cell;
+
         use embassy_sync::blocking_mutex::raw::NoopRawMutex;
         use embassy_sync::channel::Channel;
         use embassy_sync::signal::Signal;
@@ -840,6 +1738,15 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
         // This ensures we never have more sockets in use than available in the pool
         let accept_signals: [Signal<NoopRawMutex, ()>; Q] = [(); Q].map(|_| Signal::new());
 
+        // Number of sockets currently accepted and not yet processed by a worker, and a signal
+        // raised when that number reaches `Q`, i.e. when the server is at capacity
+        let sockets_in_use = Cell::new(0);
+        let saturated = Signal::<NoopRawMutex, ()>::new();
+
+        // Raised when a connection accepted by the overflow acceptor task - and served rather than rejected,
+        // see below - is done with, so that the overflow acceptor task can accept again
+        let overflow_done = Signal::<NoopRawMutex, ()>::new();
+
         // Create Q acceptor tasks - each waits for its signal before accepting
         // This ensures we never have more than (Q - sockets_in_use) acceptors calling accept()
         let mut acceptor_tasks = heapless::Vec::<_, Q>::new();
@@ -852,6 +1759,8 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
         for (acceptor_id, signal) in accept_signals.iter().enumerate() {
             let acceptor = &acceptor;
             let socket_queue = &socket_queue;
+            let sockets_in_use = &sockets_in_use;
+            let saturated = &saturated;
 
             unwrap!(acceptor_tasks
                 .push(async move {
@@ -872,6 +1781,11 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
                                     display2format!(acceptor_id)
                                 );
 
+                                sockets_in_use.set(sockets_in_use.get() + 1);
+                                if sockets_in_use.get() >= Q {
+                                    saturated.signal(());
+                                }
+
                                 // Send the socket along with the acceptor ID to the queue
                                 // This allows workers to signal the correct acceptor when done
                                 socket_queue.send((io, acceptor_id)).await;
@@ -882,11 +1796,21 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
                                 );
                             }
                             Err(e) => {
-                                warn!(
-                                    "Acceptor task {}: Error accepting connection: {:?}",
-                                    display2format!(acceptor_id),
-                                    debug2format!(e)
-                                );
+                                if acceptor.is_backpressure(&e) {
+                                    debug!(
+                                        "Acceptor task {}: Acceptor at capacity, backing off",
+                                        display2format!(acceptor_id)
+                                    );
+
+                                    accept_backoff().await;
+                                } else {
+                                    warn!(
+                                        "Acceptor task {}: Error accepting connection: {:?}",
+                                        display2format!(acceptor_id),
+                                        debug2format!(e)
+                                    );
+                                }
+
                                 // Signal ourselves again to retry
                                 signal.signal(());
                             }
@@ -901,18 +1825,69 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
             signal.signal(());
         }
 
+        // Create the overflow acceptor task, which - when enabled - only accepts connections
+        // while all `Q` sockets are in use, and rejects these with a 503 response
+        //
+        // The capacity is checked again once a connection is accepted, as a worker might have finished
+        // in the meantime, the freed acceptor and the overflow acceptor task then racing for the connection.
+        // If the server is no longer at capacity, the connection is served with the overflow acceptor task's
+        // socket instead (hence the `Q + 1` sockets) and the task only accepts again once it is done with.
+        let overflow_task = async {
+            if !reject_when_full {
+                return core::future::pending().await;
+            }
+
+            loop {
+                if sockets_in_use.get() < Q {
+                    saturated.wait().await;
+                    continue;
+                }
+
+                debug!("Overflow acceptor task: Server at capacity, waiting for connection");
+
+                match acceptor.accept().await {
+                    Ok((_, io)) if sockets_in_use.get() < Q => {
+                        debug!("Overflow acceptor task: No longer at capacity, enqueueing");
+
+                        sockets_in_use.set(sockets_in_use.get() + 1);
+                        if sockets_in_use.get() >= Q {
+                            saturated.signal(());
+                        }
+
+                        socket_queue.send((io, Q)).await;
+                        overflow_done.wait().await;
+                    }
+                    Ok((_, io)) => reject_connection(io, Q).await,
+                    Err(e) if acceptor.is_backpressure(&e) => accept_backoff().await,
+                    Err(e) => {
+                        warn!(
+                            "Overflow acceptor task: Error accepting connection: {:?}",
+                            debug2format!(e)
+                        );
+                    }
+                }
+            }
+        };
+
+        // Derive the pointer to the buffer of each worker task once, so that only the buffer of one task
+        // is ever reborrowed, and never the whole array, whose other buffers might be in use
+        let buffers = self.buffers.as_mut_ptr().cast::<[u8; B]>();
+        let buffers: [*mut [u8; B]; P] =
+            core::array::from_fn(|index| unsafe { buffers.add(index) });
+
         // Create worker tasks
         let mut worker_tasks = heapless::Vec::<_, P>::new();
 
-        for index in 0..P {
-            let task_id = index;
+        for (task_id, buf) in buffers.into_iter().enumerate() {
             let handler = &handler;
             let socket_queue = &socket_queue;
             let accept_signals = &accept_signals;
-            // Safety: The server buffer array is properly initialized (MaybeUninit is used correctly),
-            // and each worker task gets exclusive access to its own buffer slice via its unique index.
-            // The pointer remains valid for the lifetime of the server and the buffer is not moved.
-            let buf: *mut [u8; B] = &mut unsafe { self.0.assume_init_mut() }[index];
+            let sockets_in_use = &sockets_in_use;
+            let overflow_done = &overflow_done;
+            let config = ConnectionConfig {
+                keepalive_timeout_ms,
+                ..self.config
+            };
 
             unwrap!(worker_tasks
                 .push(async move {
@@ -931,10 +1906,10 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
                             display2format!(acceptor_id)
                         );
 
-                        handle_connection::<_, _, N>(
+                        handle_connection_with_config::<_, _, N>(
                             io,
                             unwrap!(unsafe { buf.as_mut() }),
-                            keepalive_timeout_ms,
+                            config,
                             task_id,
                             handler,
                         )
@@ -948,7 +1923,12 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
                             display2format!(task_id),
                             display2format!(acceptor_id)
                         );
-                        accept_signals[acceptor_id].signal(());
+                        sockets_in_use.set(sockets_in_use.get() - 1);
+                        if let Some(signal) = accept_signals.get(acceptor_id) {
+                            signal.signal(());
+                        } else {
+                            overflow_done.signal(());
+                        }
                     }
                 })
                 .map_err(|_| ()));
@@ -963,8 +1943,8 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
 
         // Run all acceptor and worker tasks concurrently
         // Use select to run both acceptors and workers, return if any completes
-        use embassy_futures::select::Either;
-        let result = embassy_futures::select::select(
+        use embassy_futures::select::Either3;
+        let result = embassy_futures::select::select3(
             async {
                 let (result, _acceptor_index): (Result<(), Error<A::Error>>, _) =
                     embassy_futures::select::select_slice(acceptor_tasks).await;
@@ -975,27 +1955,29 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
                     embassy_futures::select::select_slice(worker_tasks).await;
                 result
             },
+            overflow_task,
         )
         .await;
 
         // Neither acceptor nor worker tasks should complete normally
         match result {
-            Either::First(Err(e)) => {
+            Either3::First(Err(e)) => {
                 warn!("Acceptor task quit with error: {:?}", debug2format!(e));
                 Err(e)
             }
-            Either::First(Ok(_)) => {
+            Either3::First(Ok(_)) => {
                 warn!("Acceptor task quit unexpectedly");
                 Ok(())
             }
-            Either::Second(Err(e)) => {
+            Either3::Second(Err(e)) => {
                 warn!("Worker task quit with error: {:?}", debug2format!(e));
                 Err(e)
             }
-            Either::Second(Ok(_)) => {
+            Either3::Second(Ok(_)) => {
                 warn!("Worker task quit unexpectedly");
                 Ok(())
             }
+            Either3::Third(()) => unreachable!(),
         }
     }
 }
@@ -1005,3 +1987,906 @@ impl<const P: usize, const B: usize, const N: usize> Default for Server<P, B, N>
         Self::new()
     }
 }
+
+/// An HTTP server that can handle multiple requests concurrently, drawing the buffers of its connections
+/// from an `edge_nal::BufferPool`.
+///
+/// Unlike `Server`, which owns one buffer per handler task, this server allocates a buffer from the pool
+/// for each accepted connection and returns it to the pool when the connection is closed.
+/// This way, the memory of several servers (or of a server and other users of the pool) is governed by one pool.
+///
+/// When the pool is exhausted, the accepted connection is rejected with `503 Service Unavailable`.
+pub struct PooledServer<
+    'p,
+    BP,
+    const P: usize = DEFAULT_HANDLER_TASKS_COUNT,
+    const N: usize = DEFAULT_MAX_HEADERS_COUNT,
+> {
+    pool: &'p BP,
+    config: ConnectionConfig<'p>,
+}
+
+impl<'p, BP, const P: usize, const N: usize> PooledServer<'p, BP, P, N>
+where
+    BP: edge_nal::BufferPool,
+{
+    /// Create a new HTTP server drawing its connection buffers from the provided pool
+    pub const fn new(pool: &'p BP) -> Self {
+        Self {
+            pool,
+            config: ConnectionConfig::new(),
+        }
+    }
+
+    /// Set a timeout in milliseconds for receiving the headers of each incoming request
+    ///
+    /// See `Server::with_request_timeout` for details.
+    pub const fn with_request_timeout(mut self, request_timeout_ms: u32) -> Self {
+        self.config = self.config.with_request_timeout(request_timeout_ms);
+        self
+    }
+
+    /// Set the headers to be added automatically to each response, unless the handler provides them
+    ///
+    /// See `Server::with_default_headers` for details.
+    pub const fn with_default_headers(mut self, default_headers: DefaultHeaders<'p>) -> Self {
+        self.config = self.config.with_default_headers(default_headers);
+        self
+    }
+
+    /// Set the limits on the headers section of each incoming request
+    ///
+    /// See `Server::with_header_limits` for details.
+    pub const fn with_header_limits(mut self, header_limits: HeaderLimits) -> Self {
+        self.config = self.config.with_header_limits(header_limits);
+        self
+    }
+
+    /// Run the server with the specified acceptor and handler
+    ///
+    /// Same as `Server::run`, except that each connection gets its buffer from the pool for
+    /// as long as the connection is open.
+    ///
+    /// Parameters:
+    /// - `keepalive_timeout_ms`: An optional timeout in milliseconds for detecting an idle keepalive
+    ///   connection that should be closed. If not provided, the function will not close idle connections.
+    /// - `acceptor`: An implementation of `edge_nal::TcpAccept` to accept incoming connections
+    /// - `handler`: An implementation of `Handler` to handle incoming requests
+    #[inline(never)]
+    #[cold]
+    pub async fn run<A, H>(
+        &self,
+        keepalive_timeout_ms: Option<u32>,
+        acceptor: A,
+        handler: H,
+    ) -> Result<(), Error<A::Error>>
+    where
+        A: edge_nal::TcpAccept,
+        H: Handler,
+    {
+        let config = ConnectionConfig {
+            keepalive_timeout_ms,
+            ..self.config
+        };
+
+        run_tasks::<_, _, _, _, P, N>(acceptor, handler, config, |_| self.pool.alloc()).await
+    }
+}
+
+/// Run `P` handler tasks, each accepting connections with the acceptor and serving them with the handler
+///
+/// The buffer of each accepted connection is provided by `buffer`, which is called with the ID of the task,
+/// and is held for as long as the connection is open. When `buffer` returns `None`, the connection is rejected
+/// with `503 Service Unavailable`.
+async fn run_tasks<A, H, F, BF, const P: usize, const N: usize>(
+    acceptor: A,
+    handler: H,
+    config: ConnectionConfig<'_>,
+    buffer: F,
+) -> Result<(), Error<A::Error>>
+where
+    A: edge_nal::TcpAccept,
+    H: Handler,
+    F: Fn(usize) -> Option<BF>,
+    BF: DerefMut<Target = [u8]>,
+{
+    let mut tasks = heapless::Vec::<_, P>::new();
+
+    info!(
+        "Creating {} handler tasks, memory: {}B",
+        P,
+        core::mem::size_of_val(&tasks)
+    );
+
+    for index in 0..P {
+        let acceptor = &acceptor;
+        let task_id = index;
+        let handler = &handler;
+        let buffer = &buffer;
+
+        unwrap!(tasks
+            .push(async move {
+                loop {
+                    debug!(
+                        "Handler task {}: Waiting for connection",
+                        display2format!(task_id)
+                    );
+
+                    let io = match acceptor.accept().await {
+                        Ok((_, io)) => io,
+                        Err(e) if acceptor.is_backpressure(&e) => {
+                            debug!(
+                                "Handler task {}: Acceptor at capacity, backing off",
+                                display2format!(task_id)
+                            );
+
+                            accept_backoff().await;
+                            continue;
+                        }
+                        Err(e) => Err(Error::Io(e))?,
+                    };
+
+                    let Some(mut buf) = buffer(task_id) else {
+                        warn!(
+                            "Handler task {}: No buffers available",
+                            display2format!(task_id)
+                        );
+
+                        reject_connection(io, task_id).await;
+                        continue;
+                    };
+
+                    debug!(
+                        "Handler task {}: Got connection request",
+                        display2format!(task_id)
+                    );
+
+                    handle_connection_with_config::<_, _, N>(
+                        io, &mut buf, config, task_id, handler,
+                    )
+                    .await;
+                }
+            })
+            .map_err(|_| ()));
+    }
+
+    let tasks = pin!(tasks);
+
+    let tasks = unsafe { tasks.map_unchecked_mut(|t| t.as_mut_slice()) };
+    let (result, _) = embassy_futures::select::select_slice(tasks).await;
+
+    warn!(
+        "Server processing loop quit abruptly: {:?}",
+        debug2format!(result)
+    );
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use core::fmt::Display;
+    use core::pin::pin;
+
+    use edge_nal::{Close, TcpSplit};
+
+    use embassy_sync::signal::Signal;
+
+    use embedded_io_async::{Read, Write};
+
+    use crate::io::testing::{
+        Acceptor, Duplex, OkHandler, Peer, SliceRead, SlowHandler, Socket, VecWrite,
+    };
+    use crate::io::Error;
+
+    #[test]
+    fn test_pipelined_requests() {
+        use super::Connection;
+
+        embassy_futures::block_on(async move {
+            let mut io = Duplex(
+                SliceRead(
+                    b"POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /b HTTP/1.1\r\n\r\n",
+                ),
+                VecWrite(heapless::Vec::new()),
+            );
+
+            // The first handler reads only part of the body
+            {
+                let mut buf = [0; 128];
+                let mut connection =
+                    unwrap!(pin!(Connection::<_, 4>::new(&mut buf, &mut io)).await);
+                assert_eq!(unwrap!(connection.headers()).path, "/a");
+
+                let mut body = [0; 2];
+                assert_eq!(unwrap!(pin!(connection.read(&mut body)).await), 2);
+                assert_eq!(&body, b"he");
+
+                unwrap!(pin!(connection.initiate_response(200, None, &[])).await);
+                unwrap!(pin!(connection.complete()).await);
+                assert!(!connection.needs_close());
+            }
+
+            // The second handler reads past the end of its (empty) body
+            {
+                let mut buf = [0; 128];
+                let mut connection =
+                    unwrap!(pin!(Connection::<_, 4>::new(&mut buf, &mut io)).await);
+                assert_eq!(unwrap!(connection.headers()).path, "/b");
+
+                let mut body = [0; 8];
+                assert_eq!(unwrap!(pin!(connection.read(&mut body)).await), 0);
+            }
+        })
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        use super::{handle_connection, Connection};
+
+        const REQUEST: &[u8] =
+            b"POST /a HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello";
+
+        // The `100 Continue` is sent once the handler reads the body, and before the response
+        let peer = Peer::default();
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 128];
+            let mut connection = unwrap!(
+                pin!(Connection::<_, 4>::new(
+                    &mut buf,
+                    Socket::new(REQUEST, &peer)
+                ))
+                .await
+            );
+
+            assert!(peer.written.borrow().is_empty());
+
+            let mut body = [0; 8];
+            assert_eq!(unwrap!(pin!(connection.read(&mut body)).await), 5);
+            assert_eq!(&body[..5], b"hello");
+
+            assert_eq!(
+                peer.written.borrow().as_slice(),
+                b"HTTP/1.1 100 Continue\r\n\r\n"
+            );
+
+            unwrap!(pin!(connection.initiate_response(200, None, &[])).await);
+            unwrap!(pin!(connection.complete()).await);
+            assert!(!connection.needs_close());
+        });
+
+        assert!(peer
+            .written
+            .borrow()
+            .starts_with(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+
+        // When the handler responds without reading the body, the client never sends it, so rather
+        // than being drained - here, up to the next pipelined request - the connection is closed
+        let peer = Peer::default();
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 256];
+
+            pin!(handle_connection::<_, _, 4>(
+                Socket::new(
+                    b"POST /a HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n\
+                    helloGET /b HTTP/1.1\r\n\r\n",
+                    &peer
+                ),
+                &mut buf,
+                None,
+                0,
+                OkHandler,
+            ))
+            .await;
+        });
+
+        let written = peer.written.borrow();
+        let response = unwrap!(core::str::from_utf8(&written));
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\nConnection: Close\r\n"));
+        assert!(!response.contains("100 Continue"));
+        assert_eq!(response.matches("HTTP/1.1 ").count(), 1);
+        assert_eq!(
+            peer.closed.borrow().as_slice(),
+            &[Close::Write, Close::Both]
+        );
+    }
+
+    #[test]
+    fn test_range_response() {
+        use super::{handle_connection, Connection, Handler};
+
+        const CONTENT: &[u8] = b"0123456789";
+
+        /// Serves `CONTENT`, or the byte range of it requested
+        struct RangeHandler;
+
+        impl Handler for RangeHandler {
+            type Error<E>
+                = Error<E>
+            where
+                E: core::fmt::Debug;
+
+            async fn handle<T, const N: usize>(
+                &self,
+                _task_id: impl Display + Copy,
+                connection: &mut Connection<'_, T, N>,
+            ) -> Result<(), Self::Error<T::Error>>
+            where
+                T: Read + Write + TcpSplit,
+            {
+                let range = connection
+                    .initiate_range_response(CONTENT.len() as _, &[("Content-Type", "text/plain")])
+                    .await?;
+
+                if let Some(range) = range {
+                    connection
+                        .write_all(&CONTENT[range.start as usize..range.end as usize])
+                        .await?;
+                }
+
+                Ok(())
+            }
+        }
+
+        for (range, status, headers, body) in [
+            (
+                "bytes=2-5",
+                "206 Partial Content",
+                &["Content-Length: 4", "Content-Range: bytes 2-5/10"][..],
+                "2345",
+            ),
+            (
+                "bytes=-3",
+                "206 Partial Content",
+                &["Content-Length: 3", "Content-Range: bytes 7-9/10"],
+                "789",
+            ),
+            (
+                "bytes=10-",
+                "416 Range Not Satisfiable",
+                &["Content-Length: 0", "Content-Range: bytes */10"],
+                "",
+            ),
+            ("lines=1-2", "200 OK", &["Content-Length: 10"], "0123456789"),
+        ] {
+            let mut request = heapless::String::<64>::new();
+            unwrap!(request.push_str("GET / HTTP/1.1\r\nRange: "));
+            unwrap!(request.push_str(range));
+            unwrap!(request.push_str("\r\n\r\n"));
+
+            let peer = Peer::default();
+
+            embassy_futures::block_on(async {
+                let mut buf = [0; 256];
+
+                pin!(handle_connection::<_, _, 4>(
+                    Socket::new(request.as_bytes(), &peer),
+                    &mut buf,
+                    None,
+                    0,
+                    RangeHandler,
+                ))
+                .await;
+            });
+
+            let written = peer.written.borrow();
+            let response = unwrap!(core::str::from_utf8(&written));
+            let (head, response_body) = unwrap!(response.split_once("\r\n\r\n"));
+            let mut lines = head.split("\r\n");
+
+            assert_eq!(
+                lines.next().and_then(|line| line.strip_prefix("HTTP/1.1 ")),
+                Some(status)
+            );
+
+            let lines = lines.collect::<heapless::Vec<_, 8>>();
+
+            for header in headers.iter().chain(&["Accept-Ranges: bytes"]) {
+                assert!(lines.contains(header));
+            }
+
+            assert_eq!(
+                lines.iter().any(|line| line.starts_with("Content-Range: ")),
+                headers.len() > 1
+            );
+            assert_eq!(response_body, body);
+        }
+    }
+
+    #[test]
+    fn test_router() {
+        use super::{handle_connection, Connection, RouteHandler};
+        use crate::{Method, PathParams, Router};
+
+        /// Responds with the `id` path parameter in an `Id` header
+        struct Device;
+
+        impl RouteHandler for Device {
+            type Error<E>
+                = Error<E>
+            where
+                E: core::fmt::Debug;
+
+            async fn handle<T, const N: usize, const P: usize>(
+                &self,
+                _task_id: impl Display + Copy,
+                params: &PathParams<'_, '_, P>,
+                connection: &mut Connection<'_, T, N>,
+            ) -> Result<(), Self::Error<T::Error>>
+            where
+                T: Read + Write + TcpSplit,
+            {
+                connection
+                    .initiate_response(200, None, &[("Id", unwrap!(params.get("id")))])
+                    .await
+            }
+        }
+
+        let mut router = Router::<'_, _, 4, 1>::new();
+        router
+            .add(Method::Get, "/devices/:id", Device)
+            .add(Method::Put, "/devices/:id", Device)
+            .add(Method::Get, "/devices/:id/state", Device)
+            .add(Method::Delete, "/devices/:id", Device);
+
+        for (request, expected) in [
+            (
+                &b"GET /devices/42 HTTP/1.1\r\n\r\n"[..],
+                &["HTTP/1.1 200 OK", "Id: 42"][..],
+            ),
+            (
+                b"GET /devices HTTP/1.1\r\n\r\n",
+                &["HTTP/1.1 404 Not Found"],
+            ),
+            (
+                b"POST /devices/42 HTTP/1.1\r\n\r\n",
+                &["HTTP/1.1 405 Method Not Allowed", "Allow: GET, PUT, DELETE"],
+            ),
+            (
+                b"DELETE /devices/42/state HTTP/1.1\r\n\r\n",
+                &["HTTP/1.1 405 Method Not Allowed", "Allow: GET"],
+            ),
+        ] {
+            let peer = Peer::default();
+
+            embassy_futures::block_on(async {
+                let mut buf = [0; 256];
+
+                pin!(handle_connection::<_, _, 4>(
+                    Socket::new(request, &peer),
+                    &mut buf,
+                    None,
+                    0,
+                    &router,
+                ))
+                .await;
+            });
+
+            let written = peer.written.borrow();
+            let response = unwrap!(core::str::from_utf8(&written));
+            let mut lines = unwrap!(response.split_once("\r\n\r\n")).0.split("\r\n");
+
+            assert_eq!(lines.next(), Some(expected[0]));
+            assert_eq!(
+                lines
+                    .filter(|line| line.starts_with("Id: ") || line.starts_with("Allow: "))
+                    .collect::<heapless::Vec<_, 2>>(),
+                expected[1..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_limits() {
+        use super::{handle_connection_with_config, Connection, ConnectionConfig, HeaderLimits};
+
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\nAccept: */*\r\n\r\n";
+
+        embassy_futures::block_on(async move {
+            for (limits, expected) in [
+                (HeaderLimits::new(), None),
+                (HeaderLimits::new().with_max_len(REQUEST.len()), None),
+                (
+                    HeaderLimits::new().with_max_len(REQUEST.len() - 1),
+                    Some(Error::TooLongHeaders),
+                ),
+                (HeaderLimits::new().with_max_count(2), None),
+                (
+                    HeaderLimits::new().with_max_count(1),
+                    Some(Error::TooManyHeaders),
+                ),
+            ] {
+                let mut io = Duplex(SliceRead(REQUEST), VecWrite(heapless::Vec::new()));
+                let mut buf = [0; 128];
+
+                let result = pin!(Connection::<_, 4>::new_with_header_limits(
+                    &mut buf, &mut io, limits
+                ))
+                .await;
+
+                assert_eq!(result.err(), expected);
+
+                // The server answers with a 431 and closes the connection, draining it first
+                let peer = Peer::default();
+                let mut socket = Socket::new(REQUEST, &peer);
+
+                pin!(handle_connection_with_config::<_, _, 4>(
+                    &mut socket,
+                    &mut buf,
+                    ConnectionConfig::new().with_header_limits(limits),
+                    0,
+                    OkHandler,
+                ))
+                .await;
+
+                assert!(socket.input.0.is_empty());
+
+                if expected.is_some() {
+                    assert_eq!(
+                        peer.written.borrow().as_slice(),
+                        b"HTTP/1.1 431 Request Header Fields Too Large\r\n\
+                        Content-Length: 0\r\nConnection: close\r\n\r\n"
+                    );
+                    assert_eq!(
+                        peer.closed.borrow().as_slice(),
+                        &[Close::Write, Close::Both]
+                    );
+                } else {
+                    assert!(peer.written.borrow().starts_with(b"HTTP/1.1 200 OK\r\n"));
+                    assert!(peer.aborted.get());
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        use super::{handle_connection_with_config, ConnectionConfig};
+
+        // The peer stalls mid-headers, so the server answers with a 408 and closes the connection
+        let peer = Peer::default();
+        peer.stall.set(true);
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 128];
+
+            pin!(handle_connection_with_config::<_, _, 4>(
+                Socket::new(b"GET / HTTP/1.1\r\nHost: a\r\n", &peer),
+                &mut buf,
+                ConnectionConfig::new().with_request_timeout(100),
+                0,
+                OkHandler,
+            ))
+            .await;
+        });
+
+        assert_eq!(
+            peer.written.borrow().as_slice(),
+            b"HTTP/1.1 408 Request Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+        assert_eq!(
+            peer.closed.borrow().as_slice(),
+            &[Close::Write, Close::Both]
+        );
+    }
+
+    #[test]
+    fn test_middleware() {
+        use super::{
+            handle_connection, Connection, Handler, HandlerError, Middleware,
+            MAX_ADDED_RESPONSE_HEADERS,
+        };
+
+        /// Short-circuits the requests without credentials with a `401 Unauthorized`
+        struct Auth;
+
+        impl Middleware for Auth {
+            async fn handle<H, T, const N: usize>(
+                &self,
+                task_id: impl Display + Copy,
+                connection: &mut Connection<'_, T, N>,
+                handler: &H,
+            ) -> Result<(), HandlerError<T::Error, H::Error<T::Error>>>
+            where
+                H: Handler,
+                T: Read + Write + TcpSplit,
+            {
+                if connection.headers()?.headers.authorization().is_none() {
+                    connection
+                        .initiate_response(401, None, &[("WWW-Authenticate", "Basic")])
+                        .await?;
+
+                    return Ok(());
+                }
+
+                handler
+                    .handle(task_id, connection)
+                    .await
+                    .map_err(HandlerError::Handler)
+            }
+        }
+
+        /// Adds the CORS headers to the responses of the wrapped handler
+        struct Cors;
+
+        impl Middleware for Cors {
+            async fn handle<H, T, const N: usize>(
+                &self,
+                task_id: impl Display + Copy,
+                connection: &mut Connection<'_, T, N>,
+                handler: &H,
+            ) -> Result<(), HandlerError<T::Error, H::Error<T::Error>>>
+            where
+                H: Handler,
+                T: Read + Write + TcpSplit,
+            {
+                connection.add_response_headers(&[
+                    ("Access-Control-Allow-Origin", "*"),
+                    ("Server", "cors"),
+                ])?;
+
+                handler
+                    .handle(task_id, connection)
+                    .await
+                    .map_err(HandlerError::Handler)
+            }
+        }
+
+        let handler = Cors.compose(Auth.compose(OkHandler));
+
+        for (request, expected) in [
+            (
+                &b"GET / HTTP/1.1\r\n\r\n"[..],
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic\r\n",
+            ),
+            (
+                b"GET / HTTP/1.1\r\nAuthorization: Basic YTpi\r\n\r\n",
+                "HTTP/1.1 200 OK\r\n",
+            ),
+        ] {
+            let peer = Peer::default();
+
+            embassy_futures::block_on(async {
+                let mut buf = [0; 256];
+
+                pin!(handle_connection::<_, _, 4>(
+                    Socket::new(request, &peer),
+                    &mut buf,
+                    None,
+                    0,
+                    &handler,
+                ))
+                .await;
+            });
+
+            let written = peer.written.borrow();
+            let response = unwrap!(core::str::from_utf8(&written));
+
+            // The headers added by the middleware are sent whether the handler or another middleware
+            // responds, and take precedence over the default ones
+            assert!(response.starts_with(expected));
+            assert!(response.contains("\r\nAccess-Control-Allow-Origin: *\r\n"));
+            assert!(response.contains("\r\nServer: cors\r\n"));
+            assert!(!response.contains("edge-http"));
+        }
+
+        // The response headers cannot be added once the response is initiated, or beyond the limit
+        let mut io = Duplex(
+            SliceRead(b"GET / HTTP/1.1\r\n\r\n"),
+            VecWrite(heapless::Vec::new()),
+        );
+
+        embassy_futures::block_on(async {
+            let mut buf = [0; 128];
+            let mut connection = unwrap!(pin!(Connection::<_, 4>::new(&mut buf, &mut io)).await);
+
+            for _ in 0..MAX_ADDED_RESPONSE_HEADERS {
+                unwrap!(connection.add_response_headers(&[("A", "b")]));
+            }
+
+            assert!(matches!(
+                connection.add_response_headers(&[("A", "b")]),
+                Err(Error::TooManyHeaders)
+            ));
+
+            unwrap!(pin!(connection.initiate_response(200, None, &[("A", "c")])).await);
+
+            assert!(matches!(
+                connection.add_response_headers(&[("A", "b")]),
+                Err(Error::InvalidState)
+            ));
+        });
+
+        // ... and the ones provided by the handler take precedence over them
+        let response = unwrap!(core::str::from_utf8(&io.1 .0));
+        assert!(response.contains("\r\nA: c\r\n"));
+        assert!(!response.contains("\r\nA: b\r\n"));
+    }
+
+    #[test]
+    fn test_date_header() {
+        use super::{Connection, DefaultHeaders};
+        use crate::{Clock, HttpDate};
+
+        struct FixedClock(u64);
+
+        impl Clock for FixedClock {
+            fn now(&self) -> Option<u64> {
+                Some(self.0)
+            }
+        }
+
+        for (now, date) in [
+            (HttpDate::MAX, Some("Fri, 31 Dec 9999 23:59:59 GMT")),
+            (HttpDate::MAX + 1, None),
+        ] {
+            let clock = FixedClock(now);
+
+            let mut io = Duplex(
+                SliceRead(b"GET / HTTP/1.1\r\n\r\n"),
+                VecWrite(heapless::Vec::new()),
+            );
+
+            embassy_futures::block_on(async {
+                let mut buf = [0; 128];
+                let mut connection =
+                    unwrap!(pin!(Connection::<_, 4>::new(&mut buf, &mut io)).await);
+
+                unwrap!(
+                    connection.set_default_headers(DefaultHeaders::none().with_clock(Some(&clock)))
+                );
+                unwrap!(pin!(connection.initiate_response(200, None, &[])).await);
+            });
+
+            let response = unwrap!(core::str::from_utf8(&io.1 .0));
+            let mut headers = response.split("\r\n");
+
+            assert_eq!(headers.next(), Some("HTTP/1.1 200 OK"));
+            assert_eq!(
+                headers.find_map(|header| header.strip_prefix("Date: ")),
+                date
+            );
+        }
+    }
+
+    #[test]
+    fn test_reject_connection() {
+        const REQUEST: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc";
+
+        let peer = Peer::default();
+        let mut socket = Socket::new(REQUEST, &peer);
+
+        embassy_futures::block_on(super::reject_connection(&mut socket, 0));
+
+        assert_eq!(
+            peer.written.borrow().as_slice(),
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+
+        // The request is drained after closing the write half, so that the peer is not reset
+        assert!(socket.input.0.is_empty());
+        assert_eq!(
+            peer.closed.borrow().as_slice(),
+            &[Close::Write, Close::Both]
+        );
+    }
+
+    #[test]
+    fn test_socket_queue_reject_after_capacity_frees() {
+        use super::Server;
+
+        let peers = [(); 4].map(|_| Peer::default());
+        let acceptor = Acceptor::new([
+            Socket::new(b"GET /slow HTTP/1.1\r\n\r\n", &peers[0]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[1]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[2]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[3]),
+        ]);
+        let handler = SlowHandler(Signal::new());
+
+        let mut server = Server::<1, 512, 8>::new();
+
+        embassy_futures::block_on(embassy_futures::select::select(
+            pin!(server.run_with_socket_queue_and_reject::<_, _, 1>(None, &acceptor, &handler)),
+            async {
+                // The first connection keeps the only socket busy, so the second one is rejected
+                acceptor.available.set(2);
+
+                while !peers[1].is_done() {
+                    embassy_futures::yield_now().await;
+                }
+
+                assert!(peers[1].written.borrow().starts_with(b"HTTP/1.1 503 "));
+
+                // The overflow acceptor task is accepting again when the first connection completes
+                handler.0.signal(());
+
+                while !peers[0].is_done() || acceptor.accepts.get() < 4 {
+                    embassy_futures::yield_now().await;
+                }
+
+                // ... and gets the third connection, which is served as the server is no longer at capacity
+                acceptor.available.set(3);
+
+                while !peers[2].is_done() {
+                    embassy_futures::yield_now().await;
+                }
+
+                assert!(peers[0].written.borrow().starts_with(b"HTTP/1.1 200 "));
+                assert!(peers[2].written.borrow().starts_with(b"HTTP/1.1 200 "));
+            },
+        ));
+    }
+
+    #[test]
+    fn test_pooled_server() {
+        use core::cell::{RefCell, RefMut};
+
+        use super::PooledServer;
+
+        /// A pool of a single buffer, in use while it is borrowed
+        struct Pool(RefCell<[u8; 512]>);
+
+        impl Pool {
+            fn used(&self) -> usize {
+                self.0.try_borrow_mut().is_err().into()
+            }
+        }
+
+        impl edge_nal::BufferPool for Pool {
+            type Buffer<'a>
+                = RefMut<'a, [u8]>
+            where
+                Self: 'a;
+
+            fn alloc(&self) -> Option<Self::Buffer<'_>> {
+                let buf = self.0.try_borrow_mut().ok()?;
+
+                Some(RefMut::map(buf, |buf| buf.as_mut_slice()))
+            }
+        }
+
+        let peers = [(); 4].map(|_| Peer::default());
+        let acceptor = Acceptor::new([
+            Socket::new(b"GET /slow HTTP/1.1\r\n\r\n", &peers[0]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[1]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[2]),
+            Socket::new(b"GET / HTTP/1.1\r\n\r\n", &peers[3]),
+        ]);
+        let handler = SlowHandler(Signal::new());
+
+        let pool = Pool(RefCell::new([0; 512]));
+        let server = PooledServer::<_, 2, 8>::new(&pool);
+
+        embassy_futures::block_on(embassy_futures::select::select(
+            pin!(server.run(None, &acceptor, &handler)),
+            async {
+                // The first connection holds the only buffer of the pool, so the second one is rejected
+                acceptor.available.set(2);
+
+                while !peers[1].is_done() {
+                    embassy_futures::yield_now().await;
+                }
+
+                assert_eq!(pool.used(), 1);
+                assert!(peers[1].written.borrow().starts_with(b"HTTP/1.1 503 "));
+
+                // Once the first connection is closed, its buffer serves the next one
+                handler.0.signal(());
+                acceptor.available.set(3);
+
+                while !peers[0].is_done() || !peers[2].is_done() {
+                    embassy_futures::yield_now().await;
+                }
+
+                assert_eq!(pool.used(), 0);
+                assert!(peers[0].written.borrow().starts_with(b"HTTP/1.1 200 "));
+                assert!(peers[2].written.borrow().starts_with(b"HTTP/1.1 200 "));
+            },
+        ));
+    }
+}
@@ -0,0 +1,346 @@
+//! Fixtures shared by the tests of the server and the client:
+//! in-memory streams, a fake TCP stack and simple request handlers.
+
+use core::cell::{Cell, RefCell};
+use core::fmt::Display;
+
+use edge_nal::{Close, Readable, TcpAccept, TcpConnect, TcpShutdown, TcpSplit};
+
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::signal::Signal;
+
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+
+use super::server::{Connection, Handler};
+use super::Error;
+
+pub(crate) struct SliceRead<'a>(pub(crate) &'a [u8]);
+
+pub(crate) struct VecWrite(pub(crate) heapless::Vec<u8, 256>);
+
+pub(crate) struct Duplex<'a>(pub(crate) SliceRead<'a>, pub(crate) VecWrite);
+
+impl ErrorType for Duplex<'_> {
+    type Error = core::convert::Infallible;
+}
+
+impl Read for Duplex<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.0.read(buf).await
+    }
+}
+
+impl Write for Duplex<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.1.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl ErrorType for VecWrite {
+    type Error = core::convert::Infallible;
+}
+
+impl Write for VecWrite {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        unwrap!(self.0.extend_from_slice(buf));
+
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ErrorType for SliceRead<'a> {
+    type Error = core::convert::Infallible;
+}
+
+impl<'a> Read for SliceRead<'a> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = core::cmp::min(buf.len(), self.0.len());
+        buf[..len].copy_from_slice(&self.0[..len]);
+
+        self.0 = &self.0[len..];
+
+        Ok(len)
+    }
+}
+
+impl Readable for SliceRead<'_> {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// The peer of a `Socket`: what was written to the socket, and how the socket was closed
+#[derive(Default)]
+pub(crate) struct Peer {
+    pub(crate) written: RefCell<heapless::Vec<u8, 512>>,
+    pub(crate) closed: RefCell<heapless::Vec<Close, 4>>,
+    pub(crate) aborted: Cell<bool>,
+    /// Set to have the peer reset the connection, failing the reads and writes of the socket from then on
+    pub(crate) reset: Cell<bool>,
+    /// Set to have the peer stall once the input of the socket is read, rather than close its write half
+    pub(crate) stall: Cell<bool>,
+}
+
+impl Peer {
+    pub(crate) fn is_done(&self) -> bool {
+        self.aborted.get() || self.closed.borrow().contains(&Close::Both)
+    }
+
+    fn check_reset(&self) -> Result<(), ErrorKind> {
+        if self.reset.get() {
+            Err(ErrorKind::ConnectionReset)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub(crate) struct PeerRead<'s, 'a>(&'s mut SliceRead<'a>, &'a Peer);
+
+impl ErrorType for PeerRead<'_, '_> {
+    type Error = ErrorKind;
+}
+
+impl Read for PeerRead<'_, '_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.1.check_reset()?;
+
+        if self.1.stall.get() && self.0 .0.is_empty() {
+            core::future::pending::<()>().await;
+        }
+
+        let Ok(len) = self.0.read(buf).await;
+
+        Ok(len)
+    }
+}
+
+impl Readable for PeerRead<'_, '_> {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.1.check_reset()
+    }
+}
+
+pub(crate) struct PeerWrite<'a>(&'a Peer);
+
+impl ErrorType for PeerWrite<'_> {
+    type Error = ErrorKind;
+}
+
+impl Write for PeerWrite<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.check_reset()?;
+
+        unwrap!(self.0.written.borrow_mut().extend_from_slice(buf));
+
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.check_reset()
+    }
+}
+
+/// A TCP socket reading the request from a slice, and reporting to its `Peer`
+pub(crate) struct Socket<'a> {
+    pub(crate) input: SliceRead<'a>,
+    pub(crate) peer: &'a Peer,
+}
+
+impl<'a> Socket<'a> {
+    pub(crate) fn new(input: &'a [u8], peer: &'a Peer) -> Self {
+        Self {
+            input: SliceRead(input),
+            peer,
+        }
+    }
+}
+
+impl ErrorType for Socket<'_> {
+    type Error = ErrorKind;
+}
+
+impl Read for Socket<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        PeerRead(&mut self.input, self.peer).read(buf).await
+    }
+}
+
+impl Readable for Socket<'_> {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        PeerRead(&mut self.input, self.peer).readable().await
+    }
+}
+
+impl Write for Socket<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        PeerWrite(self.peer).write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        PeerWrite(self.peer).flush().await
+    }
+}
+
+impl<'a> TcpSplit for Socket<'a> {
+    type Read<'s>
+        = PeerRead<'s, 'a>
+    where
+        Self: 's;
+    type Write<'s>
+        = PeerWrite<'a>
+    where
+        Self: 's;
+
+    fn split(&mut self) -> (Self::Read<'_>, Self::Write<'_>) {
+        (PeerRead(&mut self.input, self.peer), PeerWrite(self.peer))
+    }
+}
+
+impl TcpShutdown for Socket<'_> {
+    async fn close(&mut self, what: Close) -> Result<(), Self::Error> {
+        unwrap!(self.peer.closed.borrow_mut().push(what));
+
+        Ok(())
+    }
+
+    async fn abort(&mut self) -> Result<(), Self::Error> {
+        self.peer.aborted.set(true);
+
+        Ok(())
+    }
+}
+
+/// A handler responding to each request with an empty `200 OK`
+pub(crate) struct OkHandler;
+
+impl Handler for OkHandler {
+    type Error<E>
+        = Error<E>
+    where
+        E: core::fmt::Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit,
+    {
+        connection.initiate_response(200, None, &[]).await
+    }
+}
+
+/// A handler responding to each request with an empty `200 OK`, holding the requests to `/slow`
+/// until signalled
+pub(crate) struct SlowHandler(pub(crate) Signal<NoopRawMutex, ()>);
+
+impl Handler for SlowHandler {
+    type Error<E>
+        = Error<E>
+    where
+        E: core::fmt::Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + Write + TcpSplit,
+    {
+        if connection.headers()?.path == "/slow" {
+            self.0.wait().await;
+        }
+
+        connection.initiate_response(200, None, &[]).await
+    }
+}
+
+/// An acceptor handing out the sockets in order, the n-th `accept` call getting the n-th socket,
+/// once the number of available sockets exceeds n
+pub(crate) struct Acceptor<'a> {
+    pub(crate) sockets: [Cell<Option<Socket<'a>>>; 4],
+    pub(crate) available: Cell<usize>,
+    pub(crate) accepts: Cell<usize>,
+}
+
+impl<'a> Acceptor<'a> {
+    pub(crate) fn new(sockets: [Socket<'a>; 4]) -> Self {
+        Self {
+            sockets: sockets.map(|socket| Cell::new(Some(socket))),
+            available: Cell::new(0),
+            accepts: Cell::new(0),
+        }
+    }
+}
+
+impl<'a> TcpAccept for Acceptor<'a> {
+    type Error = ErrorKind;
+
+    type Socket<'s>
+        = Socket<'a>
+    where
+        Self: 's;
+
+    async fn accept(&self) -> Result<(core::net::SocketAddr, Self::Socket<'_>), Self::Error> {
+        let index = self.accepts.get();
+        self.accepts.set(index + 1);
+
+        loop {
+            if index < self.available.get() {
+                if let Some(socket) = self.sockets.get(index).and_then(Cell::take) {
+                    return Ok((unwrap!("127.0.0.1:1234".parse()), socket));
+                }
+            }
+
+            embassy_futures::yield_now().await;
+        }
+    }
+}
+
+/// A TCP stack whose connections read the response from a slice, and report to a `Peer`,
+/// the n-th `connect` call getting the n-th connection
+pub(crate) struct Connector<'a> {
+    pub(crate) connections: &'a [(&'a [u8], &'a Peer)],
+    pub(crate) remotes: RefCell<heapless::Vec<core::net::SocketAddr, 4>>,
+}
+
+impl<'a> Connector<'a> {
+    pub(crate) fn new(connections: &'a [(&'a [u8], &'a Peer)]) -> Self {
+        Self {
+            connections,
+            remotes: RefCell::new(heapless::Vec::new()),
+        }
+    }
+}
+
+impl<'a> TcpConnect for Connector<'a> {
+    type Error = ErrorKind;
+
+    type Socket<'s>
+        = Socket<'a>
+    where
+        Self: 's;
+
+    async fn connect(
+        &self,
+        remote: core::net::SocketAddr,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        let mut remotes = self.remotes.borrow_mut();
+
+        let (response, peer) = self.connections[remotes.len()];
+        unwrap!(remotes.push(remote));
+
+        Ok(Socket::new(response, peer))
+    }
+}
@@ -5,6 +5,7 @@
 #![allow(unknown_lints)]
 
 use core::fmt::Display;
+use core::mem;
 use core::str;
 
 use httparse::{Header, EMPTY_HEADER};
@@ -282,6 +283,11 @@ impl<'b, const N: usize> Headers<'b, N> {
         self.get("Upgrade")
     }
 
+    /// Utility method to return the value of the `Authorization` header, if present
+    pub fn authorization(&self) -> Option<&str> {
+        self.get("Authorization")
+    }
+
     /// Iterate over all headers which have valid UTF-8 values
     pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
         self.iter_raw()
@@ -297,13 +303,27 @@ impl<'b, const N: usize> Headers<'b, N> {
     }
 
     /// Get the value of a header by name
+    ///
+    /// The name is matched case-insensitively. If the header is present multiple times,
+    /// the value of the first occurrence is returned.
     pub fn get(&self, name: &str) -> Option<&str> {
         self.iter()
             .find(|(hname, _)| name.eq_ignore_ascii_case(hname))
             .map(|(_, value)| value)
     }
 
+    /// Iterate over the values of all occurrences of a header by name (i.e. `Cookie`)
+    ///
+    /// The name is matched case-insensitively.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter()
+            .filter(move |(hname, _)| name.eq_ignore_ascii_case(hname))
+            .map(|(_, value)| value)
+    }
+
     /// Get the raw value of a header by name, returning the value as a raw byte slice
+    ///
+    /// The name is matched case-insensitively.
     pub fn get_raw(&self, name: &str) -> Option<&[u8]> {
         self.iter_raw()
             .find(|(hname, _)| name.eq_ignore_ascii_case(hname))
@@ -523,19 +543,28 @@ impl ConnectionType {
 
     /// Create a connection type from a header
     ///
-    /// If the header is not a `Connection` header, this method returns `None`
+    /// The value of the `Connection` header is a comma-separated list of options (i.e. `keep-alive, Upgrade`),
+    /// where `close` wins over `Upgrade`, which wins over `keep-alive`. Other options (i.e. `TE`) are ignored.
+    ///
+    /// If the header is not a `Connection` header, or has none of the above options, this method returns `None`
     pub fn from_header(name: &str, value: &str) -> Option<Self> {
-        if "Connection".eq_ignore_ascii_case(name) && value.eq_ignore_ascii_case("Close") {
-            Some(Self::Close)
-        } else if "Connection".eq_ignore_ascii_case(name)
-            && value.eq_ignore_ascii_case("Keep-Alive")
-        {
-            Some(Self::KeepAlive)
-        } else if "Connection".eq_ignore_ascii_case(name) && value.eq_ignore_ascii_case("Upgrade") {
-            Some(Self::Upgrade)
-        } else {
-            None
+        if !"Connection".eq_ignore_ascii_case(name) {
+            return None;
+        }
+
+        let mut connection = None;
+
+        for option in value.split(',').map(str::trim) {
+            if option.eq_ignore_ascii_case("Close") {
+                return Some(Self::Close);
+            } else if option.eq_ignore_ascii_case("Upgrade") {
+                connection = Some(Self::Upgrade);
+            } else if option.eq_ignore_ascii_case("Keep-Alive") && connection.is_none() {
+                connection = Some(Self::KeepAlive);
+            }
         }
+
+        connection
     }
 
     /// Create a connection type from headers
@@ -691,9 +720,14 @@ impl BodyType {
     /// Create a body type from a header
     ///
     /// If the header is not a `Content-Length` or `Transfer-Encoding` header, this method returns `None`
+    ///
+    /// A `Transfer-Encoding` header is recognized as chunked if `chunked` is its final transfer coding
+    /// (i.e. `Transfer-Encoding: gzip, chunked`), as per RFC 9112
     pub fn from_header(name: &str, value: &str) -> Option<Self> {
         if "Transfer-Encoding".eq_ignore_ascii_case(name) {
-            if value.eq_ignore_ascii_case("Chunked") {
+            let last_coding = value.rsplit(',').next().unwrap_or("").trim();
+
+            if last_coding.eq_ignore_ascii_case("Chunked") {
                 return Some(Self::Chunked);
             }
         } else if "Content-Length".eq_ignore_ascii_case(name) {
@@ -708,7 +742,8 @@ impl BodyType {
 
     /// Create a body type from headers
     ///
-    /// If multiple body type headers are found, this method logs a warning and returns the last one
+    /// If multiple body type headers are found, this method logs a warning and returns the last one,
+    /// except that a chunked `Transfer-Encoding` always overrides `Content-Length`, as per RFC 9112
     /// If no body type headers are found, this method returns `None`
     pub fn from_headers<'a, H>(headers: H) -> Option<Self>
     where
@@ -727,8 +762,13 @@ impl BodyType {
                     );
                 }
 
-                // The last body header wins
-                body = Some(header_body);
+                // The last body header wins, unless Transfer-Encoding overrides Content-Length
+                if !matches!(
+                    (body, header_body),
+                    (Some(BodyType::Chunked), BodyType::ContentLen(_))
+                ) {
+                    body = Some(header_body);
+                }
             }
         }
 
@@ -778,6 +818,85 @@ impl defmt::Format for BodyType {
     }
 }
 
+/// The byte range requested with a `Range` request header, resolved against the length of the content
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ByteRange {
+    /// No (supported) range was requested, so the whole content should be sent
+    All,
+    /// A single range of the content should be sent; `end` is inclusive, as in `Content-Range`
+    Partial { start: u64, end: u64 },
+    /// The requested range cannot be satisfied
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Resolve the byte range requested by a `Range` header against the content length
+    ///
+    /// Only a single `bytes` range is supported. Requests for multiple ranges, as well as
+    /// syntactically invalid `Range` headers, are ignored (i.e. resolved to `All`), as allowed by RFC 9110.
+    ///
+    /// Parameters:
+    /// - `header`: The value of the `Range` header, if present
+    /// - `len`: The length of the content
+    pub fn resolve(header: Option<&str>, len: u64) -> Self {
+        let Some(spec) = header.map(str::trim).and_then(|header| {
+            header
+                .get(..6)
+                .filter(|unit| unit.eq_ignore_ascii_case("bytes="))
+                .map(|_| header[6..].trim())
+        }) else {
+            return Self::All;
+        };
+
+        if spec.contains(',') {
+            return Self::All;
+        }
+
+        let Some((start, end)) = spec.split_once('-') else {
+            return Self::All;
+        };
+
+        let (start, end) = (start.trim(), end.trim());
+
+        if start.is_empty() {
+            // Suffix range, i.e. the last N bytes
+            match end.parse::<u64>() {
+                Ok(0) => Self::Unsatisfiable,
+                Ok(_) if len == 0 => Self::Unsatisfiable,
+                Ok(suffix) => Self::Partial {
+                    start: len.saturating_sub(suffix),
+                    end: len - 1,
+                },
+                Err(_) => Self::All,
+            }
+        } else {
+            let Ok(start) = start.parse::<u64>() else {
+                return Self::All;
+            };
+
+            let end = if end.is_empty() {
+                u64::MAX
+            } else if let Ok(end) = end.parse::<u64>() {
+                end
+            } else {
+                return Self::All;
+            };
+
+            if end < start {
+                Self::All
+            } else if start >= len {
+                Self::Unsatisfiable
+            } else {
+                Self::Partial {
+                    start,
+                    end: end.min(len - 1),
+                }
+            }
+        }
+    }
+}
+
 /// Request headers including the request line (method, path)
 #[derive(Debug)]
 pub struct RequestHeaders<'b, const N: usize> {
@@ -809,6 +928,303 @@ impl<const N: usize> RequestHeaders<'_, N> {
     }
 }
 
+impl<'b, const N: usize> RequestHeaders<'b, N> {
+    /// Return the path of the request, without the query (if any), unlike the `path` field
+    ///
+    /// Note that the path is returned as-is, i.e. it is not percent-decoded.
+    pub fn path(&self) -> &'b str {
+        self.path
+            .split_once('?')
+            .map(|(path, _)| path)
+            .unwrap_or(self.path)
+    }
+
+    /// Return the raw query of the request (the part of the path after `?`), if any
+    pub fn query(&self) -> Option<&'b str> {
+        self.path.split_once('?').map(|(_, query)| query)
+    }
+
+    /// Return an iterator over the percent-decoded `(name, value)` pairs of the request query
+    ///
+    /// The pairs are decoded into `buf`, which should be at least as long as the raw query.
+    /// Iteration stops early if `buf` is too small. Pairs which are not valid UTF-8 once decoded are skipped.
+    pub fn query_params<'a>(&self, buf: &'a mut [u8]) -> QueryParams<'b, 'a> {
+        QueryParams::new(self.query().unwrap_or(""), buf)
+    }
+}
+
+/// An iterator over the percent-decoded `(name, value)` pairs of a URL query,
+/// where `+` is decoded as a space, as per `application/x-www-form-urlencoded`
+///
+/// The pairs are decoded into a caller-provided buffer, so no allocations are necessary.
+pub struct QueryParams<'q, 'a> {
+    query: &'q str,
+    buf: &'a mut [u8],
+}
+
+impl<'q, 'a> QueryParams<'q, 'a> {
+    /// Create a new iterator over the pairs of `query`, decoding them into `buf`
+    pub fn new(query: &'q str, buf: &'a mut [u8]) -> Self {
+        Self { query, buf }
+    }
+
+    fn decode(&mut self, raw: &str) -> Option<Option<&'a str>> {
+        let buf = mem::take(&mut self.buf);
+        if buf.len() < raw.len() {
+            return None;
+        }
+
+        let bytes = raw.as_bytes();
+
+        let mut len = 0;
+        let mut index = 0;
+
+        while index < bytes.len() {
+            let decoded_escape = (bytes[index] == b'%')
+                .then(|| bytes.get(index + 1..index + 3))
+                .flatten()
+                // `from_str_radix` alone would also accept a sign, i.e. `%+5`
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            buf[len] = match (bytes[index], decoded_escape) {
+                (_, Some(byte)) => {
+                    index += 2;
+                    byte
+                }
+                (b'+', _) => b' ',
+                (byte, _) => byte,
+            };
+
+            len += 1;
+            index += 1;
+        }
+
+        let (decoded, rest) = buf.split_at_mut(len);
+        self.buf = rest;
+
+        Some(str::from_utf8(decoded).ok())
+    }
+}
+
+impl<'a> Iterator for QueryParams<'_, 'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.query.is_empty() {
+                return None;
+            }
+
+            let (pair, rest) = self.query.split_once('&').unwrap_or((self.query, ""));
+            self.query = rest;
+
+            if pair.is_empty() {
+                continue;
+            }
+
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+            let name = self.decode(name)?;
+            let value = self.decode(value)?;
+
+            if let (Some(name), Some(value)) = (name, value) {
+                return Some((name, value));
+            }
+        }
+    }
+}
+
+/// The default maximum number of path parameters of a route pattern in a `Router`
+pub const DEFAULT_MAX_PATH_PARAMS_COUNT: usize = 4;
+
+/// A request router, dispatching requests by method and path to the values registered for them
+/// (i.e. the handlers of the routes; see `io::server::RouteHandler`)
+///
+/// The path patterns are matched segment by segment against the path of the request (without the query):
+/// - A segment starting with `:` (i.e. `:id` in `/api/devices/:id`) matches any non-empty segment,
+///   which is captured as a path parameter with that name (without the `:`)
+/// - Any other segment must match exactly
+///
+/// Trailing slashes are significant, i.e. `/api/` does not match `/api`.
+///
+/// The router holds up to `N` routes, each with up to `P` path parameters.
+pub struct Router<'r, R, const N: usize, const P: usize = DEFAULT_MAX_PATH_PARAMS_COUNT> {
+    routes: heapless::Vec<(Method, &'r str, R), N>,
+}
+
+impl<'r, R, const N: usize, const P: usize> Router<'r, R, N, P> {
+    /// Create a new, empty router
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            routes: heapless::Vec::new(),
+        }
+    }
+
+    /// Register a route
+    ///
+    /// Routes are matched in registration order, so the first route matching a request wins.
+    ///
+    /// Panics if the router already holds `N` routes, or if the pattern has more than `P` path parameters.
+    pub fn add(&mut self, method: Method, pattern: &'r str, value: R) -> &mut Self {
+        if pattern
+            .split('/')
+            .filter(|segment| segment.starts_with(':'))
+            .count()
+            > P
+        {
+            panic!("Too many path parameters");
+        }
+
+        if self.routes.push((method, pattern, value)).is_err() {
+            panic!("No space left");
+        }
+
+        self
+    }
+
+    /// Find the route of a request, returning its value and the path parameters captured by its pattern
+    ///
+    /// The path is matched without its query, if any. The path parameters are not percent-decoded.
+    ///
+    /// Fails with `RouteError::MethodNotAllowed` if routes match the path, but none of them the method,
+    /// and with `RouteError::NotFound` if no route matches the path.
+    pub fn route<'p>(
+        &self,
+        method: Method,
+        path: &'p str,
+    ) -> Result<(&R, PathParams<'r, 'p, P>), RouteError> {
+        let path = path.split_once('?').map(|(path, _)| path).unwrap_or(path);
+
+        let mut result = Err(RouteError::NotFound);
+
+        for (route_method, pattern, value) in &self.routes {
+            let Some(params) = Self::match_path(pattern, path) else {
+                continue;
+            };
+
+            if *route_method == method {
+                return Ok((value, params));
+            }
+
+            result = Err(RouteError::MethodNotAllowed);
+        }
+
+        result
+    }
+
+    /// Return an iterator over the methods of the routes matching the provided path
+    /// (i.e. for the `Allow` header of a `405 Method Not Allowed` response)
+    pub fn allowed_methods<'a>(
+        &'a self,
+        path: &'a str,
+    ) -> impl Iterator<Item = Method> + use<'a, 'r, R, N, P> {
+        let path = path.split_once('?').map(|(path, _)| path).unwrap_or(path);
+
+        self.routes
+            .iter()
+            .filter(move |(_, pattern, _)| Self::match_path(pattern, path).is_some())
+            .map(|(method, _, _)| *method)
+    }
+
+    fn match_path<'p>(pattern: &'r str, path: &'p str) -> Option<PathParams<'r, 'p, P>> {
+        let mut params = PathParams::new();
+
+        let mut pattern_segments = pattern.split('/');
+        let mut path_segments = path.split('/');
+
+        loop {
+            match (pattern_segments.next(), path_segments.next()) {
+                (None, None) => break Some(params),
+                (Some(pattern_segment), Some(path_segment)) => {
+                    if let Some(name) = pattern_segment.strip_prefix(':') {
+                        if path_segment.is_empty() {
+                            break None;
+                        }
+
+                        // Cannot fail, as the number of parameters is checked when the route is added
+                        params.0.push((name, path_segment)).ok()?;
+                    } else if pattern_segment != path_segment {
+                        break None;
+                    }
+                }
+                _ => break None,
+            }
+        }
+    }
+}
+
+impl<R, const N: usize, const P: usize> Default for Router<'_, R, N, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The error returned by `Router::route` when no route matches a request
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RouteError {
+    /// No route matches the path of the request (`404 Not Found`)
+    NotFound,
+    /// Routes match the path of the request, but not its method (`405 Method Not Allowed`)
+    MethodNotAllowed,
+}
+
+impl Display for RouteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "No route matches the path"),
+            Self::MethodNotAllowed => write!(f, "No route matches the method"),
+        }
+    }
+}
+
+impl core::error::Error for RouteError {}
+
+/// The path parameters captured by the pattern of a route (see `Router`)
+#[derive(Debug, Clone)]
+pub struct PathParams<'r, 'p, const P: usize = DEFAULT_MAX_PATH_PARAMS_COUNT>(
+    heapless::Vec<(&'r str, &'p str), P>,
+);
+
+impl<'r, 'p, const P: usize> PathParams<'r, 'p, P> {
+    /// Create a new, empty set of path parameters
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+
+    /// Get the value of a path parameter by name (without the `:` of the pattern)
+    pub fn get(&self, name: &str) -> Option<&'p str> {
+        self.iter()
+            .find(|(pname, _)| *pname == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Iterate over the `(name, value)` pairs of the path parameters, in the order of the pattern
+    pub fn iter(&self) -> impl Iterator<Item = (&'r str, &'p str)> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Return the number of path parameters
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return `true` if there are no path parameters
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<const P: usize> Default for PathParams<'_, '_, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<const N: usize> Default for RequestHeaders<'_, N> {
     #[inline(always)]
     fn default() -> Self {
@@ -851,6 +1267,90 @@ impl<const N: usize> defmt::Format for RequestHeaders<'_, N> {
     }
 }
 
+/// Return the reason phrase registered with IANA for the provided HTTP status code,
+/// or `None` for codes without one.
+pub const fn reason_phrase(status: u16) -> Option<&'static str> {
+    let reason = match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Content Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Content",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => return None,
+    };
+
+    Some(reason)
+}
+
+/// Return `true` if the provided status code and reason phrase make a valid status line,
+/// i.e. the code has three digits and the reason phrase has no control characters other than tab.
+pub fn is_valid_status(status: u16, reason: Option<&str>) -> bool {
+    // RFC 9112:   reason-phrase  = 1*( HTAB / SP / VCHAR / obs-text )
+
+    (100..=999).contains(&status)
+        && reason.is_none_or(|reason| {
+            reason
+                .bytes()
+                .all(|b| b == b'\t' || b == b' ' || (0x21..0x7f).contains(&b) || b >= 0x80)
+        })
+}
+
 /// Response headers including the response line (HTTP version, status code, reason phrase)
 #[derive(Debug)]
 pub struct ResponseHeaders<'b, const N: usize> {
@@ -929,11 +1429,148 @@ impl<const N: usize> defmt::Format for ResponseHeaders<'_, N> {
     }
 }
 
+/// The value of the `Server` header added by default to the responses of the server
+pub const DEFAULT_SERVER_HEADER: &str = "edge-http";
+
+/// The length of a date formatted with `HttpDate` (i.e. `Sun, 06 Nov 1994 08:49:37 GMT`)
+pub const HTTP_DATE_LEN: usize = 29;
+
+/// A source of the current wall-clock time, used for the `Date` header of the server responses
+///
+/// Many embedded devices do not have a real-time clock, or only learn the time after
+/// synchronizing with i.e. an SNTP server, hence the time is optional.
+pub trait Clock {
+    /// Return the current time as seconds since the Unix epoch,
+    /// or `None` if the time is not known (yet)
+    fn now(&self) -> Option<u64>;
+}
+
+impl<T> Clock for &T
+where
+    T: Clock + ?Sized,
+{
+    fn now(&self) -> Option<u64> {
+        (**self).now()
+    }
+}
+
+/// A `Clock` counting the wall-clock time from the `embassy_time` monotonic time, once the
+/// wall-clock time is set with `set` (i.e. after an SNTP synchronization).
+#[cfg(feature = "embassy-time")]
+pub struct EmbassyClock {
+    /// The Unix time of the `embassy_time` epoch in seconds, or 0 if not set yet
+    boot_time: core::sync::atomic::AtomicU32,
+}
+
+#[cfg(feature = "embassy-time")]
+impl EmbassyClock {
+    /// Create a new clock with an unknown time
+    pub const fn new() -> Self {
+        Self {
+            boot_time: core::sync::atomic::AtomicU32::new(0),
+        }
+    }
+
+    /// Set the current wall-clock time as seconds since the Unix epoch
+    pub fn set(&self, now: u64) {
+        let boot_time = now.saturating_sub(embassy_time::Instant::now().as_secs());
+
+        self.boot_time.store(
+            boot_time.clamp(1, u32::MAX as _) as _,
+            core::sync::atomic::Ordering::Relaxed,
+        );
+    }
+}
+
+#[cfg(feature = "embassy-time")]
+impl Default for EmbassyClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "embassy-time")]
+impl Clock for EmbassyClock {
+    fn now(&self) -> Option<u64> {
+        let boot_time = self.boot_time.load(core::sync::atomic::Ordering::Relaxed);
+
+        (boot_time > 0).then(|| boot_time as u64 + embassy_time::Instant::now().as_secs())
+    }
+}
+
+/// A Unix time in seconds, displayed in the IMF-fixdate format of the HTTP `Date` header
+/// (i.e. `Sun, 06 Nov 1994 08:49:37 GMT`)
+///
+/// The format has a four-digit year, so displaying a time past `HttpDate::MAX` (i.e. from the year 10000 on)
+/// fails with `core::fmt::Error`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HttpDate(pub u64);
+
+impl HttpDate {
+    /// The last time which can be displayed, i.e. `Fri, 31 Dec 9999 23:59:59 GMT`
+    pub const MAX: u64 = 253402300799;
+
+    const WEEKDAYS: [&'static str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&'static str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    /// Return the date as `(year, month, day, hour, minute, second, weekday)`,
+    /// where `month` is 1-based and `weekday` is 0-based, starting from Thursday
+    fn civil(&self) -> (u64, usize, u64, u64, u64, u64, usize) {
+        let days = self.0 / 86400;
+        let secs = self.0 % 86400;
+
+        // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z % 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as u64;
+
+        (
+            year,
+            month as usize,
+            day,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+            (days % 7) as usize,
+        )
+    }
+}
+
+impl Display for HttpDate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.0 > Self::MAX {
+            return Err(core::fmt::Error);
+        }
+
+        let (year, month, day, hour, minute, second, weekday) = self.civil();
+
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            Self::WEEKDAYS[weekday],
+            day,
+            Self::MONTHS[month - 1],
+            year,
+            hour,
+            minute,
+            second
+        )
+    }
+}
+
 /// Websocket utilities
 pub mod ws {
     use base64::Engine;
 
-    use crate::Method;
+    use crate::{ConnectionType, Method};
 
     pub const NONCE_LEN: usize = 16;
     pub const MAX_BASE64_KEY_LEN: usize = 28;
@@ -985,7 +1622,10 @@ pub mod ws {
 
         for (name, value) in request_headers {
             if name.eq_ignore_ascii_case("Connection") {
-                connection = value.eq_ignore_ascii_case("Upgrade");
+                connection = matches!(
+                    ConnectionType::from_header(name, value),
+                    Some(ConnectionType::Upgrade)
+                );
             } else if name.eq_ignore_ascii_case("Upgrade") {
                 upgrade = value.eq_ignore_ascii_case("websocket");
             }
@@ -1001,6 +1641,8 @@ pub mod ws {
         NoVersion,
         /// No `Sec-WebSocket-Key` header
         NoSecKey,
+        /// Invalid `Sec-WebSocket-Key` header (not a base64-encoded 16 bytes nonce)
+        InvalidSecKey,
         /// Unsupported `Sec-WebSocket-Version`
         UnsupportedVersion,
     }
@@ -1010,6 +1652,7 @@ pub mod ws {
             match self {
                 Self::NoVersion => write!(f, "No Sec-WebSocket-Version header"),
                 Self::NoSecKey => write!(f, "No Sec-WebSocket-Key header"),
+                Self::InvalidSecKey => write!(f, "Invalid Sec-WebSocket-Key header"),
                 Self::UnsupportedVersion => write!(f, "Unsupported Sec-WebSocket-Version"),
             }
         }
@@ -1021,6 +1664,7 @@ pub mod ws {
             match self {
                 Self::NoVersion => defmt::write!(f, "No Sec-WebSocket-Version header"),
                 Self::NoSecKey => defmt::write!(f, "No Sec-WebSocket-Key header"),
+                Self::InvalidSecKey => defmt::write!(f, "Invalid Sec-WebSocket-Key header"),
                 Self::UnsupportedVersion => defmt::write!(f, "Unsupported Sec-WebSocket-Version"),
             }
         }
@@ -1048,11 +1692,15 @@ pub mod ws {
         for (name, value) in request_headers {
             if name.eq_ignore_ascii_case("Sec-WebSocket-Version") {
                 if !value.eq_ignore_ascii_case(version.unwrap_or("13")) {
-                    return Err(UpgradeError::NoVersion);
+                    return Err(UpgradeError::UnsupportedVersion);
                 }
 
                 version_ok = true;
             } else if name.eq_ignore_ascii_case("Sec-WebSocket-Key") {
+                if !is_valid_sec_key(value) {
+                    return Err(UpgradeError::InvalidSecKey);
+                }
+
                 sec_key_resp_len = Some(sec_key_response(value, buf).len());
             }
         }
@@ -1102,7 +1750,10 @@ pub mod ws {
 
         for (name, value) in response_headers {
             if name.eq_ignore_ascii_case("Connection") {
-                connection = value.eq_ignore_ascii_case("Upgrade");
+                connection = matches!(
+                    ConnectionType::from_header(name, value),
+                    Some(ConnectionType::Upgrade)
+                );
             } else if name.eq_ignore_ascii_case("Upgrade") {
                 upgrade = value.eq_ignore_ascii_case("websocket");
             } else if name.eq_ignore_ascii_case("Sec-WebSocket-Accept") {
@@ -1139,6 +1790,16 @@ pub mod ws {
         sec_key_response_finalize(&mut sha1, buf)
     }
 
+    /// Check if the `Sec-WebSocket-Key` header value is a base64-encoded nonce of `NONCE_LEN` bytes, as per RFC 6455
+    fn is_valid_sec_key(sec_key: &str) -> bool {
+        let mut nonce = [0_u8; NONCE_LEN + 2];
+
+        base64::engine::general_purpose::STANDARD
+            .decode_slice(sec_key.trim(), &mut nonce)
+            .map(|len| len == NONCE_LEN)
+            .unwrap_or(false)
+    }
+
     fn sec_key_response_start(sec_key: &str, sha1: &mut sha1_smol::Sha1) {
         debug!("Computing response for key: {}", sec_key);
 
@@ -1165,10 +1826,27 @@ pub mod ws {
 #[cfg(test)]
 mod test {
     use crate::{
-        ws::{sec_key_response, MAX_BASE64_KEY_RESPONSE_LEN},
-        BodyType, ConnectionType,
+        is_valid_status, reason_phrase,
+        ws::{
+            sec_key_response, upgrade_response_headers, UpgradeError, MAX_BASE64_KEY_RESPONSE_LEN,
+        },
+        BodyType, ByteRange, ConnectionType, Headers, HttpDate, Method, RequestHeaders, RouteError,
+        Router, HTTP_DATE_LEN,
     };
 
+    #[test]
+    fn test_status() {
+        assert_eq!(reason_phrase(207), Some("Multi-Status"));
+        assert_eq!(reason_phrase(429), Some("Too Many Requests"));
+        assert_eq!(reason_phrase(299), None);
+
+        assert!(is_valid_status(299, None));
+        assert!(is_valid_status(429, Some("Slow Down\tPlease")));
+        assert!(!is_valid_status(99, None));
+        assert!(!is_valid_status(1000, Some("Too Long")));
+        assert!(!is_valid_status(200, Some("OK\r\nX-Injected: 1")));
+    }
+
     #[test]
     fn test_resp() {
         let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
@@ -1177,6 +1855,237 @@ mod test {
         assert_eq!(resp, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
     }
 
+    #[test]
+    fn test_upgrade_resp_headers() {
+        let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
+
+        let headers = unwrap!(upgrade_response_headers(
+            [
+                ("Sec-WebSocket-Version", "13"),
+                ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+            ],
+            None,
+            &mut buf,
+        ));
+        assert_eq!(
+            headers[3],
+            ("Sec-WebSocket-Accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+
+        let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
+        assert_eq!(
+            upgrade_response_headers(
+                [
+                    ("Sec-WebSocket-Version", "13"),
+                    ("Sec-WebSocket-Key", "not a key"),
+                ],
+                None,
+                &mut buf,
+            ),
+            Err(UpgradeError::InvalidSecKey)
+        );
+
+        let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
+        assert_eq!(
+            upgrade_response_headers([("Sec-WebSocket-Version", "8")], None, &mut buf),
+            Err(UpgradeError::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    fn test_headers_get() {
+        let mut headers = Headers::<4>::new();
+        headers
+            .set("Content-Type", "text/plain")
+            .set("Cookie", "a=b");
+
+        // `set` replaces existing headers, so add a repeated header directly, as the parser would
+        headers.0[2] = httparse::Header {
+            name: "cookie",
+            value: b"c=d",
+        };
+
+        assert_eq!(headers.get("content-type"), Some("text/plain"));
+        assert_eq!(headers.content_type(), Some("text/plain"));
+        assert_eq!(headers.authorization(), None);
+
+        let mut cookies = headers.get_all("COOKIE");
+        assert_eq!(cookies.next(), Some("a=b"));
+        assert_eq!(cookies.next(), Some("c=d"));
+        assert_eq!(cookies.next(), None);
+
+        assert_eq!(headers.iter().count(), 3);
+    }
+
+    #[test]
+    fn test_query_params() {
+        let request = RequestHeaders::<'_, 1> {
+            path: "/api/v1?a=1&b=hello+world&&c=%2F%3d%e2%82%ac&d&e=%zz%4&f=%+5",
+            ..Default::default()
+        };
+
+        assert_eq!(request.path(), "/api/v1");
+        assert_eq!(
+            request.query(),
+            Some("a=1&b=hello+world&&c=%2F%3d%e2%82%ac&d&e=%zz%4&f=%+5")
+        );
+
+        let mut buf = [0; 64];
+        let mut params = request.query_params(&mut buf);
+
+        assert_eq!(params.next(), Some(("a", "1")));
+        assert_eq!(params.next(), Some(("b", "hello world")));
+        assert_eq!(params.next(), Some(("c", "/=€")));
+        assert_eq!(params.next(), Some(("d", "")));
+        assert_eq!(params.next(), Some(("e", "%zz%4")));
+        assert_eq!(params.next(), Some(("f", "% 5")));
+        assert_eq!(params.next(), None);
+
+        let request = RequestHeaders::<'_, 1> {
+            path: "/",
+            ..Default::default()
+        };
+
+        assert_eq!(request.path(), "/");
+        assert_eq!(request.query(), None);
+        assert_eq!(request.query_params(&mut buf).next(), None);
+    }
+
+    #[test]
+    fn test_router() {
+        let mut router = Router::<'_, u8, 4, 2>::new();
+        router
+            .add(Method::Get, "/", 0)
+            .add(Method::Get, "/devices/:id", 1)
+            .add(Method::Put, "/devices/:id/state/:key", 2)
+            .add(Method::Delete, "/devices/:id", 3);
+
+        let (route, params) = router.route(Method::Get, "/").unwrap();
+        assert_eq!(*route, 0);
+        assert!(params.is_empty());
+
+        let (route, params) = router.route(Method::Get, "/devices/42?verbose=1").unwrap();
+        assert_eq!(*route, 1);
+        assert_eq!(params.get("id"), Some("42"));
+
+        let (route, params) = router.route(Method::Put, "/devices/42/state/led").unwrap();
+        assert_eq!(*route, 2);
+        assert_eq!(
+            params.iter().collect::<heapless::Vec<_, 2>>(),
+            [("id", "42"), ("key", "led")]
+        );
+
+        let (route, _) = router.route(Method::Delete, "/devices/42").unwrap();
+        assert_eq!(*route, 3);
+
+        assert_eq!(
+            router.route(Method::Post, "/devices/42").err(),
+            Some(RouteError::MethodNotAllowed)
+        );
+        assert!(router
+            .allowed_methods("/devices/42")
+            .eq([Method::Get, Method::Delete]));
+
+        assert_eq!(
+            router.route(Method::Get, "/devices/").err(),
+            Some(RouteError::NotFound)
+        );
+        assert_eq!(
+            router.route(Method::Get, "/devices/42/").err(),
+            Some(RouteError::NotFound)
+        );
+        assert_eq!(
+            router.route(Method::Get, "/other").err(),
+            Some(RouteError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_http_date() {
+        use core::fmt::Write as _;
+
+        for (time, date) in [
+            (0, "Thu, 01 Jan 1970 00:00:00 GMT"),
+            (784111777, "Sun, 06 Nov 1994 08:49:37 GMT"),
+            (951782400, "Tue, 29 Feb 2000 00:00:00 GMT"),
+            (1792108799, "Thu, 15 Oct 2026 23:59:59 GMT"),
+            (HttpDate::MAX, "Fri, 31 Dec 9999 23:59:59 GMT"),
+        ] {
+            let mut buf = heapless::String::<HTTP_DATE_LEN>::new();
+            write!(&mut buf, "{}", HttpDate(time)).unwrap();
+
+            assert_eq!(buf, date);
+        }
+
+        // Five-digit years do not fit the format
+        for time in [HttpDate::MAX + 1, u64::MAX] {
+            let mut buf = heapless::String::<64>::new();
+
+            assert!(write!(&mut buf, "{}", HttpDate(time)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(ByteRange::resolve(None, 100), ByteRange::All);
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=0-9"), 100),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("Bytes=90-"), 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=90-200"), 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=-10"), 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=-200"), 100),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=100-"), 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=-0"), 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            ByteRange::resolve(Some("bytes=0-1,5-6"), 100),
+            ByteRange::All
+        );
+        assert_eq!(ByteRange::resolve(Some("bytes=5-1"), 100), ByteRange::All);
+        assert_eq!(ByteRange::resolve(Some("items=0-1"), 100), ByteRange::All);
+    }
+
+    #[test]
+    fn test_conn_header() {
+        assert_eq!(
+            ConnectionType::from_header("connection", "close"),
+            Some(ConnectionType::Close)
+        );
+        assert_eq!(
+            ConnectionType::from_header("Connection", "keep-alive, Upgrade"),
+            Some(ConnectionType::Upgrade)
+        );
+        assert_eq!(
+            ConnectionType::from_header("Connection", "Keep-Alive, TE"),
+            Some(ConnectionType::KeepAlive)
+        );
+        assert_eq!(
+            ConnectionType::from_header("Connection", "TE,close"),
+            Some(ConnectionType::Close)
+        );
+        assert_eq!(ConnectionType::from_header("Connection", "TE"), None);
+        assert_eq!(ConnectionType::from_header("Upgrade", "close"), None);
+    }
+
     #[test]
     fn test_resolve_conn() {
         // Default connection type resolution
@@ -1304,6 +2213,31 @@ mod test {
         .is_err());
     }
 
+    #[test]
+    fn test_body_from_headers() {
+        assert_eq!(
+            BodyType::from_header("transfer-encoding", "chunked"),
+            Some(BodyType::Chunked)
+        );
+        assert_eq!(
+            BodyType::from_header("Transfer-Encoding", "gzip, Chunked"),
+            Some(BodyType::Chunked)
+        );
+        assert_eq!(
+            BodyType::from_header("Transfer-Encoding", "chunked, gzip"),
+            None
+        );
+
+        assert_eq!(
+            BodyType::from_headers([("Transfer-Encoding", "chunked"), ("Content-Length", "10")]),
+            Some(BodyType::Chunked)
+        );
+        assert_eq!(
+            BodyType::from_headers([("Content-Length", "10"), ("Transfer-Encoding", "chunked")]),
+            Some(BodyType::Chunked)
+        );
+    }
+
     #[test]
     fn test_resolve_body() {
         // Request with no body type specified means Content-Length=0
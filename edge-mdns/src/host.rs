@@ -103,6 +103,23 @@ impl Service<'_> {
     {
         host.visit_answers(&mut f)?;
 
+        self.visit_service_answers(host, &[], f)
+    }
+
+    /// Visit the answers of the service, except for the host A/AAAA answers
+    ///
+    /// The `_services._dns-sd._udp.local` enumeration PTRs of the service type and subtypes are skipped
+    /// when already visited for one of the `previous` services, so that each type is enumerated only once.
+    fn visit_service_answers<F, E>(
+        &self,
+        host: &Host,
+        previous: &[Service],
+        mut f: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(HostAnswer) -> Result<(), E>,
+        E: From<MdnsError>,
+    {
         let owner = &[self.name, self.service, self.protocol, "local"];
         let stype = &[self.service, self.protocol, "local"];
         let target = &[host.hostname, "local"];
@@ -126,12 +143,14 @@ impl Service<'_> {
             RecordDataChain::This(Txt::new(self.txt_kvs)),
         ))?;
 
-        f(Record::new(
-            DNS_SD_OWNER,
-            Class::IN,
-            host.ttl,
-            RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(stype)))),
-        ))?;
+        if !previous.iter().any(|other| self.is_same_type(other)) {
+            f(Record::new(
+                DNS_SD_OWNER,
+                Class::IN,
+                host.ttl,
+                RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(stype)))),
+            ))?;
+        }
 
         f(Record::new(
             NameSlice::new(stype),
@@ -140,7 +159,15 @@ impl Service<'_> {
             RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(owner)))),
         ))?;
 
-        for subtype in self.service_subtypes {
+        for &subtype in self.service_subtypes {
+            let enumerated = previous.iter().any(|other| {
+                self.is_same_type(other)
+                    && other
+                        .service_subtypes
+                        .iter()
+                        .any(|other| other.eq_ignore_ascii_case(subtype))
+            });
+
             let subtype_owner = &[subtype, self.name, self.service, self.protocol, "local"];
             let subtype = &[subtype, "_sub", self.service, self.protocol, "local"];
 
@@ -158,16 +185,23 @@ impl Service<'_> {
                 RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(subtype_owner)))),
             ))?;
 
-            f(Record::new(
-                DNS_SD_OWNER,
-                Class::IN,
-                host.ttl,
-                RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(subtype)))),
-            ))?;
+            if !enumerated {
+                f(Record::new(
+                    DNS_SD_OWNER,
+                    Class::IN,
+                    host.ttl,
+                    RecordDataChain::Next(AllRecordData::Ptr(Ptr::new(NameSlice::new(subtype)))),
+                ))?;
+            }
         }
 
         Ok(())
     }
+
+    fn is_same_type(&self, other: &Service) -> bool {
+        self.service.eq_ignore_ascii_case(other.service)
+            && self.protocol.eq_ignore_ascii_case(other.protocol)
+    }
 }
 
 /// A wrapper around a `Service` that also provides the Host of the service
@@ -193,3 +227,111 @@ impl HostAnswers for ServiceAnswers<'_> {
         self.service.visit_answers(self.host, &mut f)
     }
 }
+
+/// A wrapper around a slice of `Service` instances that also provides the Host of the services
+/// and thus allows a single responder to advertise multiple services.
+///
+/// The host A/AAAA answers are generated only once, followed by the SRV, TXT and PTR
+/// answers of each service. The `_services._dns-sd._udp.local` enumeration PTR of a service type
+/// (or subtype) shared by several services is generated only once as well.
+pub struct ServicesAnswers<'a> {
+    host: &'a Host<'a>,
+    services: &'a [Service<'a>],
+}
+
+impl<'a> ServicesAnswers<'a> {
+    /// Create a new `ServicesAnswers` instance.
+    pub const fn new(host: &'a Host<'a>, services: &'a [Service<'a>]) -> Self {
+        Self { host, services }
+    }
+}
+
+impl HostAnswers for ServicesAnswers<'_> {
+    fn visit<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(HostAnswer) -> Result<(), E>,
+        E: From<MdnsError>,
+    {
+        self.host.visit_answers(&mut f)?;
+
+        for (index, service) in self.services.iter().enumerate() {
+            service.visit_service_answers(self.host, &self.services[..index], &mut f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::fmt::Write;
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::domain::base::{ToName, Ttl};
+    use crate::domain::rdata::AllRecordData;
+    use crate::{HostAnswers, MdnsError, NameSlice, RecordDataChain, DNS_SD_OWNER};
+
+    use super::{Host, Service, ServicesAnswers};
+
+    #[test]
+    fn test_services_enumeration() {
+        let host = Host {
+            hostname: "foo",
+            ipv4: Ipv4Addr::new(192, 168, 0, 1),
+            ipv6: Ipv6Addr::UNSPECIFIED,
+            ttl: Ttl::from_secs(60),
+        };
+
+        let service = |name, service, service_subtypes| Service {
+            name,
+            priority: 0,
+            weight: 0,
+            service,
+            protocol: "_tcp",
+            port: 80,
+            service_subtypes,
+            txt_kvs: &[],
+        };
+
+        let services = [
+            service("a", "_http", &["_printer"][..]),
+            service("b", "_http", &["_printer", "_scanner"]),
+            service("c", "_ipp", &["_printer"]),
+        ];
+
+        let mut enumerated = heapless::Vec::<heapless::String<64>, 8>::new();
+        let mut http_instances = 0;
+
+        unwrap!(
+            ServicesAnswers::new(&host, &services).visit(|answer| -> Result<(), MdnsError> {
+                if let RecordDataChain::Next(AllRecordData::Ptr(ptr)) = answer.data() {
+                    if answer.owner().name_eq(&DNS_SD_OWNER) {
+                        let mut name = heapless::String::new();
+                        unwrap!(write!(name, "{}", ptr.ptrdname()));
+                        unwrap!(enumerated.push(name));
+                    } else if answer
+                        .owner()
+                        .name_eq(&NameSlice::new(&["_http", "_tcp", "local"]))
+                    {
+                        http_instances += 1;
+                    }
+                }
+
+                Ok(())
+            })
+        );
+
+        // Each service type and subtype is enumerated once, while each instance is advertised
+        assert_eq!(
+            enumerated,
+            [
+                "_http._tcp.local.",
+                "_printer._sub._http._tcp.local.",
+                "_scanner._sub._http._tcp.local.",
+                "_ipp._tcp.local.",
+                "_printer._sub._ipp._tcp.local.",
+            ]
+        );
+        assert_eq!(http_instances, 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Conversions between the `core::net` address types and the embassy-net ones.
+//!
+//! Note on IPv6 scope ids: an embassy-net `Stack` drives exactly one network interface,
+//! so link-local (i.e. `fe80::/10`) addresses are always reached through that interface,
+//! and the scope id of a `SocketAddrV6` has nothing to select. It is therefore ignored when
+//! converting to the embassy-net types, and is `0` in the addresses converted from them.
+//!
+//! These are free functions rather than `From` / `TryFrom` impls, as both sides of each
+//! conversion are foreign types. The fallible ones return `AddrFamilyDisabled`, which
+//! converts to `TcpError::ProtoDisabled` / `UdpError::ProtoDisabled` with `?`.
+
+use core::fmt::Display;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use embassy_net::{IpAddress, IpEndpoint, IpListenEndpoint};
+
+/// The error returned when converting an address whose family (IPv4 or IPv6) is not
+/// enabled with the `proto-ipv4` / `proto-ipv6` features of this crate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddrFamilyDisabled;
+
+impl Display for AddrFamilyDisabled {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Address family not enabled (see the `proto-ipv4` / `proto-ipv6` features)"
+        )
+    }
+}
+
+impl core::error::Error for AddrFamilyDisabled {}
+
+/// Convert an embassy-net `IpEndpoint` to a standard library `SocketAddr`.
+///
+/// IPv6 addresses get a scope id of `0` (see the module documentation).
+pub fn to_net_socket(socket: IpEndpoint) -> SocketAddr {
+    SocketAddr::new(to_net_addr(socket.addr), socket.port)
+}
+
+/// Convert a standard library `SocketAddr` to an embassy-net `IpEndpoint`.
+///
+/// The scope id of IPv6 addresses is ignored, as the stack has only one interface (see the module documentation).
+///
+/// Fails if the address family is not supported by the enabled `proto-ipv*` features.
+pub fn to_emb_socket(socket: SocketAddr) -> Result<IpEndpoint, AddrFamilyDisabled> {
+    Ok(IpEndpoint {
+        addr: to_emb_addr(socket.ip())?,
+        port: socket.port(),
+    })
+}
+
+/// Convert a standard library `SocketAddr` to an embassy-net `IpListenEndpoint`.
+///
+/// An unspecified address (i.e. `0.0.0.0` or `::`) is converted to a listen endpoint
+/// without an address, which binds to all local addresses.
+///
+/// Fails if the address family is not supported by the enabled `proto-ipv*` features,
+/// including for the unspecified address: i.e. `::` with only `proto-ipv4` enabled is
+/// rejected rather than binding to all the IPv4 addresses.
+pub fn to_emb_bind_socket(socket: SocketAddr) -> Result<IpListenEndpoint, AddrFamilyDisabled> {
+    // Check the family before the unspecified address, so that both are rejected the same way
+    let addr = to_emb_addr(socket.ip())?;
+
+    Ok(IpListenEndpoint {
+        addr: (!socket.ip().is_unspecified()).then_some(addr),
+        port: socket.port(),
+    })
+}
+
+/// Convert an embassy-net `IpListenEndpoint` to a standard library `SocketAddr`.
+///
+/// A listen endpoint without an address is converted to the unspecified address,
+/// i.e. `0.0.0.0` if the `proto-ipv4` feature is enabled, and `::` otherwise.
+pub fn to_net_bind_socket(socket: IpListenEndpoint) -> SocketAddr {
+    let addr = match socket.addr {
+        Some(addr) => to_net_addr(addr),
+        None if cfg!(feature = "proto-ipv4") => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        None => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    SocketAddr::new(addr, socket.port)
+}
+
+/// Convert a standard library `IpAddr` to an embassy-net `IpAddress`.
+///
+/// Fails if the address family is not supported by the enabled `proto-ipv*` features.
+pub fn to_emb_addr(addr: IpAddr) -> Result<IpAddress, AddrFamilyDisabled> {
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddr::V4(addr) => Ok(addr.into()),
+        #[cfg(feature = "proto-ipv6")]
+        IpAddr::V6(addr) => Ok(addr.into()),
+        #[allow(unreachable_patterns)]
+        _ => Err(AddrFamilyDisabled),
+    }
+}
+
+/// Convert an embassy-net `IpAddress` to a standard library `IpAddr`.
+pub fn to_net_addr(addr: IpAddress) -> IpAddr {
+    addr.into()
+}
+
+// A host test binary has no `defmt` logger to link against
+#[cfg(all(test, not(feature = "defmt")))]
+mod test {
+    use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use embassy_net::IpListenEndpoint;
+
+    use super::{to_emb_bind_socket, AddrFamilyDisabled};
+
+    fn bind(addr: impl Into<core::net::IpAddr>) -> Result<IpListenEndpoint, AddrFamilyDisabled> {
+        to_emb_bind_socket(SocketAddr::new(addr.into(), 80))
+    }
+
+    #[test]
+    fn test_bind_socket_v4() {
+        let any = bind(Ipv4Addr::UNSPECIFIED);
+        let specific = bind(Ipv4Addr::new(192, 168, 1, 1));
+
+        if cfg!(feature = "proto-ipv4") {
+            assert_eq!(any.map(|ep| ep.addr), Ok(None));
+            assert!(matches!(
+                specific,
+                Ok(IpListenEndpoint {
+                    addr: Some(_),
+                    port: 80
+                })
+            ));
+        } else {
+            assert_eq!(any, Err(AddrFamilyDisabled));
+            assert_eq!(specific, Err(AddrFamilyDisabled));
+        }
+    }
+
+    #[test]
+    fn test_bind_socket_v6() {
+        let any = bind(Ipv6Addr::UNSPECIFIED);
+        let specific = bind(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+
+        if cfg!(feature = "proto-ipv6") {
+            assert_eq!(any.map(|ep| ep.addr), Ok(None));
+            assert!(matches!(
+                specific,
+                Ok(IpListenEndpoint {
+                    addr: Some(_),
+                    port: 80
+                })
+            ));
+        } else {
+            assert_eq!(any, Err(AddrFamilyDisabled));
+            assert_eq!(specific, Err(AddrFamilyDisabled));
+        }
+    }
+}
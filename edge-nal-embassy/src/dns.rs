@@ -1,13 +1,38 @@
+#[cfg(feature = "udp")]
+use core::cell::{Cell, RefCell};
 use core::fmt::Display;
 use core::net::IpAddr;
+#[cfg(feature = "udp")]
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+#[cfg(feature = "udp")]
+use core::ops::Range;
+#[cfg(feature = "udp")]
+use core::pin::pin;
 
 use edge_nal::AddrType;
 
 use embassy_net::dns::{DnsQueryType, Error};
 use embassy_net::Stack;
 
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::semaphore::{GreedySemaphore, Semaphore, SemaphoreReleaser};
+
+#[cfg(feature = "udp")]
+use edge_nal::{UdpBind, UdpReceive, UdpSend};
+
+#[cfg(feature = "udp")]
+use rand_core::Rng;
+
+#[cfg(feature = "udp")]
+use embassy_time::{with_timeout, Duration, Instant};
+
+#[cfg(feature = "udp")]
+use crate::{DynPool, Udp, UdpSocketBuffers};
+
 use embedded_io_async::ErrorKind;
 
+use crate::StackHandle;
+
 /// A type that implements the `Dns` trait from `edge-nal`.
 /// It uses the DNS resolver from the provided Embassy networking stack.
 ///
@@ -15,6 +40,11 @@ use embedded_io_async::ErrorKind;
 #[derive(Copy, Clone)]
 pub struct Dns<'a> {
     stack: Stack<'a>,
+    #[cfg(feature = "udp")]
+    server: Option<DnsServer<'a>>,
+    #[cfg(feature = "udp")]
+    negative_cache: Option<(&'a [Cell<Option<NegativeEntry>>], Duration)>,
+    query_limit: Option<&'a DnsQueryLimit>,
 }
 
 impl<'a> Dns<'a> {
@@ -22,7 +52,254 @@ impl<'a> Dns<'a> {
     ///
     /// NOTE: If using DHCP, make sure it has reconfigured the stack to ensure the DNS servers are updated
     pub fn new(stack: Stack<'a>) -> Self {
-        Self { stack }
+        Self {
+            stack,
+            #[cfg(feature = "udp")]
+            server: None,
+            #[cfg(feature = "udp")]
+            negative_cache: None,
+            query_limit: None,
+        }
+    }
+
+    /// Create a new `Dns` instance which sends its queries to the provided DNS server,
+    /// rather than to the DNS servers the Embassy networking stack is configured with.
+    ///
+    /// The queries are sent over UDP sockets allocated from the provided UDP buffers pool,
+    /// so one UDP socket from the pool is in use for the duration of each query.
+    ///
+    /// # Arguments
+    /// - `stack`: The Embassy networking stack to use for creating UDP sockets.
+    /// - `buffers`: A pool of UDP socket buffers to use for creating UDP sockets.
+    /// - `server`: The IP address of the DNS server.
+    /// - `rng`: The random number generator drawing the ID of each query. As only a reply carrying the ID
+    ///   of the query is accepted, the IDs must not be predictable (i.e. use a hardware RNG), or else
+    ///   anyone on the path to the server can spoof its replies.
+    #[cfg(feature = "udp")]
+    pub fn with_server(
+        stack: Stack<'a>,
+        buffers: &'a dyn DynPool<UdpSocketBuffers>,
+        server: IpAddr,
+        rng: &'a RefCell<dyn Rng>,
+    ) -> Self {
+        Self {
+            stack,
+            server: Some(DnsServer {
+                addr: SocketAddr::new(server, DNS_PORT),
+                udp: Udp::new(stack, buffers),
+                rng,
+            }),
+            negative_cache: None,
+            query_limit: None,
+        }
+    }
+
+    /// Cache the names which do not resolve in the provided cache, so that repeated lookups
+    /// of these names fail immediately, without querying the DNS server again.
+    ///
+    /// Only the authoritative negative replies of the DNS server set with `Dns::with_server`
+    /// (the name does not exist, or has no address of the requested type) are cached.
+    /// The DNS resolver of the Embassy networking stack does not tell these apart from a failed lookup
+    /// (i.e. a timeout, or the link being down), so none of its results are cached.
+    /// Names longer than `DNS_NEGATIVE_CACHE_MAX_NAME_LEN` are not cached either.
+    ///
+    /// # Arguments
+    /// - `cache`: The cache of the negative results.
+    /// - `ttl`: For how long a negative result is cached. A shorter TTL advertised by the server
+    ///   (the minimum TTL of the SOA record in the reply) takes precedence.
+    #[cfg(feature = "udp")]
+    pub fn with_negative_cache<const N: usize>(
+        mut self,
+        cache: &'a DnsNegativeCache<N>,
+        ttl: Duration,
+    ) -> Self {
+        self.negative_cache = Some((&cache.entries, ttl));
+        self
+    }
+
+    /// Limit the number of queries in flight at any time to the limit of the provided `DnsQueryLimit`,
+    /// the queries in excess waiting for one of the others to complete.
+    ///
+    /// The limit is shared by all the `Dns` instances using the same `DnsQueryLimit`.
+    pub fn with_query_limit(mut self, limit: &'a DnsQueryLimit) -> Self {
+        self.query_limit = Some(limit);
+        self
+    }
+
+    /// Wait for a query slot, if the number of queries in flight is limited
+    async fn acquire_query_slot(&self) -> Option<DnsQuerySlot<'a>> {
+        match self.query_limit {
+            Some(limit) => Some(unwrap!(limit.semaphore.acquire(1).await)),
+            None => None,
+        }
+    }
+
+    /// Return `true` if the name is in the negative cache
+    #[cfg(feature = "udp")]
+    fn is_negative(&self, key: &NegativeKey) -> bool {
+        self.negative_cache
+            .is_some_and(|(entries, _)| negative_lookup(entries, key, Instant::now()))
+    }
+
+    /// Add the name to the negative cache, for the configured TTL or for `ttl_secs`, whichever is shorter
+    #[cfg(feature = "udp")]
+    fn cache_negative(&self, key: NegativeKey, ttl_secs: Option<u32>) {
+        let Some((entries, ttl)) = self.negative_cache else {
+            return;
+        };
+
+        let ttl = ttl_secs.map_or(ttl, |secs| ttl.min(Duration::from_secs(secs as _)));
+
+        negative_insert(entries, key, Instant::now(), ttl);
+    }
+}
+
+/// A limit of the number of DNS queries in flight, for `Dns::with_query_limit`
+///
+/// The DNS resolver of the Embassy networking stack runs up to 4 queries at a time, and it can register
+/// only one of the tasks waiting for a free query slot, so that several waiting tasks keep waking each other
+/// up until a slot frees. A limit of at most 4 makes the tasks in excess wait here instead, without spinning.
+/// With a DNS server set with `Dns::with_server`, the limit also bounds the UDP sockets used by the queries.
+pub struct DnsQueryLimit {
+    semaphore: GreedySemaphore<NoopRawMutex>,
+}
+
+impl DnsQueryLimit {
+    /// Create a new limit of `limit` queries in flight
+    ///
+    /// # Panics
+    /// If `limit` is 0.
+    pub const fn new(limit: usize) -> Self {
+        core::assert!(limit > 0, "The DNS query limit must be at least 1");
+
+        Self {
+            semaphore: GreedySemaphore::new(limit),
+        }
+    }
+}
+
+type DnsQuerySlot<'a> = SemaphoreReleaser<'a, GreedySemaphore<NoopRawMutex>>;
+
+/// The maximum length of a name in a `DnsNegativeCache`, without its trailing dot; longer names are not cached
+#[cfg(feature = "udp")]
+pub const DNS_NEGATIVE_CACHE_MAX_NAME_LEN: usize = 64;
+
+/// A cache of negative DNS results (names which do not resolve), for `Dns::with_negative_cache`
+///
+/// The cache holds up to `N` names; when full, the entry expiring the soonest is evicted.
+#[cfg(feature = "udp")]
+pub struct DnsNegativeCache<const N: usize> {
+    entries: [Cell<Option<NegativeEntry>>; N],
+}
+
+#[cfg(feature = "udp")]
+impl<const N: usize> DnsNegativeCache<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Cell<Option<NegativeEntry>> = Cell::new(None);
+
+    /// Create a new, empty cache
+    pub const fn new() -> Self {
+        Self {
+            entries: [Self::EMPTY; N],
+        }
+    }
+
+    /// Remove all the entries from the cache
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            entry.set(None);
+        }
+    }
+}
+
+#[cfg(feature = "udp")]
+impl<const N: usize> Default for DnsNegativeCache<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "udp")]
+#[derive(Copy, Clone)]
+struct NegativeEntry {
+    key: NegativeKey,
+    expires: Instant,
+}
+
+/// The key of a name and query type in the negative cache
+#[cfg(feature = "udp")]
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct NegativeKey {
+    /// The name, lower-cased and without its trailing dot, padded with zeroes
+    name: [u8; DNS_NEGATIVE_CACHE_MAX_NAME_LEN],
+    name_len: u8,
+    qtype: DnsQueryType,
+}
+
+#[cfg(feature = "udp")]
+impl NegativeKey {
+    /// Create the key of `host`, or return `None` if the name is too long to be cached
+    fn new(host: &str, qtype: DnsQueryType) -> Option<Self> {
+        let host = host.strip_suffix('.').unwrap_or(host);
+
+        let mut name = [0; DNS_NEGATIVE_CACHE_MAX_NAME_LEN];
+        name.get_mut(..host.len())?
+            .iter_mut()
+            .zip(host.bytes())
+            .for_each(|(dst, src)| *dst = src.to_ascii_lowercase());
+
+        Some(Self {
+            name,
+            name_len: host.len() as _,
+            qtype,
+        })
+    }
+}
+
+/// Return `true` if the name has a negative cache entry not yet expired at `now`
+#[cfg(feature = "udp")]
+fn negative_lookup(
+    entries: &[Cell<Option<NegativeEntry>>],
+    key: &NegativeKey,
+    now: Instant,
+) -> bool {
+    entries.iter().any(|entry| {
+        entry
+            .get()
+            .is_some_and(|entry| entry.key == *key && entry.expires > now)
+    })
+}
+
+/// Add a negative cache entry for the name, expiring `ttl` after `now`
+#[cfg(feature = "udp")]
+fn negative_insert(
+    entries: &[Cell<Option<NegativeEntry>>],
+    key: NegativeKey,
+    now: Instant,
+    ttl: Duration,
+) {
+    // Reuse the slot of the same name, or a free or expired slot, or else the one expiring the soonest
+    let slot = entries
+        .iter()
+        .find(|slot| slot.get().is_some_and(|entry| entry.key == key))
+        .or_else(|| {
+            entries.iter().min_by_key(|slot| {
+                slot.get()
+                    .map_or(Instant::MIN, |entry| entry.expires.max(now))
+            })
+        });
+
+    if let Some(slot) = slot {
+        slot.set(Some(NegativeEntry {
+            key,
+            expires: now + ttl,
+        }));
+    }
+}
+
+impl<'a> StackHandle<'a> for Dns<'a> {
+    fn stack(&self) -> Stack<'a> {
+        self.stack
     }
 }
 
@@ -38,6 +315,32 @@ impl edge_nal::Dns for Dns<'_> {
             AddrType::IPv6 => DnsQueryType::Aaaa,
             _ => DnsQueryType::A,
         };
+
+        #[cfg(feature = "udp")]
+        if let Some(server) = self.server.as_ref() {
+            let key = NegativeKey::new(host, qtype);
+
+            if key.as_ref().is_some_and(|key| self.is_negative(key)) {
+                debug!("{} is in the negative cache", host);
+                return Err(Error::Failed.into());
+            }
+
+            let _slot = self.acquire_query_slot().await;
+
+            return match pin!(query(server, host, qtype)).await? {
+                Resolved::Addr(addr) => Ok(addr),
+                Resolved::Negative { ttl_secs } => {
+                    if let Some(key) = key {
+                        self.cache_negative(key, ttl_secs);
+                    }
+
+                    Err(Error::Failed.into())
+                }
+            };
+        }
+
+        let _slot = self.acquire_query_slot().await;
+
         let addrs = self.stack.dns_query(host, qtype).await?;
         if let Some(first) = addrs.first() {
             Ok((*first).into())
@@ -55,6 +358,459 @@ impl edge_nal::Dns for Dns<'_> {
     }
 }
 
+#[cfg(feature = "udp")]
+const DNS_PORT: u16 = 53;
+
+/// The maximum size of a DNS message over UDP, as per RFC 1035
+#[cfg(feature = "udp")]
+const DNS_MAX_LEN: usize = 512;
+
+#[cfg(feature = "udp")]
+const DNS_ATTEMPTS: usize = 3;
+
+#[cfg(feature = "udp")]
+const DNS_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[cfg(feature = "udp")]
+const QTYPE_A: u16 = 1;
+
+#[cfg(feature = "udp")]
+const QTYPE_AAAA: u16 = 28;
+
+#[cfg(feature = "udp")]
+const QTYPE_SRV: u16 = 33;
+
+#[cfg(feature = "udp")]
+const QTYPE_SOA: u16 = 6;
+
+#[cfg(feature = "udp")]
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// The maximum length of the target host name of an SRV record, in its dotted form
+#[cfg(feature = "udp")]
+pub const SRV_TARGET_MAX_LEN: usize = 253;
+
+/// An SRV record, as returned by `Dns::resolve_srv`
+#[cfg(feature = "udp")]
+#[derive(Clone, Debug)]
+pub struct SrvRecord {
+    /// The priority of the target host; lower values are preferred
+    pub priority: u16,
+    /// The relative weight of the target host among the ones with the same priority
+    pub weight: u16,
+    /// The port of the service on the target host
+    pub port: u16,
+    target: [u8; SRV_TARGET_MAX_LEN],
+    target_len: usize,
+}
+
+#[cfg(feature = "udp")]
+impl SrvRecord {
+    /// Create a new, empty SRV record
+    pub const fn new() -> Self {
+        Self {
+            priority: 0,
+            weight: 0,
+            port: 0,
+            target: [0; SRV_TARGET_MAX_LEN],
+            target_len: 0,
+        }
+    }
+
+    /// Return the target host name of the record
+    pub fn target(&self) -> &str {
+        core::str::from_utf8(&self.target[..self.target_len]).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "udp")]
+impl Default for SrvRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "udp")]
+impl Dns<'_> {
+    /// Resolve the SRV records of the provided service name (i.e. `_mqtt._tcp.example.com`)
+    ///
+    /// The records are returned in the order of the reply; selecting a target by priority and weight
+    /// (as per RFC 2782) is up to the caller.
+    ///
+    /// As the DNS resolver of the Embassy networking stack only supports address queries, SRV queries
+    /// are only available with an explicit DNS server (see `Dns::with_server`) and fail otherwise.
+    ///
+    /// # Arguments
+    /// - `name`: The service name to resolve.
+    /// - `out`: Receives the records; extra records in the reply are ignored.
+    ///
+    /// Returns the number of records written to `out`.
+    pub async fn resolve_srv(&self, name: &str, out: &mut [SrvRecord]) -> Result<usize, DnsError> {
+        let Some(server) = self.server.as_ref() else {
+            warn!("SRV queries need a DNS server set with `Dns::with_server`");
+            return Err(Error::Failed.into());
+        };
+
+        let _slot = self.acquire_query_slot().await;
+
+        let mut buf = [0; DNS_MAX_LEN];
+
+        let len = pin!(exchange(server, name, QTYPE_SRV, &mut buf)).await?;
+
+        srv_records(&buf[..len], out).ok_or(DnsError(Error::Failed))
+    }
+}
+
+/// Parse the SRV records of a reply into `out`, returning the number of records written
+#[cfg(feature = "udp")]
+fn srv_records(reply: &[u8], out: &mut [SrvRecord]) -> Option<usize> {
+    let mut count = 0;
+
+    for (rtype, rdata) in answers(reply)? {
+        if count == out.len() {
+            break;
+        }
+
+        if rtype != QTYPE_SRV || rdata.len() < 7 {
+            continue;
+        }
+
+        let record = &mut out[count];
+
+        let Some(target_len) = read_name(reply, rdata.start + 6, &mut record.target) else {
+            continue;
+        };
+
+        if core::str::from_utf8(&record.target[..target_len]).is_err() {
+            continue;
+        }
+
+        record.priority = unwrap!(u16_at(reply, rdata.start));
+        record.weight = unwrap!(u16_at(reply, rdata.start + 2));
+        record.port = unwrap!(u16_at(reply, rdata.start + 4));
+        record.target_len = target_len;
+
+        count += 1;
+    }
+
+    Some(count)
+}
+
+#[cfg(feature = "udp")]
+async fn query(
+    server: &DnsServer<'_>,
+    host: &str,
+    qtype: DnsQueryType,
+) -> Result<Resolved, DnsError> {
+    let (qtype, rdata_len) = match qtype {
+        DnsQueryType::Aaaa => (QTYPE_AAAA, 16),
+        _ => (QTYPE_A, 4),
+    };
+
+    let mut buf = [0; DNS_MAX_LEN];
+
+    let len = pin!(exchange(server, host, qtype, &mut buf)).await?;
+    let reply = &buf[..len];
+
+    let Some(mut answers) = answers(reply) else {
+        return if rcode(reply) == Some(RCODE_NXDOMAIN) {
+            Ok(Resolved::Negative {
+                ttl_secs: soa_ttl(reply),
+            })
+        } else {
+            Err(Error::Failed.into())
+        };
+    };
+
+    let addr = answers
+        .find(|(rtype, rdata)| *rtype == qtype && rdata.len() == rdata_len)
+        .and_then(|(_, rdata)| {
+            let rdata = &reply[rdata];
+
+            Some(if rdata.len() == 4 {
+                IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(rdata).ok()?))
+            } else {
+                IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(rdata).ok()?))
+            })
+        });
+
+    // A successful reply without an address of the requested type means the name has none (NODATA)
+    Ok(addr.map_or(
+        Resolved::Negative {
+            ttl_secs: soa_ttl(reply),
+        },
+        Resolved::Addr,
+    ))
+}
+
+/// The DNS server of a `Dns` instance created with `Dns::with_server`
+#[cfg(feature = "udp")]
+#[derive(Copy, Clone)]
+struct DnsServer<'a> {
+    addr: SocketAddr,
+    udp: Udp<'a>,
+    rng: &'a RefCell<dyn Rng>,
+}
+
+/// The outcome of a query to the DNS server
+#[cfg(feature = "udp")]
+enum Resolved {
+    /// The address of the name
+    Addr(IpAddr),
+    /// The name does not exist or has no address of the requested type; the TTL is the one of
+    /// the SOA record in the reply (if any)
+    Negative { ttl_secs: Option<u32> },
+}
+
+/// Send a query for `host` to the DNS server and receive the reply in `buf`, returning the length of the reply
+#[cfg(feature = "udp")]
+async fn exchange(
+    server: &DnsServer<'_>,
+    host: &str,
+    qtype: u16,
+    buf: &mut [u8; DNS_MAX_LEN],
+) -> Result<usize, DnsError> {
+    let DnsServer {
+        addr: server,
+        udp,
+        rng,
+    } = *server;
+
+    let local = match server {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+
+    let mut socket = pin!(udp.bind(local)).await.map_err(|e| {
+        warn!("Cannot bind a DNS socket: {:?}", e);
+        Error::Failed
+    })?;
+
+    // The query ID is the main protection against spoofed replies, so it has to be unpredictable
+    let id = rng.borrow_mut().next_u32() as u16;
+
+    for _ in 0..DNS_ATTEMPTS {
+        debug!("Querying DNS server {} for {}", server, host);
+
+        // The buffer is shared by the query and the reply, so the query is re-encoded for each attempt
+        let query_len = encode_query(buf, id, host, qtype)?;
+
+        socket.send(server, &buf[..query_len]).await.map_err(|e| {
+            warn!("Cannot send a DNS query: {:?}", e);
+            Error::Failed
+        })?;
+
+        let result = with_timeout(DNS_ATTEMPT_TIMEOUT, async {
+            loop {
+                let (len, remote) = socket.receive(buf).await?;
+
+                if remote == server && len >= 2 && buf[..2] == id.to_be_bytes() {
+                    break Ok::<_, crate::UdpError>(len);
+                }
+            }
+        })
+        .await;
+
+        match result {
+            Ok(Ok(len)) => return Ok(len),
+            Ok(Err(e)) => {
+                warn!("Cannot receive a DNS reply: {:?}", e);
+                return Err(Error::Failed.into());
+            }
+            Err(_) => debug!("No reply from DNS server {}, retrying", server),
+        }
+    }
+
+    Err(Error::Failed.into())
+}
+
+/// Encode a recursive query for `host` in `buf`, returning the length of the query
+#[cfg(feature = "udp")]
+fn encode_query(buf: &mut [u8], id: u16, host: &str, qtype: u16) -> Result<usize, Error> {
+    let host = host.strip_suffix('.').unwrap_or(host);
+
+    // Encoded name: the labels with their length prefixes, and the terminating root label
+    if host.len() + 2 > 255 {
+        return Err(Error::NameTooLong);
+    }
+
+    buf[..12].copy_from_slice(&[
+        (id >> 8) as u8,
+        id as u8,
+        0x01, // Recursion desired
+        0,
+        0, // One question
+        1,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let mut offset = 12;
+
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::InvalidName);
+        }
+
+        buf[offset] = label.len() as u8;
+        buf[offset + 1..offset + 1 + label.len()].copy_from_slice(label.as_bytes());
+
+        offset += 1 + label.len();
+    }
+
+    buf[offset] = 0;
+    buf[offset + 1..offset + 3].copy_from_slice(&qtype.to_be_bytes());
+    buf[offset + 3..offset + 5].copy_from_slice(&1_u16.to_be_bytes()); // Class IN
+
+    Ok(offset + 5)
+}
+
+/// Return an iterator over the answers of a successful reply, yielding the type
+/// and the range of the data of each answer
+#[cfg(feature = "udp")]
+fn answers(reply: &[u8]) -> Option<impl Iterator<Item = (u16, Range<usize>)> + '_> {
+    let flags = u16_at(reply, 2)?;
+
+    // Not a reply, or an error reply
+    if flags & 0x8000 == 0 || flags & 0x000f != 0 {
+        return None;
+    }
+
+    let questions = u16_at(reply, 4)?;
+    let answers = u16_at(reply, 6)?;
+
+    let mut offset = 12;
+
+    for _ in 0..questions {
+        offset = skip_name(reply, offset)? + 4;
+    }
+
+    Some((0..answers).map_while(move |_| {
+        offset = skip_name(reply, offset)?;
+
+        let rtype = u16_at(reply, offset)?;
+        let len = u16_at(reply, offset + 8)? as usize;
+        let rdata = offset + 10..offset + 10 + len;
+
+        reply.get(rdata.clone())?;
+
+        offset = rdata.end;
+
+        Some((rtype, rdata))
+    }))
+}
+
+/// Return the response code of a reply
+#[cfg(feature = "udp")]
+fn rcode(reply: &[u8]) -> Option<u16> {
+    let flags = u16_at(reply, 2)?;
+
+    (flags & 0x8000 != 0).then_some(flags & 0x000f)
+}
+
+/// Return the TTL of a negative reply as per RFC 2308, i.e. the minimum of the TTL
+/// and of the MINIMUM field of the SOA record in the authority section
+#[cfg(feature = "udp")]
+fn soa_ttl(reply: &[u8]) -> Option<u32> {
+    let questions = u16_at(reply, 4)?;
+    let answers = u16_at(reply, 6)?;
+    let authorities = u16_at(reply, 8)?;
+
+    let mut offset = 12;
+
+    for _ in 0..questions {
+        offset = skip_name(reply, offset)? + 4;
+    }
+
+    for index in 0..answers + authorities {
+        offset = skip_name(reply, offset)?;
+
+        let rtype = u16_at(reply, offset)?;
+        let ttl = u32_at(reply, offset + 4)?;
+        let len = u16_at(reply, offset + 8)? as usize;
+
+        offset += 10 + len;
+
+        if index >= answers && rtype == QTYPE_SOA && len >= 4 {
+            return Some(ttl.min(u32_at(reply, offset - 4)?));
+        }
+    }
+
+    None
+}
+
+#[cfg(feature = "udp")]
+fn u32_at(reply: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        reply.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(feature = "udp")]
+fn u16_at(reply: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        reply.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(feature = "udp")]
+fn skip_name(reply: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *reply.get(offset)? as usize;
+
+        if len == 0 {
+            break Some(offset + 1);
+        } else if len & 0xc0 == 0xc0 {
+            // Compression pointer
+            reply.get(offset + 1)?;
+
+            break Some(offset + 2);
+        } else {
+            offset += 1 + len;
+        }
+    }
+}
+
+/// Read the (possibly compressed) name at `offset` in its dotted form into `out`, returning its length
+#[cfg(feature = "udp")]
+fn read_name(reply: &[u8], mut offset: usize, out: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+
+    // Bound the number of compression pointers followed, so that pointer loops cannot hang the parsing
+    let mut pointers = 0;
+
+    loop {
+        let label_len = *reply.get(offset)? as usize;
+
+        if label_len == 0 {
+            break Some(len);
+        } else if label_len & 0xc0 == 0xc0 {
+            pointers += 1;
+            if pointers > 16 {
+                break None;
+            }
+
+            offset = (u16_at(reply, offset)? & 0x3fff) as usize;
+        } else {
+            let label = reply.get(offset + 1..offset + 1 + label_len)?;
+
+            if len > 0 {
+                *out.get_mut(len)? = b'.';
+                len += 1;
+            }
+
+            out.get_mut(len..len + label_len)?.copy_from_slice(label);
+            len += label_len;
+
+            offset += 1 + label_len;
+        }
+    }
+}
+
 /// DNS error type
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -79,3 +835,324 @@ impl embedded_io_async::Error for DnsError {
         ErrorKind::Other
     }
 }
+
+// A host test binary has no `defmt` logger to link against
+#[cfg(all(test, feature = "udp", not(feature = "defmt")))]
+mod test {
+    use super::*;
+
+    /// The question of the replies: `example.com`, type A, class IN
+    const QUESTION: &[u8] = b"\x07example\x03com\x00\x00\x01\x00\x01";
+
+    /// Concatenate the parts of a DNS message
+    fn message(parts: &[&[u8]]) -> ([u8; DNS_MAX_LEN], usize) {
+        let mut buf = [0; DNS_MAX_LEN];
+        let mut len = 0;
+
+        for part in parts {
+            buf[len..len + part.len()].copy_from_slice(part);
+            len += part.len();
+        }
+
+        (buf, len)
+    }
+
+    /// A reply with the provided flags and counts of questions, answers and authorities
+    fn header(flags: u16, questions: u16, answers: u16, authorities: u16) -> [u8; 12] {
+        let mut header = [0; 12];
+
+        header[..2].copy_from_slice(&0x1234_u16.to_be_bytes());
+        header[2..4].copy_from_slice(&flags.to_be_bytes());
+        header[4..6].copy_from_slice(&questions.to_be_bytes());
+        header[6..8].copy_from_slice(&answers.to_be_bytes());
+        header[8..10].copy_from_slice(&authorities.to_be_bytes());
+
+        header
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let mut buf = [0; DNS_MAX_LEN];
+
+        for host in ["example.com", "example.com."] {
+            let len = unwrap!(encode_query(&mut buf, 0x1234, host, QTYPE_A).ok());
+
+            assert_eq!(
+                &buf[..len],
+                b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01"
+            );
+        }
+
+        assert_eq!(
+            encode_query(&mut buf, 0, "example..com", QTYPE_A),
+            Err(Error::InvalidName)
+        );
+        assert_eq!(
+            encode_query(&mut buf, 0, "", QTYPE_A),
+            Err(Error::InvalidName)
+        );
+
+        let label = [b'a'; 64];
+        let label = unwrap!(core::str::from_utf8(&label).ok());
+
+        assert_eq!(
+            encode_query(&mut buf, 0, label, QTYPE_A),
+            Err(Error::InvalidName)
+        );
+        assert!(encode_query(&mut buf, 0, &label[..63], QTYPE_A).is_ok());
+
+        let long = [b'a'; 254];
+        let long = unwrap!(core::str::from_utf8(&long).ok());
+
+        assert_eq!(
+            encode_query(&mut buf, 0, long, QTYPE_A),
+            Err(Error::NameTooLong)
+        );
+    }
+
+    #[test]
+    fn test_answers() {
+        // A CNAME to `www.example.com`, which has the address 10.0.0.1
+        let (reply, len) = message(&[
+            &header(0x8180, 1, 2, 0),
+            QUESTION,
+            b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x06\x03www\xc0\x0c",
+            b"\xc0\x2b\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x0a\x00\x00\x01",
+        ]);
+        let reply = &reply[..len];
+
+        let mut records = unwrap!(answers(reply));
+
+        let (rtype, rdata) = unwrap!(records.next());
+        assert_eq!(rtype, 5);
+
+        let mut name = [0; 32];
+        let name_len = unwrap!(read_name(reply, rdata.start, &mut name));
+        assert_eq!(&name[..name_len], b"www.example.com");
+
+        let (rtype, rdata) = unwrap!(records.next());
+        assert_eq!(rtype, QTYPE_A);
+        assert_eq!(&reply[rdata], &[10, 0, 0, 1]);
+
+        assert!(records.next().is_none());
+
+        assert_eq!(rcode(reply), Some(0));
+        assert_eq!(soa_ttl(reply), None);
+
+        // Every truncation of the reply is handled without panicking, yielding only the complete answers
+        for end in 0..len {
+            let reply = &reply[..end];
+
+            let count = answers(reply).map_or(0, |answers| answers.count());
+            assert!(count < 2 || end == len, "{}", end);
+
+            soa_ttl(reply);
+            rcode(reply);
+            read_name(reply, 12, &mut name);
+        }
+    }
+
+    #[test]
+    fn test_error_replies() {
+        // Not a reply
+        let (query, len) = message(&[&header(0x0100, 1, 0, 0), QUESTION]);
+
+        assert!(answers(&query[..len]).is_none());
+        assert_eq!(rcode(&query[..len]), None);
+
+        // Server failure
+        let (reply, len) = message(&[&header(0x8182, 1, 0, 0), QUESTION]);
+
+        assert!(answers(&reply[..len]).is_none());
+        assert_eq!(rcode(&reply[..len]), Some(2));
+        assert_eq!(soa_ttl(&reply[..len]), None);
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        // NXDOMAIN, with the SOA record of the zone (TTL 3600, MINIMUM 300) in the authority section
+        let (reply, len) = message(&[
+            &header(0x8183, 1, 0, 1),
+            QUESTION,
+            b"\xc0\x14\x00\x06\x00\x01\x00\x00\x0e\x10\x00\x1a\x02ns\xc0\x14\x00",
+            b"\x00\x00\x00\x01\x00\x00\x0e\x10\x00\x00\x07\x08\x00\x09\x3a\x80\x00\x00\x01\x2c",
+        ]);
+        let reply = &reply[..len];
+
+        assert!(answers(reply).is_none());
+        assert_eq!(rcode(reply), Some(RCODE_NXDOMAIN));
+        assert_eq!(soa_ttl(reply), Some(300));
+
+        // NODATA: a successful reply without answers; the SOA TTL is the smaller one here
+        let (reply, len) = message(&[
+            &header(0x8180, 1, 0, 1),
+            QUESTION,
+            b"\xc0\x14\x00\x06\x00\x01\x00\x00\x00\x3c\x00\x1a\x02ns\xc0\x14\x00",
+            b"\x00\x00\x00\x01\x00\x00\x0e\x10\x00\x00\x07\x08\x00\x09\x3a\x80\x00\x00\x01\x2c",
+        ]);
+        let reply = &reply[..len];
+
+        assert_eq!(unwrap!(answers(reply)).count(), 0);
+        assert_eq!(soa_ttl(reply), Some(60));
+
+        // Truncated in the SOA record
+        assert_eq!(soa_ttl(&reply[..len - 2]), None);
+    }
+
+    #[test]
+    fn test_compression_pointers() {
+        let mut name = [0; 32];
+
+        // A pointer to a name which itself ends with a pointer
+        let (reply, len) = message(&[
+            &header(0x8180, 0, 0, 0),
+            b"\x07example\x03com\x00",
+            b"\x03www\xc0\x0c",
+            b"\x03api\xc0\x19",
+        ]);
+        let reply = &reply[..len];
+
+        let name_len = unwrap!(read_name(reply, 0x1f, &mut name));
+        assert_eq!(&name[..name_len], b"api.www.example.com");
+
+        assert_eq!(skip_name(reply, 0x19), Some(0x1f));
+        assert_eq!(skip_name(reply, 0x0c), Some(0x19));
+
+        // The name does not fit
+        assert_eq!(read_name(reply, 0x1f, &mut name[..10]), None);
+
+        // A pointer to itself, and two pointers to each other
+        let (reply, len) = message(&[&header(0x8180, 0, 0, 0), b"\xc0\x0c\x03www\xc0\x0e"]);
+        let reply = &reply[..len];
+
+        assert_eq!(read_name(reply, 0x0c, &mut name), None);
+        assert_eq!(read_name(reply, 0x0e, &mut name), None);
+
+        let (reply, len) = message(&[&header(0x8180, 0, 0, 0), b"\x01a\xc0\x10\x01b\xc0\x0c"]);
+        let reply = &reply[..len];
+
+        assert_eq!(read_name(reply, 0x0c, &mut name), None);
+
+        // A pointer past the end of the message
+        let (reply, len) = message(&[&header(0x8180, 0, 0, 0), b"\xc1\xff"]);
+
+        assert_eq!(read_name(&reply[..len], 0x0c, &mut name), None);
+
+        // A truncated pointer
+        assert_eq!(skip_name(&reply[..len - 1], 0x0c), None);
+    }
+
+    #[test]
+    fn test_srv_records() {
+        // Two records for `_mqtt._tcp.example.com`, the first with a compressed target
+        let (reply, len) = message(&[
+            &header(0x8180, 1, 3, 0),
+            b"\x05_mqtt\x04_tcp\x07example\x03com\x00\x00\x21\x00\x01",
+            b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x0e\x10\x00\x0d\x00\x0a\x00\x05\x07\x5b\x04mqtt\xc0\x17",
+            // Not an SRV record
+            b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x0a\x00\x00\x01",
+            b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x0e\x10\x00\x0e\x00\x14\x00\x00\x22\xb3\x06backup\x00",
+        ]);
+        let reply = &reply[..len];
+
+        let mut out = [SrvRecord::new(), SrvRecord::new(), SrvRecord::new()];
+
+        assert_eq!(srv_records(reply, &mut out), Some(2));
+
+        assert_eq!(
+            (out[0].priority, out[0].weight, out[0].port, out[0].target()),
+            (10, 5, 1883, "mqtt.example.com")
+        );
+        assert_eq!(
+            (out[1].priority, out[1].weight, out[1].port, out[1].target()),
+            (20, 0, 8883, "backup")
+        );
+
+        // Extra records are ignored
+        let mut out = [SrvRecord::new()];
+
+        assert_eq!(srv_records(reply, &mut out), Some(1));
+        assert_eq!(out[0].target(), "mqtt.example.com");
+
+        // A record whose target is a pointer loop is skipped
+        let (reply, len) = message(&[
+            &header(0x8180, 1, 1, 0),
+            b"\x05_mqtt\x04_tcp\x07example\x03com\x00\x00\x21\x00\x01",
+            b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x0e\x10\x00\x08\x00\x0a\x00\x05\x07\x5b\xc0\x3a",
+        ]);
+
+        assert_eq!(srv_records(&reply[..len], &mut out), Some(0));
+
+        // An error reply
+        let (reply, len) = message(&[&header(0x8183, 0, 0, 0)]);
+
+        assert_eq!(srv_records(&reply[..len], &mut out), None);
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        let cache = DnsNegativeCache::<2>::new();
+        let entries = &cache.entries;
+
+        let now = Instant::from_secs(100);
+        let ttl = Duration::from_secs(10);
+
+        let key = |host| unwrap!(NegativeKey::new(host, DnsQueryType::A));
+
+        negative_insert(entries, key("bad.example.com"), now, ttl);
+
+        // The names are compared in full, case-insensitively and without the trailing dot
+        assert!(negative_lookup(entries, &key("bad.example.com"), now));
+        assert!(negative_lookup(entries, &key("BAD.Example.com."), now));
+        assert!(!negative_lookup(entries, &key("bad.example.co"), now));
+        assert!(!negative_lookup(entries, &key("bad.example.com.au"), now));
+        assert!(!negative_lookup(
+            entries,
+            &unwrap!(NegativeKey::new("bad.example.com", DnsQueryType::Aaaa)),
+            now
+        ));
+
+        // The entry expires after the TTL
+        assert!(negative_lookup(
+            entries,
+            &key("bad.example.com"),
+            now + ttl - Duration::from_secs(1)
+        ));
+        assert!(!negative_lookup(
+            entries,
+            &key("bad.example.com"),
+            now + ttl
+        ));
+
+        // When full, the entry expiring the soonest is evicted
+        negative_insert(
+            entries,
+            key("a.example.com"),
+            now + Duration::from_secs(1),
+            ttl,
+        );
+        negative_insert(
+            entries,
+            key("b.example.com"),
+            now + Duration::from_secs(2),
+            ttl,
+        );
+
+        let now = now + Duration::from_secs(2);
+
+        assert!(!negative_lookup(entries, &key("bad.example.com"), now));
+        assert!(negative_lookup(entries, &key("a.example.com"), now));
+        assert!(negative_lookup(entries, &key("b.example.com"), now));
+
+        // Names too long for the cache are not cached
+        let long = [b'a'; DNS_NEGATIVE_CACHE_MAX_NAME_LEN + 1];
+        let long = unwrap!(core::str::from_utf8(&long));
+
+        assert!(NegativeKey::new(long, DnsQueryType::A).is_none());
+        assert!(NegativeKey::new(&long[1..], DnsQueryType::A).is_some());
+
+        cache.clear();
+
+        assert!(!negative_lookup(entries, &key("a.example.com"), now));
+    }
+}
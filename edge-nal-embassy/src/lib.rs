@@ -5,37 +5,144 @@
 #![allow(unknown_lints)]
 
 use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
-use core::net::{IpAddr, SocketAddr};
+use core::net::SocketAddr;
+use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
-use embassy_net::{IpAddress, IpEndpoint, IpListenEndpoint};
-
 #[cfg(feature = "dns")]
 pub use dns::*;
+#[cfg(all(feature = "tcp", feature = "udp"))]
+pub use stack::*;
 #[cfg(feature = "tcp")]
 pub use tcp::*;
 #[cfg(feature = "udp")]
 pub use udp::*;
 
-use crate::sealed::SealedDynPool;
+use embassy_net::Stack;
+#[cfg(feature = "proto-ipv4")]
+use embassy_net::StaticConfigV4;
+#[cfg(feature = "proto-ipv6")]
+use embassy_net::StaticConfigV6;
+
+use crate::sealed::{SealedDynPool, SealedPoolStorage};
 
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
 
+pub mod convert;
 #[cfg(feature = "dns")]
 mod dns;
+#[cfg(all(feature = "tcp", feature = "udp"))]
+mod stack;
 #[cfg(feature = "tcp")]
 mod tcp;
 #[cfg(feature = "udp")]
 mod udp;
 
+/// Add `len` bytes to the provided byte counter of a socket
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn count_bytes(counter: &Cell<u64>, len: usize) {
+    counter.set(counter.get().wrapping_add(len as u64));
+}
+
+/// A trait for the factories which create their sockets on an Embassy networking stack
+/// (`Tcp`, `Udp`, `Dns` and `NalStack`).
+///
+/// Exposes the state of the stack, so that the factory is the only handle the application needs
+/// for i.e. waiting for the network to be up before binding or connecting.
+pub trait StackHandle<'d> {
+    /// Return the Embassy networking stack.
+    fn stack(&self) -> Stack<'d>;
+
+    /// Check whether the link is up.
+    fn is_link_up(&self) -> bool {
+        self.stack().is_link_up()
+    }
+
+    /// Check whether the stack has a valid IP configuration.
+    fn is_config_up(&self) -> bool {
+        self.stack().is_config_up()
+    }
+
+    /// Wait for the network device to obtain a link signal.
+    async fn wait_link_up(&self) {
+        self.stack().wait_link_up().await
+    }
+
+    /// Wait for the stack to obtain a valid IP configuration (i.e. from DHCP).
+    async fn wait_config_up(&self) {
+        self.stack().wait_config_up().await
+    }
+
+    /// Get the current IPv4 configuration, if any.
+    #[cfg(feature = "proto-ipv4")]
+    fn config_v4(&self) -> Option<StaticConfigV4> {
+        self.stack().config_v4()
+    }
+
+    /// Get the current IPv6 configuration, if any.
+    #[cfg(feature = "proto-ipv6")]
+    fn config_v6(&self) -> Option<StaticConfigV6> {
+        self.stack().config_v6()
+    }
+}
+
+/// A trait grouping the socket factories of an Embassy networking stack (`Tcp` and `Udp`),
+/// for code which is generic over the transport.
+///
+/// Binding to a local address is what both transports have in common: binding with `Tcp` returns
+/// an acceptor of incoming connections (`TcpAccept`), while binding with `Udp` returns a socket receiving
+/// the datagrams sent to the local address (`UdpSocket`). The transport-specific operations remain
+/// available through the `edge-nal` traits of the factories (i.e. `TcpConnect`, as `embassy-net` has
+/// no connected UDP sockets).
+///
+/// ```no_run
+/// use core::net::SocketAddr;
+///
+/// use embassy_time::{Duration, Timer};
+///
+/// use edge_nal_embassy::{SocketFactory, StackHandle};
+///
+/// async fn serve<'d, F: SocketFactory<'d>>(factory: F, local: SocketAddr) -> Result<F::Bound, F::Error> {
+///     factory.wait_config_up().await;
+///
+///     loop {
+///         match factory.bind_local(local).await {
+///             // Give the other users of the pool the chance to drop their sockets
+///             Err(e) if factory.is_out_of_buffers(&e) => Timer::after(Duration::from_millis(100)).await,
+///             other => break other,
+///         }
+///     }
+/// }
+/// ```
+pub trait SocketFactory<'d>: StackHandle<'d> + Copy {
+    /// The error type of the factory
+    type Error: embedded_io_async::Error;
+
+    /// The type returned when binding to a local address, reporting the errors of the factory
+    type Bound: embedded_io_async::ErrorType<Error = Self::Error> + 'd;
+
+    /// Bind to the provided local address.
+    ///
+    /// Named differently from `TcpBind::bind` and `UdpBind::bind`, so that it is not ambiguous
+    /// with these when all traits are in scope.
+    async fn bind_local(&self, local: SocketAddr) -> Result<Self::Bound, Self::Error>;
+
+    /// Return `true` if the error is due to the pool of socket buffers being exhausted,
+    /// i.e. the operation might succeed later, once sockets are dropped.
+    fn is_out_of_buffers(&self, error: &Self::Error) -> bool;
+}
+
 /// A const-generics-erased trait variant of `Pool`
 ///
 /// Allows for types like `Tcp`, `TcpSocket`, `Udp` and `UdpSocket` that do reference the
 /// pool to erase the const-generics set on the Pool object type when used for TCP and UDP buffers.
 ///
-/// To erase the type of the pool itself, these types use `&dyn DynPool<B>`
+/// To erase the type of the pool itself, these types use `&dyn DynPool<B>` by default.
+/// When the type erasure is not necessary, they can also be constructed with the concrete
+/// pool type (i.e. `Tcp::new_static`), avoiding the dynamic dispatch.
 pub trait DynPool<B>: SealedDynPool<B> {}
 
 impl<T, B> DynPool<B> for &T where T: DynPool<B> {}
@@ -47,8 +154,11 @@ mod sealed {
     pub trait SealedDynPool<B> {
         /// Allocate an object from the pool.
         ///
-        /// Returns `None` if the pool is exhausted.
-        fn alloc(&self) -> Option<B>;
+        /// Returns the token identifying the allocated slot, which is later passed to `free`,
+        /// together with the object, or `None` if the pool is exhausted.
+        ///
+        /// Use `PoolGuard::alloc` rather than calling this directly, so that the slot is freed on drop.
+        fn alloc(&self) -> Option<(NonNull<u8>, B)>;
 
         /// Free an object back to the pool.
         ///
@@ -57,11 +167,17 @@ mod sealed {
         unsafe fn free(&self, buffer_token: NonNull<u8>);
     }
 
+    /// The sealed trait variant of `PoolStorage`.
+    pub trait SealedPoolStorage<T> {
+        /// Return a pointer to the first of the (possibly uninitialized) objects of the storage.
+        fn slots(&self) -> *mut T;
+    }
+
     impl<T, B> SealedDynPool<B> for &T
     where
         T: SealedDynPool<B>,
     {
-        fn alloc(&self) -> Option<B> {
+        fn alloc(&self) -> Option<(NonNull<u8>, B)> {
             (**self).alloc()
         }
 
@@ -71,28 +187,246 @@ mod sealed {
     }
 }
 
+/// A slot allocated from a `DynPool`, which is freed back to the pool when the guard is dropped.
+///
+/// The socket types hold one of these next to the socket borrowing the slot's buffers,
+/// declared after the socket, so that the socket is dropped before its buffers are freed.
+pub(crate) struct PoolGuard<'p, B, P>
+where
+    P: DynPool<B> + ?Sized,
+{
+    pool: &'p P,
+    token: NonNull<u8>,
+    _buffers: PhantomData<fn() -> B>,
+}
+
+impl<'p, B, P> PoolGuard<'p, B, P>
+where
+    P: DynPool<B> + ?Sized,
+{
+    /// Allocate a slot from the pool, returning its guard and the buffers of the slot.
+    ///
+    /// Returns `None` if the pool is exhausted.
+    pub(crate) fn alloc(pool: &'p P) -> Option<(Self, B)> {
+        let (token, buffers) = pool.alloc()?;
+
+        Some((
+            Self {
+                pool,
+                token,
+                _buffers: PhantomData,
+            },
+            buffers,
+        ))
+    }
+}
+
+impl<B, P> Drop for PoolGuard<'_, B, P>
+where
+    P: DynPool<B> + ?Sized,
+{
+    fn drop(&mut self) {
+        // Safety: the token was returned by `alloc` on this very pool, and the guard
+        // is the only owner of the slot, so it is freed exactly once.
+        unsafe {
+            self.pool.free(self.token);
+        }
+    }
+}
+
 /// A simple fixed-size pool allocator for `T`.
-pub struct Pool<T, const N: usize> {
+///
+/// The pool is not `Sync`, as its bookkeeping uses `Cell`s rather than atomics or a critical section.
+/// Consequently, the `Tcp`, `Udp` and `Dns` types (and their copies) referencing a pool are neither `Send`
+/// nor `Sync`, and the compiler prevents sharing them across threads or executors.
+/// Sharing them among any number of tasks of a single executor is sound though, because allocating
+/// from and freeing to the pool never yields to the executor, so tasks cannot interleave mid-operation.
+/// The same holds for futures of a single task (i.e. joined with `join`): a slot is owned by its socket
+/// (or `PoolBuffer`) from the moment it is allocated, so it cannot be handed out twice, and a future
+/// cancelled while holding a slot frees it exactly once, when the future is dropped.
+///
+/// The objects of the pool live in its storage `S`, which is either the pool itself (`InlineStorage`,
+/// with `Pool::new`), or memory borrowed by the pool (`ExternalStorage`, with `Pool::new_external`).
+/// The latter is useful when only a specific memory region can back the objects (i.e. DMA-capable SRAM
+/// or external PSRAM for the network buffers), as the memory can then be placed with a linker section.
+pub struct Pool<T, const N: usize, S = InlineStorage<T, N>> {
     used: [Cell<bool>; N],
-    data: [UnsafeCell<MaybeUninit<T>>; N],
+    data: S,
+    in_use: Cell<usize>,
+    high_water: Cell<usize>,
+    _objects: PhantomData<T>,
+}
+
+/// A trait for the storage of the objects of a `Pool` (see `InlineStorage` and `ExternalStorage`).
+pub trait PoolStorage<T>: SealedPoolStorage<T> {}
+
+/// The storage of a `Pool` owning its objects, i.e. the objects live wherever the pool itself lives.
+pub struct InlineStorage<T, const N: usize>([UnsafeCell<MaybeUninit<T>>; N]);
+
+impl<T, const N: usize> SealedPoolStorage<T> for InlineStorage<T, N> {
+    fn slots(&self) -> *mut T {
+        self.0.as_ptr() as *mut T
+    }
 }
 
+impl<T, const N: usize> PoolStorage<T> for InlineStorage<T, N> {}
+
+/// The storage of a `Pool` borrowing the memory of its objects.
+pub struct ExternalStorage<'a, T, const N: usize>(
+    NonNull<T>,
+    PhantomData<&'a mut [MaybeUninit<T>; N]>,
+);
+
+impl<T, const N: usize> SealedPoolStorage<T> for ExternalStorage<'_, T, N> {
+    fn slots(&self) -> *mut T {
+        self.0.as_ptr()
+    }
+}
+
+impl<T, const N: usize> PoolStorage<T> for ExternalStorage<'_, T, N> {}
+
+// Safety: the storage is the exclusive owner of the borrowed memory, just like `InlineStorage` of its array
+unsafe impl<T, const N: usize> Send for ExternalStorage<'_, T, N> where T: Send {}
+
 impl<T, const N: usize> Pool<T, N> {
-    #[allow(clippy::declare_interior_mutable_const)]
-    const VALUE: Cell<bool> = Cell::new(false);
     #[allow(clippy::declare_interior_mutable_const)]
     const UNINIT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
 
     /// Create a new pool.
     pub const fn new() -> Self {
+        Self::new_in(InlineStorage([Self::UNINIT; N]))
+    }
+}
+
+impl<'a, T, const N: usize> Pool<T, N, ExternalStorage<'a, T, N>> {
+    /// Create a new pool, whose objects live in the provided memory rather than in the pool itself.
+    ///
+    /// Only the bookkeeping of the pool (a few words plus a flag per object) lives in the pool,
+    /// so the memory of the objects can be placed in a specific memory region, i.e.:
+    #[cfg_attr(
+        feature = "tcp",
+        doc = r#"
+```no_run
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+use edge_nal::TcpConnect;
+use edge_nal_embassy::{Pool, Tcp, TcpSlot};
+
+#[link_section = ".psram"]
+static mut TCP_BUFFERS: [MaybeUninit<TcpSlot<4096, 4096>>; 4] = [const { MaybeUninit::uninit() }; 4];
+
+async fn run(stack: embassy_net::Stack<'static>) {
+    // Safety: the memory is only ever borrowed here
+    let pool = Pool::new_external(unsafe { &mut *addr_of_mut!(TCP_BUFFERS) });
+    let tcp = Tcp::new(stack, &pool);
+
+    let socket = tcp.connect("192.168.1.1:80".parse().unwrap()).await.unwrap();
+    // The socket buffers live in `TCP_BUFFERS`
+}
+```
+"#
+    )]
+    ///
+    /// The pool implements `DynPool` for the TCP and UDP socket buffers just like `TcpBuffers` and `UdpBuffers`,
+    /// so it can be used with `Tcp::new` and `Udp::new`.
+    pub const fn new_external(storage: &'a mut [MaybeUninit<T>; N]) -> Self {
+        Self::new_in(ExternalStorage(
+            unsafe { NonNull::new_unchecked(storage.as_mut_ptr() as *mut T) },
+            PhantomData,
+        ))
+    }
+}
+
+impl<T, const N: usize, S> Pool<T, N, S>
+where
+    S: PoolStorage<T>,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const VALUE: Cell<bool> = Cell::new(false);
+
+    const fn new_in(data: S) -> Self {
         Self {
             used: [Self::VALUE; N],
-            data: [Self::UNINIT; N],
+            data,
+            in_use: Cell::new(0),
+            high_water: Cell::new(0),
+            _objects: PhantomData,
+        }
+    }
+
+    /// Return the number of objects currently allocated from the pool.
+    pub fn used(&self) -> usize {
+        self.in_use.get()
+    }
+
+    /// Return the maximum number of objects simultaneously allocated from the pool since its creation.
+    ///
+    /// Useful for right-sizing `N`: run the workload, then set `N` just above the high-water mark.
+    pub fn high_water(&self) -> usize {
+        self.high_water.get()
+    }
+
+    /// Return the indices of the slots currently allocated from the pool.
+    ///
+    /// Useful for hunting leaks: the index of each slot is also logged (at trace level) when it is
+    /// allocated and freed, so the allocations still live can be traced back to their owners.
+    pub fn live_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.used
+            .iter()
+            .enumerate()
+            .filter(|(_, used)| used.get())
+            .map(|(index, _)| index)
+    }
+
+    /// Touch the memory of all the free slots of the pool by zeroing them.
+    ///
+    /// On targets where the memory of the pool is faulted in or zeroed lazily on first access,
+    /// this moves the cost of the first access to the call, so that the first allocation of each slot
+    /// (i.e. the socket answering the first packet) is as fast as the subsequent ones.
+    ///
+    /// The slots in use are left untouched, so the pool can be pre-warmed at any time,
+    /// although the most useful time is during the startup of the application.
+    pub fn prewarm(&self) {
+        for (n, used) in self.used.iter().enumerate() {
+            if !used.get() {
+                // Safety: the slot is not allocated, so nothing references its memory
+                unsafe {
+                    core::ptr::write_bytes(self.data.slots().add(n), 0, 1);
+                }
+            }
         }
     }
 }
 
-impl<T, const N: usize> Pool<T, N> {
+/// In debug builds, report the slots still in use when the pool is dropped,
+/// as these are objects which were leaked (i.e. with `mem::forget`) rather than returned to the pool.
+impl<T, const N: usize, S> Drop for Pool<T, N, S> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            let leaked = self.in_use.get();
+
+            if leaked > 0 {
+                warn!(
+                    "Pool dropped with {} of its {} objects still allocated (leaked?)",
+                    leaked, N
+                );
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, S> Pool<T, N, S>
+where
+    S: PoolStorage<T>,
+{
     /// Allocate an object from the pool.
     ///
     /// # Returns
@@ -103,7 +437,14 @@ impl<T, const N: usize> Pool<T, N> {
             // this can't race because Pool is not Sync.
             if !self.used[n].get() {
                 self.used[n].set(true);
-                let p = self.data[n].get() as *mut T;
+
+                let in_use = self.in_use.get() + 1;
+                self.in_use.set(in_use);
+                self.high_water.set(self.high_water.get().max(in_use));
+
+                trace!("Pool slot {} allocated", n);
+
+                let p = unsafe { self.data.slots().add(n) };
                 return Some(unsafe { NonNull::new_unchecked(p) });
             }
         }
@@ -117,49 +458,330 @@ impl<T, const N: usize> Pool<T, N> {
     /// # Arguments
     /// - `p`: A pointer to the object to free.
     unsafe fn free(&self, p: NonNull<T>) {
-        let origin = self.data.as_ptr() as *mut T;
+        let origin = self.data.slots();
         let n = p.as_ptr().offset_from(origin);
         assert!(n >= 0);
         assert!((n as usize) < N);
         self.used[n as usize].set(false);
+        self.in_use.set(self.in_use.get() - 1);
+
+        trace!("Pool slot {} freed", n);
     }
 }
 
-/// Convert an embassy-net `IpEndpoint` to a standard library `SocketAddr`.
-pub(crate) fn to_net_socket(socket: IpEndpoint) -> SocketAddr {
-    SocketAddr::new(socket.addr.into(), socket.port)
+/// Pools of byte arrays can be used as `edge_nal::BufferPool`, so that other crates
+/// (i.e. the `edge-http` server) can draw their scratch buffers from them.
+impl<const B: usize, const N: usize, S> edge_nal::BufferPool for Pool<[u8; B], N, S>
+where
+    S: PoolStorage<[u8; B]>,
+{
+    type Buffer<'a>
+        = PoolBuffer<'a, B, N, S>
+    where
+        Self: 'a;
+
+    fn alloc(&self) -> Option<Self::Buffer<'_>> {
+        let buf = Pool::alloc(self)?;
+
+        // The slots of the pool are uninitialized memory, so zero the buffer before handing it out
+        unsafe {
+            core::ptr::write_bytes(buf.as_ptr() as *mut u8, 0, B);
+        }
+
+        Some(PoolBuffer { pool: self, buf })
+    }
 }
 
-/// Convert an embassy-net `IpListenEndpoint` to a standard library `SocketAddr`.
-pub(crate) fn to_emb_socket(socket: SocketAddr) -> Option<IpEndpoint> {
-    Some(IpEndpoint {
-        addr: to_emb_addr(socket.ip())?,
-        port: socket.port(),
-    })
+/// A byte buffer allocated from a `Pool`, which is returned to the pool when dropped.
+pub struct PoolBuffer<'a, const B: usize, const N: usize, S = InlineStorage<[u8; B], N>>
+where
+    S: PoolStorage<[u8; B]>,
+{
+    pool: &'a Pool<[u8; B], N, S>,
+    buf: NonNull<[u8; B]>,
 }
 
-/// Convert a standard library `SocketAddr` to an embassy-net `IpListenEndpoint`.
-pub(crate) fn to_emb_bind_socket(socket: SocketAddr) -> Option<IpListenEndpoint> {
-    let addr = if socket.ip().is_unspecified() {
-        None
-    } else {
-        Some(to_emb_addr(socket.ip())?)
-    };
-
-    Some(IpListenEndpoint {
-        addr,
-        port: socket.port(),
-    })
-}
-
-/// Convert an embassy-net `IpAddress` to a standard library `IpAddr`.
-pub(crate) fn to_emb_addr(addr: IpAddr) -> Option<IpAddress> {
-    match addr {
-        #[cfg(feature = "proto-ipv4")]
-        IpAddr::V4(addr) => Some(addr.into()),
-        #[cfg(feature = "proto-ipv6")]
-        IpAddr::V6(addr) => Some(addr.into()),
-        #[allow(unreachable_patterns)]
-        _ => None,
+impl<const B: usize, const N: usize, S> Deref for PoolBuffer<'_, B, N, S>
+where
+    S: PoolStorage<[u8; B]>,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the buffer is owned exclusively until dropped, and was initialized when allocated
+        unsafe { self.buf.as_ref() }
+    }
+}
+
+impl<const B: usize, const N: usize, S> DerefMut for PoolBuffer<'_, B, N, S>
+where
+    S: PoolStorage<[u8; B]>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.buf.as_mut() }
+    }
+}
+
+impl<const B: usize, const N: usize, S> Drop for PoolBuffer<'_, B, N, S>
+where
+    S: PoolStorage<[u8; B]>,
+{
+    fn drop(&mut self) {
+        unsafe {
+            self.pool.free(self.buf);
+        }
+    }
+}
+
+// A host test binary has no `defmt` logger to link against
+#[cfg(all(test, not(feature = "defmt")))]
+mod test {
+    use core::mem::MaybeUninit;
+    use core::pin::pin;
+
+    use embassy_futures::join::join;
+    use embassy_futures::select::{select, Either};
+    use embassy_futures::{block_on, yield_now};
+
+    use edge_nal::BufferPool;
+
+    use super::Pool;
+    #[cfg(feature = "tcp")]
+    use super::PoolGuard;
+
+    #[test]
+    fn test_pool_joined_allocs() {
+        let pool = Pool::<[u8; 16], 2>::new();
+
+        // Allocations interleaved at await points never hand out the same slot twice
+        let task = || async {
+            let mut buf = BufferPool::alloc(&pool).unwrap();
+            let addr = buf.as_ptr() as usize;
+
+            yield_now().await;
+            buf.fill(addr as u8);
+            yield_now().await;
+
+            assert!(buf.iter().all(|b| *b == addr as u8));
+
+            addr
+        };
+
+        let (first, second) = block_on(join(task(), task()));
+
+        assert_ne!(first, second);
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.high_water(), 2);
+    }
+
+    #[test]
+    fn test_pool_exhausted_while_held() {
+        let pool = Pool::<[u8; 16], 1>::new();
+
+        let holder = async {
+            let _buf = BufferPool::alloc(&pool).unwrap();
+
+            yield_now().await;
+            yield_now().await;
+        };
+
+        let contender = async {
+            yield_now().await;
+            assert!(BufferPool::alloc(&pool).is_none());
+
+            yield_now().await;
+            yield_now().await;
+            assert!(BufferPool::alloc(&pool).is_some());
+        };
+
+        block_on(join(holder, contender));
+
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.high_water(), 1);
+    }
+
+    #[test]
+    fn test_pool_cancelled_alloc() {
+        let pool = Pool::<[u8; 16], 1>::new();
+
+        // A future cancelled while holding a slot frees it exactly once, when dropped
+        let holder = async {
+            let _buf = BufferPool::alloc(&pool).unwrap();
+
+            core::future::pending::<()>().await;
+        };
+
+        let canceller = async {
+            yield_now().await;
+            assert_eq!(pool.used(), 1);
+        };
+
+        {
+            let holder = pin!(holder);
+
+            assert!(matches!(
+                block_on(select(holder, canceller)),
+                Either::Second(())
+            ));
+        }
+
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.live_indices().count(), 0);
+
+        let buf = BufferPool::alloc(&pool);
+        assert!(buf.is_some());
+        assert_eq!(pool.used(), 1);
+    }
+
+    #[test]
+    fn test_pool_high_water() {
+        let pool = Pool::<[u8; 16], 3>::new();
+
+        assert_eq!((pool.used(), pool.high_water()), (0, 0));
+
+        // The high-water mark rises with the allocations...
+        let first = BufferPool::alloc(&pool).unwrap();
+        let second = BufferPool::alloc(&pool).unwrap();
+        assert_eq!((pool.used(), pool.high_water()), (2, 2));
+
+        // ... but does not fall when they are freed
+        drop(first);
+        assert_eq!((pool.used(), pool.high_water()), (1, 2));
+
+        // ... nor rise when a freed slot is reused
+        let third = BufferPool::alloc(&pool).unwrap();
+        assert_eq!((pool.used(), pool.high_water()), (2, 2));
+
+        let fourth = BufferPool::alloc(&pool).unwrap();
+        assert_eq!((pool.used(), pool.high_water()), (3, 3));
+
+        drop(second);
+        drop(third);
+        drop(fourth);
+        assert_eq!((pool.used(), pool.high_water()), (0, 3));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_socket_buffers_joined_and_cancelled() {
+        use crate::{DynPool, TcpBuffers, TcpSocketBuffers};
+
+        let buffers = TcpBuffers::<2, 64, 64>::new();
+        let pool: &dyn DynPool<TcpSocketBuffers> = &buffers;
+
+        // The path of the sockets: the slot is owned by a `PoolGuard` for as long as the socket lives
+        let task = || async {
+            let (guard, _) = PoolGuard::alloc(pool).unwrap();
+            let addr = guard.token.as_ptr() as usize;
+
+            yield_now().await;
+            yield_now().await;
+
+            addr
+        };
+
+        let (first, second) = block_on(join(task(), task()));
+
+        assert_ne!(first, second);
+        assert_eq!(buffers.used(), 0);
+        assert_eq!(buffers.high_water(), 2);
+
+        let holder = async {
+            let _slot = PoolGuard::alloc(pool).unwrap();
+
+            core::future::pending::<()>().await;
+        };
+
+        let canceller = async {
+            yield_now().await;
+            assert_eq!(buffers.used(), 1);
+        };
+
+        {
+            let holder = pin!(holder);
+
+            assert!(matches!(
+                block_on(select(holder, canceller)),
+                Either::Second(())
+            ));
+        }
+
+        assert_eq!(buffers.used(), 0);
+        assert_eq!(buffers.live_indices().count(), 0);
+    }
+
+    #[test]
+    fn test_external_pool() {
+        let mut storage = [MaybeUninit::new([0xaa_u8; 16]); 2];
+        let range = storage.as_ptr_range();
+
+        {
+            let pool = Pool::new_external(&mut storage);
+
+            let mut first = BufferPool::alloc(&pool).unwrap();
+            let second = BufferPool::alloc(&pool).unwrap();
+
+            // The objects live in the borrowed memory rather than in the pool
+            assert!(range.contains(&(first.as_ptr() as *const _)));
+            assert!(range.contains(&(second.as_ptr() as *const _)));
+            assert_ne!(first.as_ptr(), second.as_ptr());
+
+            assert!(BufferPool::alloc(&pool).is_none());
+            assert_eq!(pool.used(), 2);
+
+            drop(second);
+            assert_eq!(pool.used(), 1);
+            assert!(pool.live_indices().eq([0]));
+
+            // Pre-warming leaves the slot in use untouched
+            first.fill(0x55);
+            pool.prewarm();
+            assert!(first.iter().all(|b| *b == 0x55));
+
+            drop(first);
+            assert_eq!(pool.used(), 0);
+            assert_eq!(pool.high_water(), 2);
+        }
+
+        // ... and zeroes the free ones in the borrowed memory
+        let storage = storage.map(|slot| unsafe { slot.assume_init() });
+        assert_eq!(storage, [[0x55; 16], [0; 16]]);
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_external_socket_buffers() {
+        use crate::{DynPool, TcpSlot, TcpSocketBuffers};
+
+        let mut storage = [const { MaybeUninit::<TcpSlot<64, 64>>::uninit() }; 2];
+        let range = storage.as_ptr_range();
+
+        let buffers = Pool::new_external(&mut storage);
+        buffers.prewarm();
+
+        let pool: &dyn DynPool<TcpSocketBuffers> = &buffers;
+
+        let (first, _) = PoolGuard::alloc(pool).unwrap();
+        let (second, _) = PoolGuard::alloc(pool).unwrap();
+
+        assert!(range.contains(&(first.token.as_ptr() as *const _)));
+        assert!(range.contains(&(second.token.as_ptr() as *const _)));
+        assert_ne!(first.token, second.token);
+
+        assert!(PoolGuard::alloc(pool).is_none());
+        assert_eq!(buffers.used(), 2);
+
+        drop(first);
+        assert!(buffers.live_indices().eq([1]));
+
+        // The freed slot is handed out again
+        let (third, _) = PoolGuard::alloc(pool).unwrap();
+        assert!(range.contains(&(third.token.as_ptr() as *const _)));
+
+        drop(second);
+        drop(third);
+
+        assert_eq!(buffers.used(), 0);
+        assert_eq!(buffers.high_water(), 2);
     }
 }
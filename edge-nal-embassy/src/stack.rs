@@ -0,0 +1,225 @@
+#[cfg(feature = "dns")]
+use core::cell::RefCell;
+#[cfg(feature = "dns")]
+use core::fmt::Display;
+#[cfg(feature = "dns")]
+use core::net::{IpAddr, SocketAddr};
+#[cfg(feature = "dns")]
+use core::pin::pin;
+
+use embassy_net::Stack;
+
+#[cfg(feature = "dns")]
+use edge_nal::{AddrType, Dns as _};
+#[cfg(feature = "dns")]
+use embassy_time::Duration;
+#[cfg(feature = "dns")]
+use embedded_io_async::ErrorKind;
+
+#[cfg(feature = "dns")]
+use rand_core::Rng;
+
+#[cfg(feature = "dns")]
+use crate::{Dns, DnsError, TcpError, TcpSocket};
+use crate::{DynPool, StackHandle, Tcp, TcpSocketBuffers, Udp, UdpSocketBuffers};
+
+/// An aggregate of the `edge-nal` factories over one Embassy networking stack.
+///
+/// Builds the `Tcp`, `Udp` and `Dns` factories from a single stack and the pools of the
+/// TCP and UDP socket buffers, so that helpers needing all of them can take a single argument.
+///
+/// The type is `Copy` and `Clone`, so it can be easily passed around.
+#[derive(Copy, Clone)]
+pub struct NalStack<'d> {
+    stack: Stack<'d>,
+    tcp_buffers: &'d dyn DynPool<TcpSocketBuffers>,
+    udp_buffers: &'d dyn DynPool<UdpSocketBuffers>,
+}
+
+impl<'d> NalStack<'d> {
+    /// Create a new `NalStack` instance
+    ///
+    /// # Arguments
+    /// - `stack`: The Embassy networking stack.
+    /// - `tcp_buffers`: A pool of TCP socket buffers to use for creating TCP sockets.
+    /// - `udp_buffers`: A pool of UDP socket buffers to use for creating UDP sockets.
+    ///
+    /// As with `Tcp::new` and `Udp::new`, the pools can live on the stack of the task using them:
+    /// ```no_run
+    /// use edge_nal::{TcpConnect, UdpBind};
+    /// use edge_nal_embassy::{NalStack, TcpBuffers, UdpBuffers};
+    ///
+    /// async fn run(stack: embassy_net::Stack<'static>) {
+    ///     let tcp_buffers = TcpBuffers::<2, 1024, 1024>::new();
+    ///     let udp_buffers = UdpBuffers::<2, 512, 512, 4>::new();
+    ///     let nal = NalStack::new(stack, &tcp_buffers, &udp_buffers);
+    ///
+    ///     let socket = nal.tcp().connect("192.168.1.1:80".parse().unwrap()).await.unwrap();
+    ///     let udp = nal.udp().bind("0.0.0.0:1234".parse().unwrap()).await.unwrap();
+    /// }
+    /// ```
+    pub fn new(
+        stack: Stack<'d>,
+        tcp_buffers: &'d dyn DynPool<TcpSocketBuffers>,
+        udp_buffers: &'d dyn DynPool<UdpSocketBuffers>,
+    ) -> Self {
+        Self {
+            stack,
+            tcp_buffers,
+            udp_buffers,
+        }
+    }
+
+    /// Return a TCP factory over the stack.
+    pub fn tcp(&self) -> Tcp<'d> {
+        Tcp::new(self.stack, self.tcp_buffers)
+    }
+
+    /// Return a UDP factory over the stack.
+    pub fn udp(&self) -> Udp<'d> {
+        Udp::new(self.stack, self.udp_buffers)
+    }
+
+    /// Return a DNS resolver using the DNS servers the stack is configured with.
+    #[cfg(feature = "dns")]
+    pub fn dns(&self) -> Dns<'d> {
+        Dns::new(self.stack)
+    }
+
+    /// Return a DNS resolver sending its queries to the provided DNS server
+    /// over UDP sockets from the UDP socket buffers pool, with query IDs drawn from `rng`
+    /// (see `Dns::with_server`).
+    #[cfg(feature = "dns")]
+    pub fn dns_with_server(&self, server: IpAddr, rng: &'d RefCell<dyn Rng>) -> Dns<'d> {
+        Dns::with_server(self.stack, self.udp_buffers, server, rng)
+    }
+
+    /// Resolve the provided host name and connect to the provided port on it.
+    ///
+    /// If the stack has an IPv6 configuration, the `AAAA` record is tried first, and the `A` record
+    /// if it does not resolve or if the connection to it fails. The attempts are sequential rather
+    /// than concurrent as with "Happy Eyeballs", as each of them would need its own socket buffers.
+    ///
+    /// An IP address literal (i.e. `192.168.1.1` or `fe80::1`) is connected to without a DNS query.
+    ///
+    /// The queries are sent to the DNS servers the stack is configured with (see `NalStack::dns`).
+    /// On failure, the error of the last attempt is returned.
+    #[cfg(feature = "dns")]
+    pub async fn connect_hostname(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<TcpSocket<'d>, HostConnectError> {
+        let tcp = self.tcp();
+
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            return Ok(
+                pin!(tcp.connect_retry(SocketAddr::new(addr, port), 0, Duration::MIN)).await?,
+            );
+        }
+
+        let dns = self.dns();
+
+        let addr_types = [
+            (AddrType::IPv6, self.prefer_ipv6()),
+            (AddrType::IPv4, cfg!(feature = "proto-ipv4")),
+        ];
+
+        let mut result = Err(HostConnectError::Tcp(TcpError::ProtoDisabled));
+
+        for (addr_type, enabled) in addr_types {
+            if !enabled {
+                continue;
+            }
+
+            let addr = match pin!(dns.get_host_by_name(host, addr_type)).await {
+                Ok(addr) => addr,
+                Err(e) => {
+                    debug!("Resolving {} failed: {}", host, e);
+                    result = Err(e.into());
+                    continue;
+                }
+            };
+
+            let remote = SocketAddr::new(addr, port);
+
+            match pin!(tcp.connect_retry(remote, 0, Duration::MIN)).await {
+                Ok(socket) => return Ok(socket),
+                Err(e) => {
+                    debug!("Connecting to {} ({}) failed: {}", host, remote, e);
+                    result = Err(e.into());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Whether `connect_hostname` should try the `AAAA` record of the host
+    #[cfg(feature = "dns")]
+    fn prefer_ipv6(&self) -> bool {
+        #[cfg(feature = "proto-ipv6")]
+        {
+            !cfg!(feature = "proto-ipv4") || self.stack.config_v6().is_some()
+        }
+
+        #[cfg(not(feature = "proto-ipv6"))]
+        {
+            false
+        }
+    }
+}
+
+impl<'d> StackHandle<'d> for NalStack<'d> {
+    fn stack(&self) -> Stack<'d> {
+        self.stack
+    }
+}
+
+/// The error returned by `NalStack::connect_hostname`
+#[cfg(feature = "dns")]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostConnectError {
+    /// Resolving the host name failed
+    Dns(DnsError),
+    /// Connecting to the resolved address failed
+    Tcp(TcpError),
+}
+
+#[cfg(feature = "dns")]
+impl From<DnsError> for HostConnectError {
+    fn from(e: DnsError) -> Self {
+        Self::Dns(e)
+    }
+}
+
+#[cfg(feature = "dns")]
+impl From<TcpError> for HostConnectError {
+    fn from(e: TcpError) -> Self {
+        Self::Tcp(e)
+    }
+}
+
+#[cfg(feature = "dns")]
+impl Display for HostConnectError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Dns(e) => write!(f, "{}", e),
+            Self::Tcp(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "dns")]
+impl core::error::Error for HostConnectError {}
+
+#[cfg(feature = "dns")]
+impl embedded_io_async::Error for HostConnectError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Dns(e) => e.kind(),
+            Self::Tcp(e) => e.kind(),
+        }
+    }
+}
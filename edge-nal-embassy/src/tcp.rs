@@ -1,34 +1,65 @@
-use core::fmt::Display;
+use core::cell::Cell;
+use core::fmt::{Debug, Display};
 use core::future::{poll_fn, Future};
 use core::net::SocketAddr;
 use core::pin::pin;
 use core::ptr::NonNull;
 use core::task::Poll;
 
-use edge_nal::{Close, Readable, TcpBind, TcpConnect, TcpShutdown, TcpSplit};
+use edge_nal::{Close, Readable, TcpBind, TcpConnect, TcpShutdown, TcpSplit, Writable};
 
 use embassy_futures::join::join;
+use embassy_futures::select::select_array;
 
-use embassy_net::tcp::{AcceptError, ConnectError, Error, TcpReader, TcpWriter};
+use embassy_net::tcp::{AcceptError, ConnectError, Error, State, TcpReader, TcpWriter};
 use embassy_net::Stack;
 
-use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use embassy_time::{with_timeout, Duration, Instant, TimeoutError, Timer};
 
+use embedded_io_async::{BufRead, ErrorKind, ErrorType, Read, Write};
+
+use crate::convert::{to_emb_bind_socket, to_emb_socket, to_net_socket, AddrFamilyDisabled};
 use crate::sealed::SealedDynPool;
-use crate::{to_emb_bind_socket, to_emb_socket, to_net_socket, DynPool, Pool};
+use crate::{count_bytes, DynPool, Pool, PoolGuard, PoolStorage, SocketFactory, StackHandle};
 
 /// A type that implements the `TcpConnect` and `TcpBind` factory traits from `edge-nal`
 /// Uses the provided Embassy networking stack and TCP buffers pool to create TCP sockets.
 ///
-/// The type is `Copy` and `Clone`, so it can be easily passed around.
-#[derive(Copy, Clone)]
-pub struct Tcp<'d> {
+/// The type is `Copy` and `Clone`, so it can be easily passed around. Copies can be used
+/// concurrently by multiple tasks of the same executor (see `Pool` for why it cannot be shared across executors).
+///
+/// By default, the pool of TCP socket buffers is type-erased as `dyn DynPool<TcpSocketBuffers>`.
+/// Use `Tcp::new_static` to keep the concrete type of the pool and avoid the dynamic dispatch
+/// on each socket buffers allocation.
+///
+/// # TCP options
+/// The TCP options are negotiated by `smoltcp`, and `embassy-net` exposes no setting for them:
+/// - Selective acknowledgements (SACK) are always offered, and when the peer accepts them, the data
+///   received out of order is reported to it, so that it only retransmits the missing segments.
+///   The SACKs received from the peer are not used for the retransmissions of the socket, though.
+/// - Timestamps are never sent, as `embassy-net` does not set the timestamp generator they need.
+/// - Window scaling is always offered, based on the size of the receive buffer (see `TcpBuffers`).
+pub struct Tcp<'d, P = dyn DynPool<TcpSocketBuffers> + 'd>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     /// The Embassy networking stack to use for creating TCP sockets.
     stack: Stack<'d>,
     /// The pool of TCP socket buffers to use for creating TCP sockets.
-    buffers: &'d dyn DynPool<TcpSocketBuffers>,
+    buffers: &'d P,
+}
+
+impl<P> Clone for Tcp<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
+impl<P> Copy for Tcp<'_, P> where P: DynPool<TcpSocketBuffers> + ?Sized {}
+
 impl<'d> Tcp<'d> {
     /// Create a new `Tcp` instance for the provided Embassy networking stack, using the provided TCP buffers.
     ///
@@ -38,36 +69,113 @@ impl<'d> Tcp<'d> {
     ///   NOTE: Ensure that the number of buffers in the pool is not greater than the number of sockets
     ///   supported by the provided [embassy_net::Stack], or else [smoltcp::iface::SocketSet] will panic with
     ///   `adding a socket to a full SocketSet`.
+    ///
+    /// The pool does not need to be `'static`: the factory and its sockets are bounded by the lifetime
+    /// of the pool (and not by the borrow of the factory), so the pool can live on the stack of the task using it:
+    /// ```no_run
+    /// use edge_nal::TcpConnect;
+    /// use edge_nal_embassy::{Tcp, TcpBuffers};
+    ///
+    /// async fn run(stack: embassy_net::Stack<'static>) {
+    ///     let buffers = TcpBuffers::<2, 1024, 1024>::new();
+    ///     let tcp = Tcp::new(stack, &buffers);
+    ///
+    ///     let socket = tcp.connect("192.168.1.1:80".parse().unwrap()).await.unwrap();
+    ///     // Use the socket, which cannot outlive `buffers`
+    /// }
+    /// ```
     pub fn new(stack: Stack<'d>, buffers: &'d dyn DynPool<TcpSocketBuffers>) -> Self {
         Self { stack, buffers }
     }
 }
 
-impl TcpConnect for Tcp<'_> {
+impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize>
+    Tcp<'d, TcpBuffers<N, TX_SZ, RX_SZ>>
+{
+    /// Create a new `Tcp` instance for the provided Embassy networking stack, using the provided TCP buffers.
+    ///
+    /// Unlike `Tcp::new`, the concrete type of the pool is preserved, so allocating and freeing
+    /// socket buffers is statically dispatched.
+    ///
+    /// # Arguments
+    /// - `stack`: The Embassy networking stack to use for creating TCP sockets.
+    /// - `buffers`: A reference to a pool of TCP socket buffers.
+    ///   NOTE: Ensure that the number of buffers in the pool is not greater than the number of sockets
+    ///   supported by the provided [embassy_net::Stack], or else [smoltcp::iface::SocketSet] will panic with
+    ///   `adding a socket to a full SocketSet`.
+    pub fn new_static(stack: Stack<'d>, buffers: &'d TcpBuffers<N, TX_SZ, RX_SZ>) -> Self {
+        Self { stack, buffers }
+    }
+}
+
+impl<'d, P> StackHandle<'d> for Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn stack(&self) -> Stack<'d> {
+        self.stack
+    }
+}
+
+impl<'d, P> TcpConnect for Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     type Error = TcpError;
 
     type Socket<'a>
-        = TcpSocket<'a>
+        = TcpSocket<'d, P>
     where
         Self: 'a;
 
     async fn connect(&self, remote: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
-        let mut socket = TcpSocket::new(self.stack, self.buffers)?;
+        self.connect_retry(remote, 0, Duration::MIN).await
+    }
+}
+
+impl<'d, P> Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Connect to the provided remote address, retrying the allocation of the socket buffers
+    /// if the pool is temporarily exhausted.
+    ///
+    /// # Arguments
+    /// - `remote`: The remote address to connect to.
+    /// - `retries`: The number of allocation retries before giving up with `TcpError::NoBuffers`.
+    /// - `delay`: The delay between two allocation attempts.
+    ///
+    /// # Cancellation safety
+    /// Dropping the future returns the socket buffers to the pool and aborts the connection attempt.
+    pub async fn connect_retry(
+        &self,
+        remote: SocketAddr,
+        retries: usize,
+        delay: Duration,
+    ) -> Result<TcpSocket<'d, P>, TcpError> {
+        let mut socket = TcpSocket::new_retry(self.stack, self.buffers, retries, delay).await?;
 
         socket
             .socket
-            .connect(to_emb_socket(remote).ok_or(TcpError::UnsupportedProto)?)
-            .await?;
+            .connect(to_emb_socket(remote)?)
+            .await
+            .map_err(|source| TcpError::Connect {
+                addr: remote,
+                source,
+            })?;
 
         Ok(socket)
     }
 }
 
-impl TcpBind for Tcp<'_> {
+impl<'d, P> TcpBind for Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     type Error = TcpError;
 
     type Accept<'a>
-        = TcpAccept<'a>
+        = TcpAccept<'d, P>
     where
         Self: 'a;
 
@@ -75,59 +183,407 @@ impl TcpBind for Tcp<'_> {
         Ok(TcpAccept {
             stack: *self,
             local,
+            filter: None,
+            socket_timeout: None,
         })
     }
 }
 
+impl<'d, P> SocketFactory<'d> for Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    type Error = TcpError;
+
+    type Bound = TcpAccept<'d, P>;
+
+    async fn bind_local(&self, local: SocketAddr) -> Result<Self::Bound, Self::Error> {
+        TcpBind::bind(self, local).await
+    }
+
+    fn is_out_of_buffers(&self, error: &Self::Error) -> bool {
+        matches!(error, TcpError::NoBuffers)
+    }
+}
+
 /// A type that represents an acceptor for incoming TCP client connections.
 /// Implements the `TcpAccept` factory trait from `edge-nal`
 ///
 /// The type is `Copy` and `Clone`, so it can be easily passed around.
-#[derive(Copy, Clone)]
-pub struct TcpAccept<'d> {
-    stack: Tcp<'d>,
+pub struct TcpAccept<'d, P = dyn DynPool<TcpSocketBuffers> + 'd>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    stack: Tcp<'d, P>,
     local: SocketAddr,
+    filter: Option<&'d dyn Fn(SocketAddr) -> bool>,
+    socket_timeout: Option<Duration>,
 }
 
-impl edge_nal::TcpAccept for TcpAccept<'_> {
+impl<P> Clone for TcpAccept<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for TcpAccept<'_, P> where P: DynPool<TcpSocketBuffers> + ?Sized {}
+
+impl<P> ErrorType for TcpAccept<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    type Error = TcpError;
+}
+
+impl<'d, P> edge_nal::TcpAccept for TcpAccept<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     type Error = TcpError;
 
     type Socket<'a>
-        = TcpSocket<'a>
+        = TcpSocket<'d, P>
     where
         Self: 'a;
 
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
+        // From here on, the buffers are freed by the `Drop` impl of the socket, even if the future is cancelled
         let mut socket = TcpSocket::new(self.stack.stack, self.stack.buffers)?;
 
-        socket
-            .socket
-            .accept(to_emb_bind_socket(self.local).ok_or(TcpError::UnsupportedProto)?)
-            .await?;
+        let local = self.accept_on(&mut socket).await?;
+
+        Ok((local, socket))
+    }
+
+    /// The exhaustion of the TCP socket buffers pool (`TcpError::NoBuffers`) is reported as backpressure.
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        matches!(error, TcpError::NoBuffers)
+    }
+}
+
+impl<'d, P> TcpAccept<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Only accept the connections whose remote address satisfies the provided predicate
+    /// (i.e. is within an allowed subnet).
+    ///
+    /// Connections from other addresses are reset as soon as they are established, and the socket
+    /// (and its buffers) is reused to wait for the next connection, so these connections are never
+    /// returned by `accept` and do not take a socket from the pool.
+    ///
+    /// # Arguments
+    /// - `filter`: The predicate, called with the remote address of each connection.
+    pub fn with_filter(mut self, filter: &'d dyn Fn(SocketAddr) -> bool) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Set the inactivity timeout of each accepted socket (see `TcpSocket::set_timeout`).
+    ///
+    /// The timeout is armed as soon as the connection is established, so that clients which connect
+    /// but never send anything are reset without the server having to arm it on every connection
+    /// (their next read then fails with `TcpError::Timeout`).
+    /// Note that it is an inactivity timeout, so it does not catch clients trickling data slowly.
+    pub fn with_socket_timeout(mut self, timeout: Duration) -> Self {
+        self.socket_timeout = Some(timeout);
+        self
+    }
+
+    /// Accept an incoming connection, retrying the allocation of the socket buffers
+    /// if the pool is temporarily exhausted.
+    ///
+    /// # Arguments
+    /// - `retries`: The number of allocation retries before giving up with `TcpError::NoBuffers`.
+    /// - `delay`: The delay between two allocation attempts.
+    ///
+    /// # Cancellation safety
+    /// The method is cancellation safe with respect to the pool: the socket buffers are owned by the
+    /// socket being accepted as soon as they are allocated, so dropping the future (i.e. when it loses a `select`)
+    /// returns them to the pool. A connection in the middle of being accepted at that moment is reset though.
+    pub async fn accept_retry(
+        &self,
+        retries: usize,
+        delay: Duration,
+    ) -> Result<(SocketAddr, TcpSocket<'d, P>), TcpError> {
+        // From here on, the buffers are freed by the `Drop` impl of the socket, even if the future is cancelled
+        let mut socket =
+            TcpSocket::new_retry(self.stack.stack, self.stack.buffers, retries, delay).await?;
+
+        let local = self.accept_on(&mut socket).await?;
+
+        Ok((local, socket))
+    }
+
+    /// Wait for a connection on the provided socket, and return the local address of the connection
+    ///
+    /// The socket is either a new one, or one which was left listening by a cancelled call (see `TcpMultiAccept`),
+    /// possibly with a connection established in the meantime.
+    async fn accept_on(&self, socket: &mut TcpSocket<'d, P>) -> Result<SocketAddr, TcpError> {
+        loop {
+            match socket.socket.state() {
+                State::Closed | State::Listen => socket
+                    .socket
+                    .accept(to_emb_bind_socket(self.local)?)
+                    .await
+                    .map_err(|source| TcpError::Accept {
+                        addr: self.local,
+                        source,
+                    })?,
+                State::SynReceived => {
+                    // `accept` cannot resume the handshake of a listening socket, so wait for it to
+                    // complete (or to fail) and check the state again
+                    let mut established = pin!(socket.socket.wait_write_ready());
+
+                    poll_fn(|cx| {
+                        if established.as_mut().poll(cx).is_ready()
+                            || !matches!(socket.socket.state(), State::SynReceived)
+                        {
+                            Poll::Ready(())
+                        } else {
+                            Poll::Pending
+                        }
+                    })
+                    .await;
+
+                    continue;
+                }
+                _ => (),
+            }
+
+            let Some(remote) = socket.socket.remote_endpoint().map(to_net_socket) else {
+                // The connection was reset right after being established
+                continue;
+            };
+
+            if self.filter.is_none_or(|filter| filter(remote)) {
+                if let Some(timeout) = self.socket_timeout {
+                    socket.set_timeout(Some(timeout));
+                }
+
+                let local_endpoint = unwrap!(socket.socket.local_endpoint());
+
+                break Ok(to_net_socket(local_endpoint));
+            }
+
+            debug!("Rejecting connection from {} on {}", remote, self.local);
+
+            // Reset the connection and wait for the next one with the same socket
+            socket.socket.abort();
+            let _ = socket.socket.flush().await;
+        }
+    }
+}
+
+impl<'d, P> Tcp<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Create an acceptor for incoming TCP client connections on several local addresses at once.
+    ///
+    /// Useful for serving the same handler on several ports (i.e. HTTP and alt-HTTP)
+    /// with a single accept loop.
+    ///
+    /// # Arguments
+    /// - `locals`: The local addresses to accept connections on.
+    pub fn bind_multi<const M: usize>(&self, locals: [SocketAddr; M]) -> TcpMultiAccept<'d, P, M> {
+        TcpMultiAccept {
+            acceptors: locals.map(|local| TcpAccept {
+                stack: *self,
+                local,
+                filter: None,
+                socket_timeout: None,
+            }),
+            backlog: core::array::from_fn(|_| Cell::new(None)),
+        }
+    }
+}
+
+/// A type that represents an acceptor for incoming TCP client connections on several local addresses.
+/// Implements the `TcpAccept` factory trait from `edge-nal`
+///
+/// Each `accept` call races one accept per local address, so it needs `M` socket buffers
+/// from the pool. Once a connection is accepted on one of the addresses, the sockets listening
+/// on the others are kept by the acceptor (with the connections established on them in the meantime, if any)
+/// for the next `accept` call, rather than being reset. Their socket buffers are returned to the pool
+/// when the acceptor is dropped.
+///
+/// Concurrent `accept` calls (i.e. from several server tasks) each listen with their own `M` sockets,
+/// but the acceptor only keeps one socket per address: a connection established on a socket which cannot
+/// be kept is reset.
+pub struct TcpMultiAccept<'d, P = dyn DynPool<TcpSocketBuffers> + 'd, const M: usize = 2>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    acceptors: [TcpAccept<'d, P>; M],
+    /// The sockets kept listening on each of the local addresses between the `accept` calls
+    backlog: [Cell<Option<TcpSocket<'d, P>>>; M],
+}
+
+impl<'d, P, const M: usize> edge_nal::TcpAccept for TcpMultiAccept<'d, P, M>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    type Error = TcpError;
+
+    type Socket<'a>
+        = TcpSocket<'d, P>
+    where
+        Self: 'a;
+
+    async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
+        let (_, addr, socket) = self.accept_port().await?;
+
+        Ok((addr, socket))
+    }
+
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        matches!(error, TcpError::NoBuffers)
+    }
+}
+
+impl<'d, P, const M: usize> TcpMultiAccept<'d, P, M>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Only accept the connections whose remote address satisfies the provided predicate,
+    /// on all of the local addresses (see `TcpAccept::with_filter`).
+    pub fn with_filter(mut self, filter: &'d dyn Fn(SocketAddr) -> bool) -> Self {
+        for acceptor in &mut self.acceptors {
+            acceptor.filter = Some(filter);
+        }
+
+        self
+    }
+
+    /// Set the inactivity timeout of each accepted socket, on all of the local addresses
+    /// (see `TcpAccept::with_socket_timeout`).
+    pub fn with_socket_timeout(mut self, timeout: Duration) -> Self {
+        for acceptor in &mut self.acceptors {
+            acceptor.socket_timeout = Some(timeout);
+        }
+
+        self
+    }
+
+    /// Accept an incoming connection on any of the local addresses.
+    ///
+    /// Returns the local port the connection was accepted on, along with
+    /// the address and the socket returned by `TcpAccept::accept`.
+    ///
+    /// # Cancellation safety
+    /// The method is cancellation safe: the sockets listening on the local addresses are kept by the acceptor.
+    pub async fn accept_port(&self) -> Result<(u16, SocketAddr, TcpSocket<'d, P>), TcpError> {
+        let mut sockets = BacklogGuard {
+            backlog: &self.backlog,
+            sockets: core::array::from_fn(|index| self.backlog[index].take()),
+        };
+
+        for (socket, acceptor) in sockets.sockets.iter_mut().zip(&self.acceptors) {
+            if socket.is_none() {
+                *socket = Some(TcpSocket::new(
+                    acceptor.stack.stack,
+                    acceptor.stack.buffers,
+                )?);
+            }
+        }
+
+        let mut acceptors = self.acceptors.iter();
+        let accepts = sockets
+            .sockets
+            .each_mut()
+            .map(|socket| unwrap!(acceptors.next()).accept_on(unwrap!(socket.as_mut())));
 
-        let local_endpoint = unwrap!(socket.socket.local_endpoint());
+        let (result, index) = select_array(accepts).await;
+        let addr = result?;
 
-        Ok((to_net_socket(local_endpoint), socket))
+        let socket = unwrap!(sockets.sockets[index].take());
+
+        Ok((self.acceptors[index].local.port(), addr, socket))
+    }
+}
+
+/// The sockets taken from the backlog of a `TcpMultiAccept` by an `accept_port` call,
+/// put back into the backlog when the call completes or is cancelled
+struct BacklogGuard<'a, 'd, P, const M: usize>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    backlog: &'a [Cell<Option<TcpSocket<'d, P>>>; M],
+    sockets: [Option<TcpSocket<'d, P>>; M],
+}
+
+impl<P, const M: usize> Drop for BacklogGuard<'_, '_, P, M>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn drop(&mut self) {
+        for (slot, socket) in self.backlog.iter().zip(&mut self.sockets) {
+            let Some(socket) = socket.take() else {
+                continue;
+            };
+
+            // A concurrent call might have put back its own socket already, keep the one with a connection, if any
+            let kept = match slot.take() {
+                Some(other) if matches!(socket.socket.state(), State::Closed | State::Listen) => {
+                    other
+                }
+                _ => socket,
+            };
+
+            slot.set(Some(kept));
+        }
     }
 }
 
 /// A type that represents a TCP socket
 /// Implements the `Read` and `Write` traits from `embedded-io-async`, as well as the `TcpSplit` factory trait from `edge-nal`
-pub struct TcpSocket<'d> {
+///
+/// Unlike `UdpSocket`, reading and writing need `&mut self`, as the underlying embassy-net socket keeps
+/// separate read and write state which cannot be shared behind a `&TcpSocket`. For a reader and a writer
+/// working concurrently (i.e. in two futures joined together, or in two tasks of the same executor),
+/// use `TcpSplit::split`: the returned `TcpSocketRead` and `TcpSocketWrite` halves borrow the socket
+/// independently and can be used at the same time, with no mutex needed around the socket.
+///
+/// # Dropping
+///
+/// `Drop` cannot await, and `embassy-net` removes the socket from the stack as soon as it is dropped,
+/// so dropping a socket neither sends a FIN nor a RST: the data not yet acknowledged by the peer is lost,
+/// and the peer only finds out that the connection is gone when it sends its next segment (which the stack
+/// answers with a RST) or when its own timeout expires. Therefore, before dropping the socket:
+/// - Call `TcpShutdown::close` with `Close::Both` (or `close_then_abort`, to bound the wait) if the
+///   last writes need to be delivered
+/// - Call `TcpShutdown::abort` if the peer should be told promptly that the connection is reset
+pub struct TcpSocket<'d, P = dyn DynPool<TcpSocketBuffers> + 'd>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     /// The underlying Embassy TCP socket.
     socket: embassy_net::tcp::TcpSocket<'d>,
-    /// The pool of TCP socket buffers used by this socket.
-    stack_buffers: &'d dyn DynPool<TcpSocketBuffers>,
-    /// The token used to identify the socket buffers in the pool.
-    buffer_token: NonNull<u8>,
+    /// The inactivity timeout of the socket and the activity it is measured against.
+    activity: Activity,
+    /// The stall timeout of the socket, if any.
+    stall_timeout: Option<Duration>,
+    /// Whether Nagle's algorithm is disabled, as embassy-net offers no getter for it.
+    nodelay: bool,
+    /// The number of bytes written to the socket.
+    bytes_sent: Cell<u64>,
+    /// The number of bytes read from the socket.
+    bytes_received: Cell<u64>,
+    /// The pool slot holding the socket buffers, freed when the socket is dropped.
+    _buffers: PoolGuard<'d, TcpSocketBuffers, P>,
 }
 
-impl<'d> TcpSocket<'d> {
-    fn new(
-        stack: Stack<'d>,
-        stack_buffers: &'d dyn DynPool<TcpSocketBuffers>,
-    ) -> Result<Self, TcpError> {
-        let mut socket_buffers = stack_buffers.alloc().ok_or(TcpError::NoBuffers)?;
+impl<'d, P> TcpSocket<'d, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn new(stack: Stack<'d>, stack_buffers: &'d P) -> Result<Self, TcpError> {
+        let (buffers, mut socket_buffers) =
+            PoolGuard::alloc(stack_buffers).ok_or(TcpError::NoBuffers)?;
 
         Ok(Self {
             socket: embassy_net::tcp::TcpSocket::new(
@@ -145,13 +601,47 @@ impl<'d> TcpSocket<'d> {
                     )
                 },
             ),
-            stack_buffers,
-            buffer_token: socket_buffers.token,
+            _buffers: buffers,
+            activity: Activity::new(None),
+            stall_timeout: None,
+            nodelay: false,
+            bytes_sent: Cell::new(0),
+            bytes_received: Cell::new(0),
         })
     }
 
+    async fn new_retry(
+        stack: Stack<'d>,
+        stack_buffers: &'d P,
+        retries: usize,
+        delay: Duration,
+    ) -> Result<Self, TcpError> {
+        let mut attempts = 0;
+
+        loop {
+            match Self::new(stack, stack_buffers) {
+                Err(TcpError::NoBuffers) if attempts < retries => {
+                    attempts += 1;
+
+                    debug!(
+                        "No TCP buffers available, retrying ({}/{})",
+                        attempts, retries
+                    );
+
+                    Timer::after(delay).await;
+                }
+                other => break other,
+            }
+        }
+    }
+
     async fn close(&mut self, what: Close) -> Result<(), TcpError> {
         async fn discard_all_data(rx: &mut TcpReader<'_>) -> Result<(), TcpError> {
+            // Discard the data directly from the receive buffer of the socket, without copying it
+            while rx.read_with(|data| (data.len(), ())).await.is_ok() {}
+
+            // `read_with` reports both the end of the data and a reset connection as an error,
+            // so tell the two apart with the regular read path
             let mut buf = [0; 32];
 
             while rx.read(&mut buf).await? > 0 {}
@@ -189,40 +679,271 @@ impl<'d> TcpSocket<'d> {
 
         Ok(())
     }
-}
 
-impl Drop for TcpSocket<'_> {
-    fn drop(&mut self) {
+    /// Close the write half of the connection, wait up to `timeout` for the pending data to be
+    /// acknowledged by the peer, and then reset the connection.
+    ///
+    /// Unlike `TcpShutdown::abort`, which discards the pending data, this gives a last message
+    /// (i.e. an error response) a bounded chance to be delivered before the reset.
+    ///
+    /// Returns `Ok(true)` if the pending data was delivered, and `Ok(false)` if the timeout expired first.
+    /// The connection is reset in both cases, as well as when flushing fails.
+    pub async fn close_then_abort(&mut self, timeout: Duration) -> Result<bool, TcpError> {
         self.socket.close();
-        unsafe {
-            self.stack_buffers.free(self.buffer_token);
+
+        let flushed = with_timeout(timeout, self.socket.flush()).await;
+
+        self.abort().await?;
+
+        match flushed {
+            Ok(result) => self.track(result).map(|()| true),
+            Err(TimeoutError) => Ok(false),
         }
     }
+
+    /// Set the inactivity timeout of the socket.
+    ///
+    /// If the timeout is set, the connection is reset if no data is received for the specified duration,
+    /// and the pending and subsequent reads and writes fail with `TcpError::Timeout`.
+    ///
+    /// Consider also setting a keep-alive interval shorter than the timeout, so that connections
+    /// which are idle, but whose peer is still alive, are not reset.
+    ///
+    /// Note: the underlying stack reports timeouts as connection resets, so a reset is reported as
+    /// `TcpError::Timeout` when the timeout could have expired, i.e. when the timeout has elapsed since
+    /// the last successful read or write, whether the connection was idle or not. Other resets
+    /// (i.e. by the peer of a connection which was recently active) are reported as `TcpError::General`,
+    /// also by the halves of a split socket.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.socket.set_timeout(timeout);
+        self.activity = Activity::new(timeout);
+    }
+
+    /// Set the stall timeout of the socket.
+    ///
+    /// If the timeout is set, a write or a flush fails with `TcpError::Stalled` when the peer does not
+    /// acknowledge any of the pending data for the specified duration, i.e. when it keeps advertising
+    /// a zero window because it does not read, or when it is unreachable.
+    ///
+    /// Unlike the inactivity timeout (see `set_timeout`), the connection is not reset, so that the caller
+    /// can decide whether to retry the write (a slow consumer) or to abort the connection (a stuck one).
+    /// The timeout does not apply to the halves of a split socket.
+    pub fn set_stall_timeout(&mut self, timeout: Option<Duration>) {
+        self.stall_timeout = timeout;
+    }
+
+    /// Return the stall timeout of the socket, if any (see `set_stall_timeout`).
+    pub fn stall_timeout(&self) -> Option<Duration> {
+        self.stall_timeout
+    }
+
+    /// Return `true` if the send buffer of the socket is full, so that writes block until the peer
+    /// acknowledges some of the pending data.
+    ///
+    /// This is transient with a peer which is reading; if it persists, the peer is advertising a zero
+    /// window (it is not reading) or it is unreachable. `embassy-net` does not expose the remote window
+    /// itself, so the two cases cannot be told apart, other than by the connection eventually being reset
+    /// by the inactivity timeout (see `set_timeout`) when the peer is unreachable.
+    pub fn is_send_blocked(&self) -> bool {
+        self.socket.may_send() && self.socket.send_queue() >= self.socket.send_capacity()
+    }
+
+    fn track<T>(&self, result: Result<T, Error>) -> Result<T, TcpError> {
+        self.activity.track(result)
+    }
+
+    /// Enable or disable `TCP_NODELAY`, i.e. disable or enable Nagle's algorithm.
+    ///
+    /// Can be toggled at any time on an established connection, e.g. left off during a bulk
+    /// transfer and turned on for the interactive phases of a protocol. Nagle's algorithm is
+    /// enabled on new sockets, i.e. `nodelay` is initially `false`.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.socket.set_nagle_enabled(!nodelay);
+        self.nodelay = nodelay;
+    }
+
+    /// Return the capacity of the receive buffer, i.e. the largest receive window the socket advertises
+    /// (see `TcpBuffers`).
+    pub fn recv_capacity(&self) -> usize {
+        self.socket.recv_capacity()
+    }
+
+    /// Return the capacity of the send buffer, i.e. how much data can be in flight unacknowledged
+    /// (see `TcpBuffers`).
+    pub fn send_capacity(&self) -> usize {
+        self.socket.send_capacity()
+    }
+
+    /// Return `true` if `TCP_NODELAY` is set, i.e. Nagle's algorithm is disabled.
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// Return the number of bytes written to the socket, including with its split write half.
+    ///
+    /// Only the payload is counted, not the TCP/IP headers or the retransmissions.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.get()
+    }
+
+    /// Return the number of bytes read from the socket, including with its split read half.
+    ///
+    /// Only the payload is counted, not the TCP/IP headers.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.get()
+    }
+
+    /// Read into several buffers in turn, i.e. a fixed-size header followed by a body.
+    ///
+    /// Like `Read::read`, waits until some data is available, but then keeps filling the buffers
+    /// with the data already received, without waiting for more. `embassy-net` has no scatter read,
+    /// so the data is copied from the receive buffer of the socket into each buffer in turn.
+    ///
+    /// Returns the total number of bytes read, which is `0` only at the end of the data
+    /// (or when the buffers are empty).
+    pub async fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, TcpError> {
+        let mut total = 0;
+
+        for buf in bufs.iter_mut() {
+            let mut offset = 0;
+
+            while offset < buf.len() {
+                // Only the first read waits for data
+                if total > 0 && self.socket.recv_queue() == 0 {
+                    return Ok(total);
+                }
+
+                let len = Read::read(self, &mut buf[offset..]).await?;
+                if len == 0 {
+                    return Ok(total);
+                }
+
+                offset += len;
+                total += len;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Wait until the socket becomes readable, or until the provided timeout expires.
+    ///
+    /// Returns `Ok(true)` if the socket is readable, and `Ok(false)` if the timeout expired.
+    /// See `Readable::readable` for the details on when the socket is considered readable.
+    pub async fn readable_timeout(&mut self, timeout: Duration) -> Result<bool, TcpError> {
+        match with_timeout(timeout, Readable::readable(self)).await {
+            Ok(result) => result.map(|_| true),
+            Err(TimeoutError) => Ok(false),
+        }
+    }
+
+    /// Return `true` if the socket can still receive data, i.e. the peer has not closed
+    /// its sending half of the connection and the connection has not been reset.
+    pub fn may_recv(&self) -> bool {
+        self.socket.may_recv()
+    }
+
+    /// Return the reason why the socket cannot receive more data, or `None` if it still can.
+    ///
+    /// Useful for telling apart a clean end of the data (the peer sent a FIN)
+    /// from a broken connection, when `read` returns `Ok(0)` or fails.
+    ///
+    /// Note that once the connection is closed on both sides and has left the `TIME-WAIT` state,
+    /// it is reported as `TcpCloseReason::Reset` too, as the TCP stack no longer tracks how it ended.
+    pub fn close_reason(&self) -> Option<TcpCloseReason> {
+        if self.socket.may_recv() {
+            return None;
+        }
+
+        match self.socket.state() {
+            State::CloseWait | State::LastAck | State::Closing | State::TimeWait => {
+                Some(TcpCloseReason::Fin)
+            }
+            State::Closed => Some(TcpCloseReason::Reset),
+            _ => None,
+        }
+    }
+}
+
+impl<P> Debug for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TcpSocket")
+            .field("local", &self.socket.local_endpoint().map(to_net_socket))
+            .field("remote", &self.socket.remote_endpoint().map(to_net_socket))
+            .field("state", &self.socket.state())
+            .finish()
+    }
 }
 
-impl ErrorType for TcpSocket<'_> {
+impl<P> ErrorType for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     type Error = TcpError;
 }
 
-impl Read for TcpSocket<'_> {
+impl<P> Read for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        Ok(self.socket.read(buf).await?)
+        let result = self.socket.read(buf).await;
+        let len = self.track(result)?;
+
+        count_bytes(&self.bytes_received, len);
+
+        Ok(len)
     }
 }
 
-impl Write for TcpSocket<'_> {
+impl<P> Write for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        Ok(self.socket.write(buf).await?)
+        let result = match self.stall_timeout {
+            // A write blocks only while the send buffer is full, and returns as soon as the peer
+            // acknowledges any data, so a write taking the whole timeout means no progress at all
+            Some(timeout) => with_timeout(timeout, self.socket.write(buf))
+                .await
+                .map_err(|_| TcpError::Stalled)?,
+            None => self.socket.write(buf).await,
+        };
+
+        let len = self.track(result)?;
+
+        count_bytes(&self.bytes_sent, len);
+
+        Ok(len)
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
-        self.socket.flush().await?;
+        let Some(timeout) = self.stall_timeout else {
+            let result = self.socket.flush().await;
 
-        Ok(())
+            return self.track(result);
+        };
+
+        loop {
+            let pending = self.socket.send_queue();
+
+            match with_timeout(timeout, self.socket.flush()).await {
+                Ok(result) => break self.track(result),
+                // Keep waiting for as long as the peer acknowledges some of the pending data
+                Err(TimeoutError) if self.socket.send_queue() < pending => (),
+                Err(TimeoutError) => break Err(TcpError::Stalled),
+            }
+        }
     }
 }
 
-impl Readable for TcpSocket<'_> {
+impl<P> Readable for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     async fn readable(&mut self) -> Result<(), Self::Error> {
         // embassy-net's wait_read_ready() only returns when can_recv() is true (data available),
         // but does not return when the peer closes the connection (FIN received) with empty buffer.
@@ -262,7 +983,27 @@ impl Readable for TcpSocket<'_> {
     }
 }
 
-impl TcpShutdown for TcpSocket<'_> {
+impl<P> Writable for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Wait until the transmit buffer of the socket has free space.
+    ///
+    /// Fails, just like writing would, if the write half of the connection is closed
+    /// (i.e. with `TcpShutdown::close`, or by a reset of the connection).
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        // Writing nothing waits for free space, but fails right away when the socket cannot send anymore
+        self.socket
+            .write_with(|_| (0, ()))
+            .await
+            .or_else(|e| self.track(Err(e)))
+    }
+}
+
+impl<P> TcpShutdown for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     async fn close(&mut self, what: Close) -> Result<(), Self::Error> {
         TcpSocket::close(self, what).await
     }
@@ -274,7 +1015,7 @@ impl TcpShutdown for TcpSocket<'_> {
 
 /// A type that represents the read half of a split TCP socket.
 /// Implements the `Read` trait from `embedded-io-async`.
-pub struct TcpSocketRead<'a>(TcpReader<'a>);
+pub struct TcpSocketRead<'a>(TcpReader<'a>, &'a Cell<u64>, &'a Activity);
 
 impl ErrorType for TcpSocketRead<'_> {
     type Error = TcpError;
@@ -282,7 +1023,12 @@ impl ErrorType for TcpSocketRead<'_> {
 
 impl Read for TcpSocketRead<'_> {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        self.0.read(buf).await.map_err(TcpError::from)
+        let result = self.0.read(buf).await;
+        let len = self.2.track(result)?;
+
+        count_bytes(self.1, len);
+
+        Ok(len)
     }
 }
 
@@ -300,7 +1046,7 @@ impl Readable for TcpSocketRead<'_> {
 
 /// A type that represents the write half of a split TCP socket.
 /// Implements the `Write` trait from `embedded-io-async`.
-pub struct TcpSocketWrite<'a>(TcpWriter<'a>);
+pub struct TcpSocketWrite<'a>(TcpWriter<'a>, &'a Cell<u64>, &'a Activity);
 
 impl ErrorType for TcpSocketWrite<'_> {
     type Error = TcpError;
@@ -308,15 +1054,38 @@ impl ErrorType for TcpSocketWrite<'_> {
 
 impl Write for TcpSocketWrite<'_> {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        self.0.write(buf).await.map_err(TcpError::from)
+        let result = self.0.write(buf).await;
+        let len = self.2.track(result)?;
+
+        count_bytes(self.1, len);
+
+        Ok(len)
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
-        self.0.flush().await.map_err(TcpError::from)
+        let result = self.0.flush().await;
+        self.2.track(result)?;
+
+        Ok(())
     }
 }
 
-impl TcpSplit for TcpSocket<'_> {
+impl Writable for TcpSocketWrite<'_> {
+    /// Wait until the transmit buffer of the socket has free space.
+    ///
+    /// Fails, just like writing would, if the write half of the connection is closed.
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0
+            .write_with(|_| (0, ()))
+            .await
+            .or_else(|e| self.2.track(Err(e)))
+    }
+}
+
+impl<P> TcpSplit for TcpSocket<'_, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
     type Read<'a>
         = TcpSocketRead<'a>
     where
@@ -330,10 +1099,181 @@ impl TcpSplit for TcpSocket<'_> {
     fn split(&mut self) -> (Self::Read<'_>, Self::Write<'_>) {
         let (read, write) = self.socket.split();
 
-        (TcpSocketRead(read), TcpSocketWrite(write))
+        (
+            TcpSocketRead(read, &self.bytes_received, &self.activity),
+            TcpSocketWrite(write, &self.bytes_sent, &self.activity),
+        )
     }
 }
 
+/// The inactivity timeout of a TCP socket, and the activity it is measured against,
+/// shared by the halves of a split socket.
+struct Activity {
+    /// The inactivity timeout of the socket, if any.
+    timeout: Option<Duration>,
+    /// The last time data was successfully read from or written to the socket.
+    last: Cell<Instant>,
+}
+
+impl Activity {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            last: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Map the result of a read or a write, blaming a reset of the connection on the inactivity timeout
+    /// only when the timeout could have expired.
+    ///
+    /// The stack leaves the socket in the same state after the timeout as after a reset by the peer,
+    /// It times out a connection, idle or not, once the timeout has elapsed since the last packet from the peer,
+    /// which the last successful read or write approximates.
+    fn track<T>(&self, result: Result<T, Error>) -> Result<T, TcpError> {
+        match result {
+            Ok(value) => {
+                if self.timeout.is_some() {
+                    self.last.set(Instant::now());
+                }
+
+                Ok(value)
+            }
+            Err(Error::ConnectionReset)
+                if self
+                    .timeout
+                    .is_some_and(|timeout| self.last.get().elapsed() >= timeout) =>
+            {
+                Err(TcpError::Timeout)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A buffered TCP socket.
+/// Implements the `BufRead` trait from `embedded-io-async` on top of a `TcpSocket`,
+/// as well as `Read`, `Write` and `Readable`.
+///
+/// Useful for line-oriented protocols, where the data is consumed up to a delimiter
+/// rather than in fixed-size chunks.
+pub struct BufTcpSocket<'d, const N: usize, P = dyn DynPool<TcpSocketBuffers> + 'd>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    socket: TcpSocket<'d, P>,
+    buf: [u8; N],
+    pos: usize,
+    len: usize,
+}
+
+impl<'d, const N: usize, P> BufTcpSocket<'d, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    /// Create a new buffered TCP socket wrapping the provided socket.
+    pub const fn new(socket: TcpSocket<'d, P>) -> Self {
+        Self {
+            socket,
+            buf: [0; N],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Return a reference to the wrapped socket.
+    pub fn socket(&mut self) -> &mut TcpSocket<'d, P> {
+        &mut self.socket
+    }
+
+    /// Release the wrapped socket.
+    ///
+    /// Note that any data buffered but not yet consumed is lost.
+    pub fn release(self) -> TcpSocket<'d, P> {
+        self.socket
+    }
+}
+
+impl<const N: usize, P> ErrorType for BufTcpSocket<'_, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    type Error = TcpError;
+}
+
+impl<const N: usize, P> BufRead for BufTcpSocket<'_, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        if self.pos == self.len {
+            self.pos = 0;
+            self.len = self.socket.read(&mut self.buf).await?;
+        }
+
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+}
+
+impl<const N: usize, P> Read for BufTcpSocket<'_, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // Bypass the buffer for large reads when nothing is buffered
+        if self.pos == self.len && buf.len() >= N {
+            return self.socket.read(buf).await;
+        }
+
+        let data = self.fill_buf().await?;
+        let len = data.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&data[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<const N: usize, P> Write for BufTcpSocket<'_, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.socket.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.socket.flush().await
+    }
+}
+
+impl<const N: usize, P> Readable for BufTcpSocket<'_, N, P>
+where
+    P: DynPool<TcpSocketBuffers> + ?Sized,
+{
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        if self.pos < self.len {
+            Ok(())
+        } else {
+            self.socket.readable().await
+        }
+    }
+}
+
+/// The reason why a TCP socket cannot receive more data, as returned by `TcpSocket::close_reason`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TcpCloseReason {
+    /// The peer gracefully closed its sending half of the connection (sent a FIN).
+    Fin,
+    /// The connection was reset (or is fully closed).
+    Reset,
+}
+
 /// A shared error type that is used by the TCP factory traits implementation as well as the TCP socket.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -341,13 +1281,28 @@ pub enum TcpError {
     /// A general TCP error.
     General(Error),
     /// An error that occurred while connecting.
-    Connect(ConnectError),
+    Connect {
+        /// The remote address of the connection.
+        addr: SocketAddr,
+        /// The underlying connect error.
+        source: ConnectError,
+    },
     /// An error that occurred while accepting a connection.
-    Accept(AcceptError),
+    Accept {
+        /// The local address the connection was accepted on.
+        addr: SocketAddr,
+        /// The underlying accept error.
+        source: AcceptError,
+    },
     /// No TCP socket buffers available.
     NoBuffers,
-    /// The provided socket address uses an unsupported protocol.
-    UnsupportedProto,
+    /// The connection was reset because of the inactivity timeout set with `TcpSocket::set_timeout`.
+    Timeout,
+    /// The peer did not acknowledge any data for the stall timeout set with `TcpSocket::set_stall_timeout`.
+    Stalled,
+    /// The address family (IPv4 or IPv6) of the provided socket address is not enabled
+    /// with the `proto-ipv4` / `proto-ipv6` features of this crate.
+    ProtoDisabled,
 }
 
 impl From<Error> for TcpError {
@@ -356,15 +1311,9 @@ impl From<Error> for TcpError {
     }
 }
 
-impl From<ConnectError> for TcpError {
-    fn from(e: ConnectError) -> Self {
-        TcpError::Connect(e)
-    }
-}
-
-impl From<AcceptError> for TcpError {
-    fn from(e: AcceptError) -> Self {
-        TcpError::Accept(e)
+impl From<AddrFamilyDisabled> for TcpError {
+    fn from(_: AddrFamilyDisabled) -> Self {
+        TcpError::ProtoDisabled
     }
 }
 
@@ -372,10 +1321,19 @@ impl Display for TcpError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             TcpError::General(e) => write!(f, "TCP general error: {:?}", e),
-            TcpError::Connect(e) => write!(f, "TCP connect error: {:?}", e),
-            TcpError::Accept(e) => write!(f, "TCP accept error: {:?}", e),
+            TcpError::Connect { addr, source } => {
+                write!(f, "TCP connect error to {}: {:?}", addr, source)
+            }
+            TcpError::Accept { addr, source } => {
+                write!(f, "TCP accept error on {}: {:?}", addr, source)
+            }
             TcpError::NoBuffers => write!(f, "TCP no buffers available"),
-            TcpError::UnsupportedProto => write!(f, "TCP unsupported protocol"),
+            TcpError::Timeout => write!(f, "TCP inactivity timeout"),
+            TcpError::Stalled => write!(f, "TCP peer stalled"),
+            TcpError::ProtoDisabled => write!(
+                f,
+                "TCP address family not enabled (see the `proto-ipv4` / `proto-ipv6` features)"
+            ),
         }
     }
 }
@@ -386,10 +1344,12 @@ impl embedded_io_async::Error for TcpError {
     fn kind(&self) -> ErrorKind {
         match self {
             TcpError::General(_) => ErrorKind::Other,
-            TcpError::Connect(_) => ErrorKind::Other,
-            TcpError::Accept(_) => ErrorKind::Other,
+            TcpError::Connect { .. } => ErrorKind::Other,
+            TcpError::Accept { .. } => ErrorKind::Other,
             TcpError::NoBuffers => ErrorKind::OutOfMemory,
-            TcpError::UnsupportedProto => ErrorKind::InvalidInput,
+            TcpError::Timeout => ErrorKind::TimedOut,
+            TcpError::Stalled => ErrorKind::TimedOut,
+            TcpError::ProtoDisabled => ErrorKind::Unsupported,
         }
     }
 }
@@ -398,8 +1358,6 @@ impl embedded_io_async::Error for TcpError {
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TcpSocketBuffers {
-    /// A token used to identify the buffer in the pool.
-    token: NonNull<u8>,
     /// The buffer for receiving data.
     rx_buf: NonNull<u8>,
     /// The buffer for transmitting data.
@@ -411,35 +1369,385 @@ pub struct TcpSocketBuffers {
 }
 
 /// A type alias for a pool of TCP socket buffers.
+///
+/// # Sizing the buffers
+/// - `RX_SZ` is the largest receive window the socket advertises to the peer, so a connection
+///   cannot receive faster than `RX_SZ` bytes per round-trip time. For a high-latency link, size it
+///   to the bandwidth-delay product of the link (i.e. 1 Mbit/s with a 600 ms RTT needs ~75 KB).
+///   Above 64 KB, `smoltcp` negotiates TCP window scaling, so the whole buffer can be advertised;
+///   it cannot exceed 1 GB (checked at compile time).
+/// - `TX_SZ` bounds the data sent but not yet acknowledged by the peer, and so limits the upload
+///   throughput the same way.
+///
+/// Congestion control is not configurable, as `embassy-net` does not expose the `smoltcp` setting.
 pub type TcpBuffers<const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> =
-    Pool<([u8; TX_SZ], [u8; RX_SZ]), N>;
+    Pool<TcpSlot<TX_SZ, RX_SZ>, N>;
+
+/// The buffers of one TCP socket in a pool of TCP socket buffers, i.e. the type of the objects
+/// of the memory passed to `Pool::new_external` for a pool of TCP socket buffers.
+pub type TcpSlot<const TX_SZ: usize, const RX_SZ: usize> = ([u8; TX_SZ], [u8; RX_SZ]);
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> SealedDynPool<TcpSocketBuffers>
-    for TcpBuffers<N, TX_SZ, RX_SZ>
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, S> SealedDynPool<TcpSocketBuffers>
+    for Pool<TcpSlot<TX_SZ, RX_SZ>, N, S>
+where
+    S: PoolStorage<TcpSlot<TX_SZ, RX_SZ>>,
 {
-    fn alloc(&self) -> Option<TcpSocketBuffers> {
+    fn alloc(&self) -> Option<(NonNull<u8>, TcpSocketBuffers)> {
+        // `smoltcp` panics when creating a socket with a larger receive buffer, as the window scale
+        // cannot advertise it (RFC 7323)
+        const {
+            core::assert!(
+                RX_SZ <= 1 << 30,
+                "The receive buffer of TcpBuffers cannot exceed 1 GB (RX_SZ <= 1 << 30)"
+            )
+        };
+
         let mut socket_buffers = Pool::alloc(self)?;
 
         let rx_buf = unsafe { &mut socket_buffers.as_mut().1 };
         let tx_buf = unsafe { &mut socket_buffers.as_mut().0 };
 
-        Some(TcpSocketBuffers {
-            token: socket_buffers.cast::<u8>(),
-            rx_buf: unwrap!(NonNull::new(rx_buf.as_mut_ptr())),
-            tx_buf: unwrap!(NonNull::new(tx_buf.as_mut_ptr())),
-            rx_buf_len: rx_buf.len(),
-            tx_buf_len: tx_buf.len(),
-        })
+        Some((
+            socket_buffers.cast::<u8>(),
+            TcpSocketBuffers {
+                rx_buf: unwrap!(NonNull::new(rx_buf.as_mut_ptr())),
+                tx_buf: unwrap!(NonNull::new(tx_buf.as_mut_ptr())),
+                rx_buf_len: rx_buf.len(),
+                tx_buf_len: tx_buf.len(),
+            },
+        ))
     }
 
     unsafe fn free(&self, buffer_token: NonNull<u8>) {
         unsafe {
-            Pool::free(self, buffer_token.cast::<([u8; TX_SZ], [u8; RX_SZ])>());
+            Pool::free(self, buffer_token.cast::<TcpSlot<TX_SZ, RX_SZ>>());
         }
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> DynPool<TcpSocketBuffers>
-    for TcpBuffers<N, TX_SZ, RX_SZ>
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, S> DynPool<TcpSocketBuffers>
+    for Pool<TcpSlot<TX_SZ, RX_SZ>, N, S>
+where
+    S: PoolStorage<TcpSlot<TX_SZ, RX_SZ>>,
 {
 }
+
+// A host test binary has no `defmt` logger to link against.
+// The tests use IPv4 addresses, and a stack over a driver of the IP medium.
+#[cfg(all(
+    test,
+    feature = "proto-ipv4",
+    feature = "medium-ip",
+    not(feature = "defmt")
+))]
+mod test {
+    use core::net::{Ipv4Addr, SocketAddr};
+    use core::pin::pin;
+    use core::task::{Context, Waker};
+
+    use edge_nal::{TcpAccept as _, TcpBind as _, TcpConnect as _};
+
+    use embassy_futures::join::join;
+    use embassy_futures::select::{select, Either};
+    use embassy_futures::{block_on, yield_now};
+
+    use embassy_net::driver::{Capabilities, Driver, HardwareAddress, LinkState, RxToken, TxToken};
+    use embassy_net::{Config, Ipv4Cidr, StackResources, StaticConfigV4};
+
+    use embassy_time::{Duration, Timer};
+
+    use embedded_io_async::Read as _;
+
+    use super::{Activity, Tcp, TcpBuffers, TcpError};
+
+    /// A driver with no link, so that the sockets of the stack never get any traffic.
+    struct NullDriver;
+
+    enum NoToken {}
+
+    impl RxToken for NoToken {
+        fn consume<R, F>(self, _f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            match self {}
+        }
+    }
+
+    impl TxToken for NoToken {
+        fn consume<R, F>(self, _len: usize, _f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            match self {}
+        }
+    }
+
+    impl Driver for NullDriver {
+        type RxToken<'a> = NoToken;
+        type TxToken<'a> = NoToken;
+
+        fn receive(&mut self, _cx: &mut Context) -> Option<(NoToken, NoToken)> {
+            None
+        }
+
+        fn transmit(&mut self, _cx: &mut Context) -> Option<NoToken> {
+            None
+        }
+
+        fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+            LinkState::Down
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            let mut caps = Capabilities::default();
+            caps.max_transmission_unit = 1500;
+            caps
+        }
+
+        fn hardware_address(&self) -> HardwareAddress {
+            HardwareAddress::Ip
+        }
+    }
+
+    const MTU: usize = 1500;
+
+    /// A driver which receives back the packets it transmits, so that the sockets of the stack
+    /// can connect to each other.
+    struct LoopbackDriver {
+        packets: [([u8; MTU], usize); 8],
+        first: usize,
+        len: usize,
+        waker: Option<Waker>,
+    }
+
+    impl LoopbackDriver {
+        const fn new() -> Self {
+            Self {
+                packets: [([0; MTU], 0); 8],
+                first: 0,
+                len: 0,
+                waker: None,
+            }
+        }
+    }
+
+    struct LoopbackRxToken([u8; MTU], usize);
+
+    impl RxToken for LoopbackRxToken {
+        fn consume<R, F>(mut self, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            f(&mut self.0[..self.1])
+        }
+    }
+
+    struct LoopbackTxToken<'a>(&'a mut LoopbackDriver);
+
+    impl TxToken for LoopbackTxToken<'_> {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            let driver = self.0;
+            let packets = driver.packets.len();
+
+            let (packet, packet_len) = &mut driver.packets[(driver.first + driver.len) % packets];
+            *packet_len = len;
+
+            let result = f(&mut packet[..len]);
+
+            driver.len += 1;
+
+            if let Some(waker) = driver.waker.take() {
+                waker.wake();
+            }
+
+            result
+        }
+    }
+
+    impl Driver for LoopbackDriver {
+        type RxToken<'a> = LoopbackRxToken;
+        type TxToken<'a> = LoopbackTxToken<'a>;
+
+        fn receive(&mut self, cx: &mut Context) -> Option<(LoopbackRxToken, LoopbackTxToken<'_>)> {
+            if self.len == 0 {
+                self.waker = Some(cx.waker().clone());
+                return None;
+            }
+
+            let (packet, len) = self.packets[self.first];
+            self.first = (self.first + 1) % self.packets.len();
+            self.len -= 1;
+
+            Some((LoopbackRxToken(packet, len), LoopbackTxToken(self)))
+        }
+
+        fn transmit(&mut self, cx: &mut Context) -> Option<LoopbackTxToken<'_>> {
+            if self.len == self.packets.len() {
+                self.waker = Some(cx.waker().clone());
+                return None;
+            }
+
+            Some(LoopbackTxToken(self))
+        }
+
+        fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+            LinkState::Up
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            let mut caps = Capabilities::default();
+            caps.max_transmission_unit = MTU;
+            caps
+        }
+
+        fn hardware_address(&self) -> HardwareAddress {
+            HardwareAddress::Ip
+        }
+    }
+
+    #[test]
+    fn test_cancelled_accept() {
+        let mut resources = StackResources::<2>::new();
+        let (stack, _runner) = embassy_net::new(NullDriver, Config::default(), &mut resources, 0);
+
+        let buffers = TcpBuffers::<1, 256, 256>::new();
+        let tcp = Tcp::new(stack, &buffers);
+
+        block_on(async {
+            let acceptor = tcp
+                .bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8080))
+                .await
+                .unwrap();
+
+            // The only socket buffers of the pool go back to it each time an accept waiting
+            // for a connection is cancelled, so the next accept can allocate them again
+            for _ in 0..3 {
+                let result = {
+                    let accept = pin!(acceptor.accept());
+                    let cancel = pin!(async {
+                        yield_now().await;
+                        assert_eq!(buffers.used(), 1);
+                    });
+
+                    select(accept, cancel).await
+                };
+
+                assert!(matches!(result, Either::Second(())));
+                assert_eq!(buffers.used(), 0);
+            }
+        });
+    }
+
+    #[test]
+    fn test_multi_accept_backlog() {
+        let mut resources = StackResources::<4>::new();
+        let (stack, _runner) = embassy_net::new(NullDriver, Config::default(), &mut resources, 0);
+
+        let buffers = TcpBuffers::<2, 256, 256>::new();
+
+        // The acceptor borrows the stack and the pool, not the temporary `Tcp` handle
+        let acceptor = Tcp::new(stack, &buffers).bind_multi([
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8080),
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8081),
+        ]);
+
+        block_on(async {
+            // The sockets listening on both ports are kept by the acceptor when an accept is cancelled,
+            // and reused by the next accept rather than allocated anew
+            for _ in 0..3 {
+                let result = {
+                    let accept = pin!(acceptor.accept());
+                    let cancel = pin!(async {
+                        yield_now().await;
+                        assert_eq!(buffers.used(), 2);
+                    });
+
+                    select(accept, cancel).await
+                };
+
+                assert!(matches!(result, Either::Second(())));
+                assert_eq!(buffers.used(), 2);
+            }
+        });
+
+        drop(acceptor);
+
+        assert_eq!(buffers.used(), 0);
+    }
+
+    #[test]
+    fn test_reset_or_timeout() {
+        use embassy_net::tcp::Error;
+        use embassy_time::Duration;
+
+        let reset = || Err::<(), _>(Error::ConnectionReset);
+
+        // Without a timeout, a reset is a reset
+        let activity = Activity::new(None);
+        assert!(matches!(activity.track(reset()), Err(TcpError::General(_))));
+
+        // The stack times out idle connections too
+        let activity = Activity::new(Some(Duration::from_secs(0)));
+        assert!(matches!(activity.track(reset()), Err(TcpError::Timeout)));
+
+        // Not before the timeout has elapsed since the last activity
+        let activity = Activity::new(Some(Duration::from_secs(3600)));
+        assert!(matches!(activity.track(reset()), Err(TcpError::General(_))));
+    }
+
+    #[test]
+    fn test_accepted_socket_timeout() {
+        let addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        let config = Config::ipv4_static(StaticConfigV4 {
+            address: Ipv4Cidr::new(addr, 24),
+            gateway: None,
+            dns_servers: Default::default(),
+        });
+
+        let mut resources = StackResources::<3>::new();
+        let (stack, mut runner) =
+            embassy_net::new(LoopbackDriver::new(), config, &mut resources, 0);
+
+        let buffers = TcpBuffers::<2, 256, 256>::new();
+        let tcp = Tcp::new(stack, &buffers);
+
+        let timeout = Duration::from_millis(200);
+
+        block_on(async {
+            let test = pin!(async {
+                let acceptor = tcp
+                    .bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8080))
+                    .await
+                    .unwrap()
+                    .with_socket_timeout(timeout);
+
+                let accept = pin!(acceptor.accept());
+                let connect = pin!(tcp.connect(SocketAddr::new(addr.into(), 8080)));
+
+                let (accepted, connected) = join(accept, connect).await;
+
+                let (_, mut socket) = accepted.unwrap();
+                let _client = connected.unwrap();
+
+                // The client never sends anything, so the stack resets the accepted socket
+                // once the timeout armed by the acceptor expires
+                Timer::after(timeout * 2).await;
+
+                let mut buf = [0; 16];
+                assert!(matches!(
+                    socket.read(&mut buf).await,
+                    Err(TcpError::Timeout)
+                ));
+            });
+
+            let run = pin!(runner.run());
+
+            assert!(matches!(select(run, test).await, Either::Second(())));
+        });
+    }
+}
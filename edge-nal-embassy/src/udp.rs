@@ -1,31 +1,61 @@
-use core::fmt::Display;
+use core::cell::Cell;
+use core::fmt::{Debug, Display};
+use core::future::poll_fn;
 use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::ptr::NonNull;
+use core::task::{Context, Poll, Waker};
 
 use edge_nal::{
     MulticastV4, MulticastV6, Readable, UdpBind, UdpReceive, UdpSend, UdpSplit, UdpSplitMulticast,
 };
 
 use embassy_net::udp::{BindError, PacketMetadata, RecvError, SendError};
-use embassy_net::Stack;
+use embassy_net::{IpAddress, IpEndpoint, Stack};
+
+use embassy_time::{with_timeout, Duration, Instant, TimeoutError, Timer};
 
 use embedded_io_async::{ErrorKind, ErrorType};
 
+use crate::convert::{
+    to_emb_bind_socket, to_emb_socket, to_net_addr, to_net_socket, AddrFamilyDisabled,
+};
 use crate::sealed::SealedDynPool;
-use crate::{to_emb_bind_socket, to_emb_socket, to_net_socket, DynPool, Pool};
+use crate::{count_bytes, DynPool, Pool, PoolGuard, PoolStorage, SocketFactory, StackHandle};
 
 /// A type that implements the `UdpBind` factory trait from `edge-nal`.
 /// Uses the provided Embassy networking stack and UDP buffers pool to create UDP sockets.
 ///
-/// The type is `Copy` and `Clone`, so it can be easily passed around.
-#[derive(Copy, Clone)]
-pub struct Udp<'d> {
+/// The type is `Copy` and `Clone`, so it can be easily passed around. Copies can be used
+/// concurrently by multiple tasks of the same executor (see `Pool` for why it cannot be shared across executors).
+///
+/// By default, the pool of UDP socket buffers is type-erased as `dyn DynPool<UdpSocketBuffers>`.
+/// Use `Udp::new_static` to keep the concrete type of the pool and avoid the dynamic dispatch
+/// on each socket buffers allocation.
+pub struct Udp<'d, P = dyn DynPool<UdpSocketBuffers> + 'd>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     /// The Embassy networking stack to use for creating UDP sockets.
     stack: Stack<'d>,
     /// The pool of UDP socket buffers to use for creating UDP sockets.
-    buffers: &'d dyn DynPool<UdpSocketBuffers>,
+    buffers: &'d P,
+    /// The range of local ports to pick from when binding to port 0, if any.
+    ephemeral_ports: Option<EphemeralPorts<'d>>,
+    /// The MTU of the network interface, if provided.
+    mtu: Option<usize>,
 }
 
+impl<P> Clone for Udp<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Udp<'_, P> where P: DynPool<UdpSocketBuffers> + ?Sized {}
+
 impl<'d> Udp<'d> {
     /// Create a new `Udp` instance for the provided Embassy networking stack using the provided UDP buffers.
     ///
@@ -36,24 +66,222 @@ impl<'d> Udp<'d> {
     ///   supported by the provided [embassy_net::Stack], or else [smoltcp::iface::SocketSet] will panic with
     ///   `adding a socket to a full SocketSet`.
     pub fn new(stack: Stack<'d>, buffers: &'d dyn DynPool<UdpSocketBuffers>) -> Self {
-        Self { stack, buffers }
+        Self {
+            stack,
+            buffers,
+            ephemeral_ports: None,
+            mtu: None,
+        }
+    }
+}
+
+impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
+    Udp<'d, UdpBuffers<N, TX_SZ, RX_SZ, M>>
+{
+    /// Create a new `Udp` instance for the provided Embassy networking stack using the provided UDP buffers.
+    ///
+    /// Unlike `Udp::new`, the concrete type of the pool is preserved, so allocating and freeing
+    /// socket buffers is statically dispatched.
+    ///
+    /// # Arguments
+    /// - `stack`: The Embassy networking stack to use for creating UDP sockets.
+    /// - `buffers`: A pool of UDP socket buffers to use for creating UDP sockets.
+    ///   NOTE: Ensure that the number of buffers in the pool is not greater than the number of sockets
+    ///   supported by the provided [embassy_net::Stack], or else [smoltcp::iface::SocketSet] will panic with
+    ///   `adding a socket to a full SocketSet`.
+    pub fn new_static(stack: Stack<'d>, buffers: &'d UdpBuffers<N, TX_SZ, RX_SZ, M>) -> Self {
+        Self {
+            stack,
+            buffers,
+            ephemeral_ports: None,
+            mtu: None,
+        }
+    }
+}
+
+impl<'d, P> Udp<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    /// Use a local port from the provided range when binding a socket to port 0,
+    /// rather than the ephemeral port picked by the TCP/IP stack.
+    ///
+    /// Useful in networks whose firewall only lets traffic from certain source ports out.
+    ///
+    /// The port is picked at random among the ports of the range not held by another socket
+    /// bound from the same `UdpEphemeralPorts` (the stack itself does not tell which ports are in use).
+    /// Binding fails with `UdpError::NoFreePort` if none is left.
+    ///
+    /// # Arguments
+    /// - `ports`: The range of ports, shared by all the `Udp` instances which should not reuse each other's ports.
+    pub fn with_ephemeral_ports<const N: usize>(mut self, ports: &'d UdpEphemeralPorts<N>) -> Self {
+        self.ephemeral_ports = Some(EphemeralPorts {
+            first: ports.first,
+            last: ports.last,
+            in_use: &ports.in_use,
+        });
+        self
+    }
+
+    /// Set the MTU of the network interface, as reported by `UdpSocket::mtu` for the sockets bound afterwards.
+    ///
+    /// `embassy-net` does not expose the capabilities of the network driver once the stack is created,
+    /// so pass the `max_transmission_unit` from the `capabilities()` of the driver here.
+    ///
+    /// # Arguments
+    /// - `mtu`: The maximum transmission unit of the interface, in bytes.
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+}
+
+/// A range of local ports for `Udp::with_ephemeral_ports`, keeping track of the ports held by the sockets
+/// bound from it, so that no two of them get the same port.
+///
+/// Up to `N` sockets can hold a port from the range at any time; `N` is usually the number of buffers
+/// of the UDP buffers pool.
+pub struct UdpEphemeralPorts<const N: usize> {
+    first: u16,
+    last: u16,
+    /// The ports held by the sockets, 0 meaning a free slot
+    in_use: [Cell<u16>; N],
+}
+
+impl<const N: usize> UdpEphemeralPorts<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: Cell<u16> = Cell::new(0);
+
+    /// Create a new range of ports
+    ///
+    /// # Arguments
+    /// - `first`: The first port of the range.
+    /// - `last`: The last port of the range (inclusive).
+    ///
+    /// # Panics
+    /// If `first` is 0 or greater than `last`.
+    pub const fn new(first: u16, last: u16) -> Self {
+        core::assert!(
+            first > 0 && first <= last,
+            "The ephemeral port range must be non-empty and must not include port 0"
+        );
+
+        Self {
+            first,
+            last,
+            in_use: [Self::FREE; N],
+        }
+    }
+}
+
+/// The type-erased view of a `UdpEphemeralPorts`
+#[derive(Copy, Clone)]
+struct EphemeralPorts<'d> {
+    first: u16,
+    last: u16,
+    in_use: &'d [Cell<u16>],
+}
+
+impl<'d> EphemeralPorts<'d> {
+    /// Pick a random port of the range which is not held by another socket, starting at `seed`,
+    /// and return the slot now holding it, or `None` if all slots or all ports of the range are taken
+    fn acquire(&self, seed: u64) -> Option<&'d Cell<u16>> {
+        let slot = self.in_use.iter().find(|slot| slot.get() == 0)?;
+
+        let span = (self.last - self.first) as u32 + 1;
+        let start = (seed % span as u64) as u32;
+
+        let port = (0..span)
+            .map(|offset| self.first + ((start + offset) % span) as u16)
+            .find(|port| self.in_use.iter().all(|slot| slot.get() != *port))?;
+
+        slot.set(port);
+
+        Some(slot)
+    }
+}
+
+impl<'d, P> StackHandle<'d> for Udp<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    fn stack(&self) -> Stack<'d> {
+        self.stack
     }
 }
 
-impl UdpBind for Udp<'_> {
+impl<'d, P> UdpBind for Udp<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     type Error = UdpError;
 
     type Socket<'a>
-        = UdpSocket<'a>
+        = UdpSocket<'d, P>
     where
         Self: 'a;
 
     async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
-        let mut socket = UdpSocket::new(self.stack, self.buffers)?;
+        self.bind_retry(local, 0, Duration::MIN).await
+    }
+}
+
+impl<'d, P> SocketFactory<'d> for Udp<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    type Error = UdpError;
+
+    type Bound = UdpSocket<'d, P>;
+
+    async fn bind_local(&self, local: SocketAddr) -> Result<Self::Bound, Self::Error> {
+        self.bind_retry(local, 0, Duration::MIN).await
+    }
+
+    fn is_out_of_buffers(&self, error: &Self::Error) -> bool {
+        matches!(error, UdpError::NoBuffers)
+    }
+}
+
+impl<'d, P> Udp<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    /// Bind a socket to the provided local address, retrying the allocation of the socket buffers
+    /// if the pool is temporarily exhausted.
+    ///
+    /// # Arguments
+    /// - `local`: The local address to bind to.
+    /// - `retries`: The number of allocation retries before giving up with `UdpError::NoBuffers`.
+    /// - `delay`: The delay between two allocation attempts.
+    pub async fn bind_retry(
+        &self,
+        local: SocketAddr,
+        retries: usize,
+        delay: Duration,
+    ) -> Result<UdpSocket<'d, P>, UdpError> {
+        let mut socket = UdpSocket::new_retry(self.stack, self.buffers, retries, delay).await?;
+        socket.mtu = self.mtu;
+
+        let mut local = local;
+
+        if let Some(ephemeral_ports) = self.ephemeral_ports.filter(|_| local.port() == 0) {
+            let port = ephemeral_ports
+                .acquire(Instant::now().as_ticks())
+                .ok_or(UdpError::NoFreePort)?;
+
+            // Released when the socket is dropped
+            socket.ephemeral_port = Some(port);
+            local.set_port(port.get());
+        }
 
         socket
             .socket
-            .bind(to_emb_bind_socket(local).ok_or(UdpError::UnsupportedProto)?)?;
+            .bind(to_emb_bind_socket(local)?)
+            .map_err(|source| UdpError::Bind {
+                addr: local,
+                source,
+            })?;
 
         Ok(socket)
     }
@@ -61,24 +289,40 @@ impl UdpBind for Udp<'_> {
 
 /// A UDP socket.
 /// Implements the `UdpReceive` `UdpSend` and `UdpSplit` traits from `edge-nal`.
-pub struct UdpSocket<'d> {
+///
+/// When the receive buffer of the socket is full (either `RX_SZ` bytes or `M` packets are queued),
+/// the TCP/IP stack silently drops any newly arriving packet. Neither a counter of the dropped packets
+/// nor a drop-oldest policy is available, as `embassy-net` / `smoltcp` only log such drops.
+/// Protocols which cannot tolerate that should size `RX_SZ` and `M` for their worst-case burst.
+pub struct UdpSocket<'d, P = dyn DynPool<UdpSocketBuffers> + 'd>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     /// The Embassy networking stack.
-    #[allow(unused)]
     stack: embassy_net::Stack<'d>,
     /// The underlying Embassy UDP socket.
     socket: embassy_net::udp::UdpSocket<'d>,
-    /// The pool of UDP socket buffers.
-    stack_buffers: &'d dyn DynPool<UdpSocketBuffers>,
-    /// The token used to identify the socket buffers in the pool.
-    buffer_token: NonNull<u8>,
+    /// Whether packets sent by this host to the local port of the socket are received.
+    multicast_loop: bool,
+    /// The MTU of the network interface, if provided with `Udp::with_mtu`.
+    mtu: Option<usize>,
+    /// The number of payload bytes sent with the socket.
+    bytes_sent: Cell<u64>,
+    /// The number of payload bytes received with the socket.
+    bytes_received: Cell<u64>,
+    /// The slot of `UdpEphemeralPorts` holding the local port of the socket, if the port was picked from there.
+    ephemeral_port: Option<&'d Cell<u16>>,
+    /// The pool slot holding the socket buffers, freed when the socket is dropped.
+    _buffers: PoolGuard<'d, UdpSocketBuffers, P>,
 }
 
-impl<'d> UdpSocket<'d> {
-    fn new(
-        stack: Stack<'d>,
-        stack_buffers: &'d dyn DynPool<UdpSocketBuffers>,
-    ) -> Result<Self, UdpError> {
-        let mut socket_buffers = stack_buffers.alloc().ok_or(UdpError::NoBuffers)?;
+impl<'d, P> UdpSocket<'d, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    fn new(stack: Stack<'d>, stack_buffers: &'d P) -> Result<Self, UdpError> {
+        let (buffers, mut socket_buffers) =
+            PoolGuard::alloc(stack_buffers).ok_or(UdpError::NoBuffers)?;
 
         Ok(Self {
             stack,
@@ -109,11 +353,250 @@ impl<'d> UdpSocket<'d> {
                     )
                 },
             ),
-            stack_buffers,
-            buffer_token: socket_buffers.token,
+            _buffers: buffers,
+            multicast_loop: true,
+            mtu: None,
+            bytes_sent: Cell::new(0),
+            bytes_received: Cell::new(0),
+            ephemeral_port: None,
         })
     }
 
+    async fn new_retry(
+        stack: Stack<'d>,
+        stack_buffers: &'d P,
+        retries: usize,
+        delay: Duration,
+    ) -> Result<Self, UdpError> {
+        let mut attempts = 0;
+
+        loop {
+            match Self::new(stack, stack_buffers) {
+                Err(UdpError::NoBuffers) if attempts < retries => {
+                    attempts += 1;
+
+                    debug!(
+                        "No UDP buffers available, retrying ({}/{})",
+                        attempts, retries
+                    );
+
+                    Timer::after(delay).await;
+                }
+                other => break other,
+            }
+        }
+    }
+
+    /// Send the same data to multiple destinations.
+    ///
+    /// The data is sent to each destination in turn, and a failure to send to one destination
+    /// does not prevent sending to the others.
+    ///
+    /// # Arguments
+    /// - `destinations`: The destinations to send the data to, each paired with a slot which receives
+    ///   the result of sending to that destination (the initial value of the slot is overwritten).
+    /// - `data`: The data to send.
+    ///
+    /// Returns the number of destinations the data was successfully sent to.
+    pub async fn send_multi(
+        &mut self,
+        destinations: &mut [(SocketAddr, Result<(), UdpError>)],
+        data: &[u8],
+    ) -> usize {
+        let mut sent = 0;
+
+        for (remote, result) in destinations.iter_mut() {
+            *result = match to_emb_socket(*remote) {
+                Ok(emb_remote) => self
+                    .socket
+                    .send_to(data, emb_remote)
+                    .await
+                    .map_err(|source| UdpError::Send {
+                        addr: *remote,
+                        source,
+                    }),
+                Err(e) => Err(e.into()),
+            };
+
+            if result.is_ok() {
+                count_bytes(&self.bytes_sent, data.len());
+                sent += 1;
+            }
+        }
+
+        sent
+    }
+
+    /// Send a packet without waiting for space in the send buffer of the socket.
+    ///
+    /// Returns `Ok(true)` if the packet was queued for sending, and `Ok(false)` if the send buffer
+    /// (or its packet metadata slots) is full, in which case the packet is not sent.
+    /// Useful for real-time senders (i.e. audio or video), for which dropping a stale packet is better
+    /// than queueing it behind a backlog.
+    ///
+    /// Note: `embassy-net` exposes neither the free space of the send buffer nor a send which does not
+    /// register a waker, so the packet is offered to the socket with a no-op waker, which replaces the waker
+    /// of any send waiting for space on the same socket. Hence `try_send` must not race with an async send
+    /// on the same socket (through `&UdpSocket` or the split `UdpSocketSend` half), as that send would then
+    /// never be woken up. The exclusive borrow taken by `try_send` rules this out while such a send is pending.
+    ///
+    /// # Arguments
+    /// - `remote`: The destination to send the data to.
+    /// - `data`: The data to send.
+    pub fn try_send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<bool, UdpError> {
+        let emb_remote = to_emb_socket(remote)?;
+
+        // The no-op waker replaces the waker of a pending send, if any, so see the note above
+        let mut cx = Context::from_waker(Waker::noop());
+
+        match self.socket.poll_send_to(data, emb_remote, &mut cx) {
+            Poll::Ready(Ok(())) => {
+                count_bytes(&self.bytes_sent, data.len());
+
+                Ok(true)
+            }
+            Poll::Ready(Err(source)) => Err(UdpError::Send {
+                addr: remote,
+                source,
+            }),
+            Poll::Pending => Ok(false),
+        }
+    }
+
+    /// Receive several queued packets at once.
+    ///
+    /// Waits until at least one packet is available, and then keeps receiving the packets
+    /// which are already queued in the socket, without waiting for more.
+    /// This drains a backlog of packets with a single wakeup, rather than one wakeup per packet.
+    ///
+    /// # Arguments
+    /// - `packets`: The buffers to receive the packets into, one packet per buffer, each paired with
+    ///   a slot which receives the length and the remote address of the packet received in the buffer,
+    ///   or `None` if no packet was received in it.
+    ///
+    /// Returns the number of packets received (at least one, unless `packets` is empty), i.e. the
+    /// number of leading slots which are set.
+    /// A failure to receive a packet other than the first one (i.e. because it is truncated) is logged,
+    /// and the packets received so far are returned.
+    pub async fn receive_batch(
+        &mut self,
+        packets: &mut [UdpBatchPacket<'_>],
+    ) -> Result<usize, UdpError> {
+        for (_, result) in packets.iter_mut() {
+            *result = None;
+        }
+
+        let mut received = 0;
+
+        while received < packets.len() {
+            let buf = &mut *packets[received].0;
+
+            let (len, remote_endpoint) = if received == 0 {
+                self.socket.recv_from(buf).await?
+            } else {
+                let next = poll_fn(|cx| match self.socket.poll_recv_from(buf, cx) {
+                    Poll::Ready(result) => Poll::Ready(Some(result)),
+                    Poll::Pending => Poll::Ready(None),
+                })
+                .await;
+
+                match next {
+                    Some(Ok(next)) => next,
+                    Some(Err(e)) => {
+                        warn!("Dropping a queued UDP packet: {:?}", e);
+                        break;
+                    }
+                    None => break,
+                }
+            };
+
+            if self.is_looped_back(remote_endpoint.endpoint) {
+                continue;
+            }
+
+            count_bytes(&self.bytes_received, len);
+
+            packets[received].1 = Some((len, to_net_socket(remote_endpoint.endpoint)));
+            received += 1;
+        }
+
+        Ok(received)
+    }
+
+    /// Return the MTU of the network interface, if it was provided with `Udp::with_mtu`.
+    ///
+    /// Datagrams whose IP packet is larger than the MTU either fail to send, or are fragmented
+    /// by the stack when `embassy-net` has IP fragmentation enabled. Protocols which split their
+    /// payloads themselves (i.e. CoAP block-wise transfers) can use `max_payload_len` instead.
+    pub fn mtu(&self) -> Option<usize> {
+        self.mtu
+    }
+
+    /// Return the largest payload which fits in a single IP packet to the provided remote address,
+    /// i.e. the MTU minus the IP and UDP headers, capped by the size of the send buffer of the socket.
+    ///
+    /// Returns `None` if the MTU was not provided with `Udp::with_mtu`.
+    pub fn max_payload_len(&self, remote: SocketAddr) -> Option<usize> {
+        const UDP_HEADER_LEN: usize = 8;
+
+        let ip_header_len = match remote {
+            SocketAddr::V4(_) => 20,
+            SocketAddr::V6(_) => 40,
+        };
+
+        self.mtu.map(|mtu| {
+            mtu.saturating_sub(ip_header_len + UDP_HEADER_LEN)
+                .min(self.socket.payload_send_capacity())
+        })
+    }
+
+    /// Return the number of payload bytes sent with the socket, including with its split send half.
+    ///
+    /// The UDP/IP headers are not counted.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.get()
+    }
+
+    /// Return the number of payload bytes received with the socket, including with its split receive half.
+    ///
+    /// The UDP/IP headers are not counted, nor are the packets dropped as looped back
+    /// (see `set_multicast_loop`).
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.get()
+    }
+
+    /// Enable or disable the reception of packets sent by this host to the local port of the socket.
+    ///
+    /// Useful when the socket both sends to and listens on a multicast group (i.e. mDNS), and the network
+    /// echoes the packets sent by the host back to it. As `smoltcp` has no notion of multicast loopback,
+    /// the packets are filtered on receive by comparing their source with the addresses of the host
+    /// and the local port of the socket.
+    ///
+    /// Enabled by default.
+    pub fn set_multicast_loop(&mut self, enable: bool) {
+        self.multicast_loop = enable;
+    }
+
+    /// Return the stack to filter looped back packets with, if filtering is enabled
+    fn loop_filter(&self) -> Option<Stack<'d>> {
+        (!self.multicast_loop).then_some(self.stack)
+    }
+
+    /// Return `true` if the packet with the provided source should be dropped as looped back
+    fn is_looped_back(&self, remote: IpEndpoint) -> bool {
+        !self.multicast_loop && is_own(self.stack, &self.socket, remote)
+    }
+
+    /// Wait until the socket has a packet to receive, or until the provided timeout expires.
+    ///
+    /// Returns `Ok(true)` if the socket is readable, and `Ok(false)` if the timeout expired.
+    pub async fn readable_timeout(&self, timeout: Duration) -> Result<bool, UdpError> {
+        match with_timeout(timeout, self.socket.wait_recv_ready()).await {
+            Ok(()) => Ok(true),
+            Err(TimeoutError) => Ok(false),
+        }
+    }
+
     async fn join_v4(
         &self,
         #[allow(unused)] multicast_addr: Ipv4Addr,
@@ -121,10 +604,10 @@ impl<'d> UdpSocket<'d> {
     ) -> Result<(), UdpError> {
         #[cfg(feature = "multicast")]
         {
-            self.stack.join_multicast_group(
-                crate::to_emb_addr(core::net::IpAddr::V4(multicast_addr))
-                    .ok_or(UdpError::UnsupportedProto)?,
-            )?;
+            self.stack
+                .join_multicast_group(crate::convert::to_emb_addr(core::net::IpAddr::V4(
+                    multicast_addr,
+                ))?)?;
         }
 
         #[cfg(not(feature = "multicast"))]
@@ -142,10 +625,10 @@ impl<'d> UdpSocket<'d> {
     ) -> Result<(), UdpError> {
         #[cfg(feature = "multicast")]
         {
-            self.stack.leave_multicast_group(
-                crate::to_emb_addr(core::net::IpAddr::V4(multicast_addr))
-                    .ok_or(UdpError::UnsupportedProto)?,
-            )?;
+            self.stack
+                .leave_multicast_group(crate::convert::to_emb_addr(core::net::IpAddr::V4(
+                    multicast_addr,
+                ))?)?;
         }
 
         #[cfg(not(feature = "multicast"))]
@@ -163,10 +646,10 @@ impl<'d> UdpSocket<'d> {
     ) -> Result<(), UdpError> {
         #[cfg(feature = "multicast")]
         {
-            self.stack.join_multicast_group(
-                crate::to_emb_addr(core::net::IpAddr::V6(multicast_addr))
-                    .ok_or(UdpError::UnsupportedProto)?,
-            )?;
+            self.stack
+                .join_multicast_group(crate::convert::to_emb_addr(core::net::IpAddr::V6(
+                    multicast_addr,
+                ))?)?;
         }
 
         #[cfg(not(feature = "multicast"))]
@@ -184,10 +667,10 @@ impl<'d> UdpSocket<'d> {
     ) -> Result<(), UdpError> {
         #[cfg(feature = "multicast")]
         {
-            self.stack.leave_multicast_group(
-                crate::to_emb_addr(core::net::IpAddr::V6(multicast_addr))
-                    .ok_or(UdpError::UnsupportedProto)?,
-            )?;
+            self.stack
+                .leave_multicast_group(crate::convert::to_emb_addr(core::net::IpAddr::V6(
+                    multicast_addr,
+                ))?)?;
         }
 
         #[cfg(not(feature = "multicast"))]
@@ -199,89 +682,223 @@ impl<'d> UdpSocket<'d> {
     }
 }
 
-impl Drop for UdpSocket<'_> {
+impl<P> Debug for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let endpoint = self.socket.endpoint();
+
+        f.debug_struct("UdpSocket")
+            .field("addr", &endpoint.addr.map(to_net_addr))
+            .field("port", &endpoint.port)
+            .finish()
+    }
+}
+
+impl<P> Drop for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     fn drop(&mut self) {
         self.socket.close();
-        unsafe {
-            self.stack_buffers.free(self.buffer_token);
+
+        if let Some(port) = self.ephemeral_port {
+            port.set(0);
         }
     }
 }
 
-impl ErrorType for UdpSocket<'_> {
+impl<P> ErrorType for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     type Error = UdpError;
 }
 
-impl UdpReceive for UdpSocket<'_> {
+impl<P> UdpReceive for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+        loop {
+            let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+
+            if !self.is_looped_back(remote_endpoint.endpoint) {
+                count_bytes(&self.bytes_received, len);
 
-        Ok((len, to_net_socket(remote_endpoint.endpoint)))
+                break Ok((len, to_net_socket(remote_endpoint.endpoint)));
+            }
+        }
     }
 }
 
-impl UdpSend for UdpSocket<'_> {
+impl<P> UdpSend for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
         self.socket
-            .send_to(
-                data,
-                to_emb_socket(remote).ok_or(UdpError::UnsupportedProto)?,
-            )
-            .await?;
+            .send_to(data, to_emb_socket(remote)?)
+            .await
+            .map_err(|source| UdpError::Send {
+                addr: remote,
+                source,
+            })?;
+
+        count_bytes(&self.bytes_sent, data.len());
 
         Ok(())
     }
 }
 
-impl ErrorType for &UdpSocket<'_> {
+impl<P> ErrorType for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     type Error = UdpError;
 }
 
-impl UdpReceive for &UdpSocket<'_> {
+impl<P> UdpReceive for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+        loop {
+            let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+
+            if !self.is_looped_back(remote_endpoint.endpoint) {
+                count_bytes(&self.bytes_received, len);
 
-        Ok((len, to_net_socket(remote_endpoint.endpoint)))
+                break Ok((len, to_net_socket(remote_endpoint.endpoint)));
+            }
+        }
     }
 }
 
-impl UdpSend for &UdpSocket<'_> {
+impl<P> UdpSend for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
         self.socket
-            .send_to(
-                data,
-                to_emb_socket(remote).ok_or(UdpError::UnsupportedProto)?,
-            )
-            .await?;
+            .send_to(data, to_emb_socket(remote)?)
+            .await
+            .map_err(|source| UdpError::Send {
+                addr: remote,
+                source,
+            })?;
+
+        count_bytes(&self.bytes_sent, data.len());
 
         Ok(())
     }
 }
 
-impl Readable for &UdpSocket<'_> {
+impl<P> Readable for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.socket.wait_recv_ready().await;
         Ok(())
     }
 }
 
-impl UdpSplit for UdpSocket<'_> {
+/// A type that represents the receive half of a split UDP socket.
+/// Implements the `UdpReceive` and `Readable` traits from `edge-nal`.
+///
+/// The receive half is a distinct type from the send half, so that only one task can receive
+/// from a split socket (embassy-net UDP sockets can register only one receive waker at a time).
+pub struct UdpSocketReceive<'a>(
+    &'a embassy_net::udp::UdpSocket<'a>,
+    Option<Stack<'a>>,
+    &'a Cell<u64>,
+);
+
+impl ErrorType for UdpSocketReceive<'_> {
+    type Error = UdpError;
+}
+
+impl UdpReceive for UdpSocketReceive<'_> {
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        loop {
+            let (len, remote_endpoint) = self.0.recv_from(buffer).await?;
+
+            let looped_back = self
+                .1
+                .is_some_and(|stack| is_own(stack, self.0, remote_endpoint.endpoint));
+
+            if !looped_back {
+                count_bytes(self.2, len);
+
+                break Ok((len, to_net_socket(remote_endpoint.endpoint)));
+            }
+        }
+    }
+}
+
+impl Readable for UdpSocketReceive<'_> {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_recv_ready().await;
+        Ok(())
+    }
+}
+
+/// A type that represents the send half of a split UDP socket.
+/// Implements the `UdpSend` trait from `edge-nal`.
+pub struct UdpSocketSend<'a>(&'a embassy_net::udp::UdpSocket<'a>, &'a Cell<u64>);
+
+impl ErrorType for UdpSocketSend<'_> {
+    type Error = UdpError;
+}
+
+impl UdpSend for UdpSocketSend<'_> {
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        self.0
+            .send_to(data, to_emb_socket(remote)?)
+            .await
+            .map_err(|source| UdpError::Send {
+                addr: remote,
+                source,
+            })?;
+
+        count_bytes(self.1, data.len());
+
+        Ok(())
+    }
+}
+
+impl<P> UdpSplit for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     type Receive<'a>
-        = &'a Self
+        = UdpSocketReceive<'a>
     where
         Self: 'a;
 
     type Send<'a>
-        = &'a Self
+        = UdpSocketSend<'a>
     where
         Self: 'a;
 
+    /// Split the socket into a receive half and a send half.
+    ///
+    /// Each half is meant to be used by a single task; the halves are distinct types,
+    /// so that two receivers (or two senders) cannot be obtained from a single split.
     fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
-        (&*self, &*self)
+        (
+            UdpSocketReceive(&self.socket, self.loop_filter(), &self.bytes_received),
+            UdpSocketSend(&self.socket, &self.bytes_sent),
+        )
     }
 }
 
-impl<'d> UdpSplitMulticast for UdpSocket<'d> {
+impl<P> UdpSplitMulticast for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     type MulticastV4<'a>
         = &'a Self
     where
@@ -300,11 +917,21 @@ impl<'d> UdpSplitMulticast for UdpSocket<'d> {
         Self::MulticastV4<'_>,
         Self::MulticastV6<'_>,
     ) {
-        (&*self, &*self, &*self, &*self)
+        let this = &*self;
+
+        (
+            UdpSocketReceive(&this.socket, this.loop_filter(), &this.bytes_received),
+            UdpSocketSend(&this.socket, &this.bytes_sent),
+            this,
+            this,
+        )
     }
 }
 
-impl MulticastV4 for UdpSocket<'_> {
+impl<P> MulticastV4 for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn join_v4(
         &mut self,
         multicast_addr: Ipv4Addr,
@@ -322,7 +949,10 @@ impl MulticastV4 for UdpSocket<'_> {
     }
 }
 
-impl MulticastV4 for &UdpSocket<'_> {
+impl<P> MulticastV4 for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn join_v4(
         &mut self,
         multicast_addr: Ipv4Addr,
@@ -340,7 +970,10 @@ impl MulticastV4 for &UdpSocket<'_> {
     }
 }
 
-impl MulticastV6 for UdpSocket<'_> {
+impl<P> MulticastV6 for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn join_v6(
         &mut self,
         multicast_addr: Ipv6Addr,
@@ -358,7 +991,10 @@ impl MulticastV6 for UdpSocket<'_> {
     }
 }
 
-impl MulticastV6 for &UdpSocket<'_> {
+impl<P> MulticastV6 for &UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn join_v6(
         &mut self,
         multicast_addr: Ipv6Addr,
@@ -376,13 +1012,34 @@ impl MulticastV6 for &UdpSocket<'_> {
     }
 }
 
-impl Readable for UdpSocket<'_> {
+impl<P> Readable for UdpSocket<'_, P>
+where
+    P: DynPool<UdpSocketBuffers> + ?Sized,
+{
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.socket.wait_recv_ready().await;
         Ok(())
     }
 }
 
+/// Return `true` if the provided source is the local port of the socket on one of the addresses of the host
+fn is_own(stack: Stack<'_>, socket: &embassy_net::udp::UdpSocket<'_>, remote: IpEndpoint) -> bool {
+    if remote.port != socket.endpoint().port {
+        return false;
+    }
+
+    match remote.addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => stack
+            .config_v4()
+            .is_some_and(|config| config.address.address() == addr),
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => stack
+            .config_v6()
+            .is_some_and(|config| config.address.address() == addr),
+    }
+}
+
 /// A shared error type that is used by the UDP factory trait implementation as well as the UDP socket
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -390,17 +1047,32 @@ pub enum UdpError {
     /// An error occurred while receiving data.
     Recv(RecvError),
     /// An error occurred while sending data.
-    Send(SendError),
+    Send {
+        /// The remote address the data was sent to.
+        addr: SocketAddr,
+        /// The underlying send error.
+        source: SendError,
+    },
     /// An error occurred while binding the socket.
-    Bind(BindError),
+    Bind {
+        /// The local address the socket was bound to.
+        addr: SocketAddr,
+        /// The underlying bind error.
+        source: BindError,
+    },
     /// The table of joined multicast groups is already full.
     MulticastGroupTableFull,
     /// Cannot join/leave the given multicast group.
     MulticastUnaddressable,
     /// No more UDP socket buffers are available.
     NoBuffers,
-    /// The provided protocol is not supported.
+    /// All the ports of the range set with `Udp::with_ephemeral_ports` are held by other sockets.
+    NoFreePort,
+    /// The requested operation is not supported (i.e. multicast without the `multicast` feature of this crate).
     UnsupportedProto,
+    /// The address family (IPv4 or IPv6) of the provided address is not enabled
+    /// with the `proto-ipv4` / `proto-ipv6` features of this crate.
+    ProtoDisabled,
 }
 
 impl From<RecvError> for UdpError {
@@ -409,15 +1081,9 @@ impl From<RecvError> for UdpError {
     }
 }
 
-impl From<SendError> for UdpError {
-    fn from(e: SendError) -> Self {
-        UdpError::Send(e)
-    }
-}
-
-impl From<BindError> for UdpError {
-    fn from(e: BindError) -> Self {
-        UdpError::Bind(e)
+impl From<AddrFamilyDisabled> for UdpError {
+    fn from(_: AddrFamilyDisabled) -> Self {
+        UdpError::ProtoDisabled
     }
 }
 
@@ -425,8 +1091,12 @@ impl Display for UdpError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             UdpError::Recv(e) => write!(f, "UDP receive error: {:?}", e),
-            UdpError::Send(e) => write!(f, "UDP send error: {:?}", e),
-            UdpError::Bind(e) => write!(f, "UDP bind error: {:?}", e),
+            UdpError::Send { addr, source } => {
+                write!(f, "UDP send error to {}: {:?}", addr, source)
+            }
+            UdpError::Bind { addr, source } => {
+                write!(f, "UDP bind error on {}: {:?}", addr, source)
+            }
             UdpError::MulticastGroupTableFull => {
                 write!(f, "UDP multicast group table is full")
             }
@@ -434,7 +1104,12 @@ impl Display for UdpError {
                 write!(f, "UDP multicast address is unaddressable")
             }
             UdpError::NoBuffers => write!(f, "No UDP socket buffers available"),
+            UdpError::NoFreePort => write!(f, "No free UDP port in the ephemeral port range"),
             UdpError::UnsupportedProto => write!(f, "Unsupported protocol"),
+            UdpError::ProtoDisabled => write!(
+                f,
+                "UDP address family not enabled (see the `proto-ipv4` / `proto-ipv6` features)"
+            ),
         }
     }
 }
@@ -458,12 +1133,14 @@ impl embedded_io_async::Error for UdpError {
     fn kind(&self) -> ErrorKind {
         match self {
             UdpError::Recv(_) => ErrorKind::Other,
-            UdpError::Send(_) => ErrorKind::Other,
-            UdpError::Bind(_) => ErrorKind::Other,
+            UdpError::Send { .. } => ErrorKind::Other,
+            UdpError::Bind { .. } => ErrorKind::Other,
             UdpError::MulticastGroupTableFull => ErrorKind::Other,
             UdpError::MulticastUnaddressable => ErrorKind::Other,
             UdpError::NoBuffers => ErrorKind::OutOfMemory,
+            UdpError::NoFreePort => ErrorKind::AddrInUse,
             UdpError::UnsupportedProto => ErrorKind::InvalidInput,
+            UdpError::ProtoDisabled => ErrorKind::Unsupported,
         }
     }
 }
@@ -472,8 +1149,6 @@ impl embedded_io_async::Error for UdpError {
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct UdpSocketBuffers {
-    /// A token used to identify the buffer in the pool.
-    token: NonNull<u8>,
     /// The metadata buffer for receiving packets.
     md_rx_buf: NonNull<PacketMetadata>,
     /// The buffer for receiving packets.
@@ -496,20 +1171,35 @@ pub type UdpBuffers<
     const TX_SZ: usize = 1472,
     const RX_SZ: usize = 1472,
     const M: usize = 2,
-> = Pool<
-    (
-        [u8; TX_SZ],
-        [u8; RX_SZ],
-        [PacketMetadata; M],
-        [PacketMetadata; M],
-    ),
-    N,
->;
-
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
-    SealedDynPool<UdpSocketBuffers> for UdpBuffers<N, TX_SZ, RX_SZ, M>
+> = Pool<UdpSlot<TX_SZ, RX_SZ, M>, N>;
+
+/// The buffers of one UDP socket in a pool of UDP socket buffers, i.e. the type of the objects
+/// of the memory passed to `Pool::new_external` for a pool of UDP socket buffers.
+pub type UdpSlot<const TX_SZ: usize, const RX_SZ: usize, const M: usize> = (
+    [u8; TX_SZ],
+    [u8; RX_SZ],
+    [PacketMetadata; M],
+    [PacketMetadata; M],
+);
+
+/// A buffer to receive a packet into with `UdpSocket::receive_batch`, paired with a slot
+/// which receives the length and the remote address of the packet.
+pub type UdpBatchPacket<'a> = (&'a mut [u8], Option<(usize, SocketAddr)>);
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize, S>
+    SealedDynPool<UdpSocketBuffers> for Pool<UdpSlot<TX_SZ, RX_SZ, M>, N, S>
+where
+    S: PoolStorage<UdpSlot<TX_SZ, RX_SZ, M>>,
 {
-    fn alloc(&self) -> Option<UdpSocketBuffers> {
+    fn alloc(&self) -> Option<(NonNull<u8>, UdpSocketBuffers)> {
+        // A socket without metadata slots can never receive or send a packet
+        const {
+            core::assert!(
+                M > 0,
+                "UdpBuffers need at least one packet metadata slot (M > 0)"
+            )
+        };
+
         let mut socket_buffers = Pool::alloc(self)?;
 
         let rx_buf = unsafe { &mut socket_buffers.as_mut().1 };
@@ -517,34 +1207,75 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
         let md_rx_buf = unsafe { &mut socket_buffers.as_mut().3 };
         let md_tx_buf = unsafe { &mut socket_buffers.as_mut().2 };
 
-        Some(UdpSocketBuffers {
-            token: socket_buffers.cast::<u8>(),
-            md_rx_buf: unwrap!(NonNull::new(md_rx_buf.as_mut_ptr())),
-            rx_buf: unwrap!(NonNull::new(rx_buf.as_mut_ptr())),
-            md_tx_buf: unwrap!(NonNull::new(md_tx_buf.as_mut_ptr())),
-            tx_buf: unwrap!(NonNull::new(tx_buf.as_mut_ptr())),
-            md_buf_len: md_rx_buf.len(),
-            rx_buf_len: rx_buf.len(),
-            tx_buf_len: tx_buf.len(),
-        })
+        Some((
+            socket_buffers.cast::<u8>(),
+            UdpSocketBuffers {
+                md_rx_buf: unwrap!(NonNull::new(md_rx_buf.as_mut_ptr())),
+                rx_buf: unwrap!(NonNull::new(rx_buf.as_mut_ptr())),
+                md_tx_buf: unwrap!(NonNull::new(md_tx_buf.as_mut_ptr())),
+                tx_buf: unwrap!(NonNull::new(tx_buf.as_mut_ptr())),
+                md_buf_len: md_rx_buf.len(),
+                rx_buf_len: rx_buf.len(),
+                tx_buf_len: tx_buf.len(),
+            },
+        ))
     }
 
     unsafe fn free(&self, buffer_token: NonNull<u8>) {
         unsafe {
-            Pool::free(
-                self,
-                buffer_token.cast::<(
-                    [u8; TX_SZ],
-                    [u8; RX_SZ],
-                    [PacketMetadata; M],
-                    [PacketMetadata; M],
-                )>(),
-            );
+            Pool::free(self, buffer_token.cast::<UdpSlot<TX_SZ, RX_SZ, M>>());
         }
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
-    DynPool<UdpSocketBuffers> for UdpBuffers<N, TX_SZ, RX_SZ, M>
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize, S>
+    DynPool<UdpSocketBuffers> for Pool<UdpSlot<TX_SZ, RX_SZ, M>, N, S>
+where
+    S: PoolStorage<UdpSlot<TX_SZ, RX_SZ, M>>,
 {
 }
+
+// A host test binary has no `defmt` logger to link against
+#[cfg(all(test, not(feature = "defmt")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ephemeral_ports() {
+        let ports = UdpEphemeralPorts::<4>::new(5000, 5002);
+        let view = EphemeralPorts {
+            first: ports.first,
+            last: ports.last,
+            in_use: &ports.in_use,
+        };
+
+        // The same seed yields distinct ports, as the held ones are skipped
+        let a = unwrap!(view.acquire(1));
+        let b = unwrap!(view.acquire(1));
+        let c = unwrap!(view.acquire(1));
+
+        assert_eq!((a.get(), b.get(), c.get()), (5001, 5002, 5000));
+
+        // All the ports of the range are held
+        assert!(view.acquire(1).is_none());
+
+        // A released port is picked again
+        b.set(0);
+
+        assert_eq!(unwrap!(view.acquire(0)).get(), 5002);
+
+        // All the slots are held
+        let ports = UdpEphemeralPorts::<1>::new(1, u16::MAX);
+        let view = EphemeralPorts {
+            first: ports.first,
+            last: ports.last,
+            in_use: &ports.in_use,
+        };
+
+        assert_eq!(
+            unwrap!(view.acquire(u64::MAX)).get(),
+            (u64::MAX % 65535) as u16 + 1
+        );
+        assert!(view.acquire(0).is_none());
+    }
+}
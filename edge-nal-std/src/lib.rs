@@ -140,6 +140,31 @@ impl TcpSocket {
     pub fn release(self) -> Async<TcpStream> {
         self.0
     }
+
+    /// Set the DSCP (Differentiated Services Code Point) of the outgoing packets, for QoS marking.
+    ///
+    /// See `UdpSocket::set_dscp` for details.
+    ///
+    /// # Arguments
+    /// - `dscp`: The DSCP value, in the range `0..=63`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dscp(&self, dscp: u8) -> Result<(), io::Error> {
+        use std::os::fd::AsRawFd;
+
+        dscp::set(
+            self.0.as_raw_fd(),
+            self.0.as_ref().local_addr()?.is_ipv6(),
+            dscp,
+        )
+    }
+
+    /// Get the DSCP (Differentiated Services Code Point) of the outgoing packets.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn dscp(&self) -> Result<u8, io::Error> {
+        use std::os::fd::AsRawFd;
+
+        dscp::get(self.0.as_raw_fd(), self.0.as_ref().local_addr()?.is_ipv6())
+    }
 }
 
 impl Deref for TcpSocket {
@@ -293,6 +318,36 @@ impl UdpSocket {
         self.0
     }
 
+    /// Set the DSCP (Differentiated Services Code Point) of the outgoing packets, for QoS marking.
+    ///
+    /// The DSCP is the upper 6 bits of the IPv4 ToS byte (or of the IPv6 Traffic Class byte), so
+    /// i.e. `46` (Expedited Forwarding) results in a ToS byte of `0xb8`. The lower 2 (ECN) bits are left
+    /// to the kernel. Common values are `0` (Best Effort), `8..=38` (Class Selector and Assured Forwarding)
+    /// and `46` (Expedited Forwarding, for voice).
+    ///
+    /// Note that routers only honor the marking if they are configured for DiffServ.
+    ///
+    /// # Arguments
+    /// - `dscp`: The DSCP value, in the range `0..=63`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dscp(&self, dscp: u8) -> Result<(), io::Error> {
+        use std::os::fd::AsRawFd;
+
+        dscp::set(
+            self.0.as_raw_fd(),
+            self.0.as_ref().local_addr()?.is_ipv6(),
+            dscp,
+        )
+    }
+
+    /// Get the DSCP (Differentiated Services Code Point) of the outgoing packets.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn dscp(&self) -> Result<u8, io::Error> {
+        use std::os::fd::AsRawFd;
+
+        dscp::get(self.0.as_raw_fd(), self.0.as_ref().local_addr()?.is_ipv6())
+    }
+
     /// Join a multicast group for IPv4.
     ///
     /// # Arguments
@@ -740,22 +795,12 @@ mod raw {
         pub fn release(self) -> (Async<std::net::UdpSocket>, u32) {
             (self.0, self.1)
         }
-    }
-
-    impl Deref for RawSocket {
-        type Target = Async<std::net::UdpSocket>;
-
-        fn deref(&self) -> &Self::Target {
-            &self.0
-        }
-    }
-
-    impl ErrorType for &RawSocket {
-        type Error = io::Error;
-    }
 
-    impl RawReceive for &RawSocket {
-        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+        /// Receive a datagram into the provided buffer, like `RawReceive::receive`,
+        /// additionally returning the EtherType of the frame it was carried in.
+        ///
+        /// NOTE: Sockets created with `Interface::bind` only receive IPv4 frames (EtherType `0x0800`).
+        pub async fn receive_meta(&self, buffer: &mut [u8]) -> io::Result<(usize, MacAddr, u16)> {
             let fut = pin!(self.0.read_with(|io| {
                 let mut storage: sys::sockaddr_storage = unsafe { core::mem::zeroed() };
                 let mut addrlen = core::mem::size_of_val(&storage) as sys::socklen_t;
@@ -776,13 +821,33 @@ mod raw {
                 let mut mac = [0; 6];
                 mac.copy_from_slice(&sockaddr.sll_addr[..6]);
 
-                Ok((ret as usize, mac))
+                Ok((ret as usize, mac, u16::from_be(sockaddr.sll_protocol)))
             }));
 
             fut.await
         }
     }
 
+    impl Deref for RawSocket {
+        type Target = Async<std::net::UdpSocket>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl ErrorType for &RawSocket {
+        type Error = io::Error;
+    }
+
+    impl RawReceive for &RawSocket {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+            let (len, mac, _) = self.receive_meta(buffer).await?;
+
+            Ok((len, mac))
+        }
+    }
+
     impl RawSend for &RawSocket {
         async fn send(&mut self, mac: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
             let mut sockaddr = sys::sockaddr_ll {
@@ -890,6 +955,60 @@ mod raw {
     }
 }
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod dscp {
+    use std::io::{self, ErrorKind};
+    use std::os::fd::RawFd;
+
+    use crate::sys;
+    use crate::syscall_los;
+
+    /// The largest DSCP value, as the DSCP is 6 bits long
+    const MAX_DSCP: u8 = 63;
+
+    pub fn set(fd: RawFd, ipv6: bool, dscp: u8) -> Result<(), io::Error> {
+        if dscp > MAX_DSCP {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid DSCP"));
+        }
+
+        let (level, option) = level_and_option(ipv6);
+        let tos: sys::c_int = (dscp << 2) as _;
+
+        syscall_los!(unsafe {
+            sys::setsockopt(
+                fd,
+                level,
+                option,
+                &tos as *const _ as *const _,
+                core::mem::size_of::<sys::c_int>() as _,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    pub fn get(fd: RawFd, ipv6: bool) -> Result<u8, io::Error> {
+        let (level, option) = level_and_option(ipv6);
+
+        let mut tos: sys::c_int = 0;
+        let mut len = core::mem::size_of::<sys::c_int>() as sys::socklen_t;
+
+        syscall_los!(unsafe {
+            sys::getsockopt(fd, level, option, &mut tos as *mut _ as *mut _, &mut len)
+        })?;
+
+        Ok((tos >> 2) as u8 & MAX_DSCP)
+    }
+
+    fn level_and_option(ipv6: bool) -> (sys::c_int, sys::c_int) {
+        if ipv6 {
+            (sys::IPPROTO_IPV6, sys::IPV6_TCLASS)
+        } else {
+            (sys::IPPROTO_IP, sys::IP_TOS)
+        }
+    }
+}
+
 #[cfg(any(target_os = "linux", target_os = "android", target_os = "espidf"))]
 mod sys {
     pub use libc::*;
@@ -5,22 +5,26 @@
 
 pub use multicast::*;
 pub use noop::*;
+pub use pool::*;
 pub use raw::*;
 pub use readable::*;
 pub use tcp::*;
 pub use timeout::*;
 pub use udp::*;
+pub use writable::*;
 
 pub use stack::*;
 
 mod multicast;
 mod noop;
+mod pool;
 mod raw;
 mod readable;
 mod stack;
 mod tcp;
 mod timeout;
 mod udp;
+mod writable;
 
 pub mod io {
     pub use embedded_io_async::*;
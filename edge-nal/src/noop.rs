@@ -8,6 +8,7 @@ use embedded_io_async::{ErrorType, Read, Write};
 use crate::{
     AddrType, Close, Dns, MulticastV4, MulticastV6, Readable, TcpAccept, TcpBind, TcpConnect,
     TcpShutdown, TcpSplit, UdpBind, UdpConnect, UdpReceive, UdpSend, UdpSplit, UdpSplitMulticast,
+    Writable,
 };
 
 /// A type that implements all `edge-nal` traits but does not support any operation
@@ -113,6 +114,12 @@ impl Readable for NoopNet {
     }
 }
 
+impl Writable for NoopNet {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        panic!("Writable not supported")
+    }
+}
+
 impl UdpSend for NoopNet {
     async fn send(&mut self, _remote: SocketAddr, _data: &[u8]) -> Result<(), Self::Error> {
         panic!("UDP send not supported")
@@ -0,0 +1,31 @@
+use core::ops::DerefMut;
+
+/// A trait for pools of fixed-size byte buffers.
+///
+/// Allows protocol implementations (i.e. the HTTP server) to draw their scratch buffers from a pool
+/// shared with other users, so that the memory budget of all users is governed by one pool.
+pub trait BufferPool {
+    /// A buffer allocated from the pool, which is returned to the pool when dropped.
+    type Buffer<'a>: DerefMut<Target = [u8]>
+    where
+        Self: 'a;
+
+    /// Allocate a buffer from the pool.
+    ///
+    /// Returns `None` if the pool is exhausted.
+    fn alloc(&self) -> Option<Self::Buffer<'_>>;
+}
+
+impl<T> BufferPool for &T
+where
+    T: BufferPool,
+{
+    type Buffer<'a>
+        = T::Buffer<'a>
+    where
+        Self: 'a;
+
+    fn alloc(&self) -> Option<Self::Buffer<'_>> {
+        (**self).alloc()
+    }
+}
@@ -1,3 +1,5 @@
+use embassy_futures::select::select_array;
+
 use embedded_io_async::ErrorType;
 
 pub trait Readable: ErrorType {
@@ -12,3 +14,71 @@ where
         (**self).readable().await
     }
 }
+
+/// Wait until any of the provided sockets becomes readable.
+///
+/// Returns the index of the readable socket, along with the result of its `Readable::readable` call
+/// (i.e. an error means that this socket - rather than the others - failed).
+/// When several sockets are readable at the same time, the one with the lowest index is returned,
+/// so a server should rotate the sockets it passes (or process all of the readable ones) to avoid
+/// starving the sockets with higher indices.
+///
+/// As `Readable` is not object-safe, the sockets need to be of the same type; sockets of different
+/// types can be combined in an enum implementing `Readable`.
+///
+/// Parameters:
+/// - `sockets`: The sockets to wait on
+pub async fn select_readable<R, const N: usize>(
+    sockets: &mut [R; N],
+) -> (usize, Result<(), R::Error>)
+where
+    R: Readable,
+{
+    let (result, index) = select_array(sockets.each_mut().map(Readable::readable)).await;
+
+    (index, result)
+}
+
+/// Wait until any of the provided sockets becomes readable, for a set of sockets which varies at runtime
+/// (i.e. the currently connected clients of a server).
+///
+/// Same as `select_readable`, except that the sockets are passed as a slice of up to `N` sockets.
+/// The futures waiting on the sockets cannot be provided by the caller, as their type is not nameable,
+/// so they are stored in the returned future, which is therefore sized for `N` sockets.
+///
+/// With no sockets at all, the returned future never completes, so that it can be selected together
+/// with i.e. the acceptor of new connections.
+///
+/// # Panics
+/// If more than `N` sockets are provided.
+///
+/// Parameters:
+/// - `sockets`: The sockets to wait on
+pub async fn select_readable_slice<R, const N: usize>(
+    sockets: &mut [R],
+) -> (usize, Result<(), R::Error>)
+where
+    R: Readable,
+{
+    core::assert!(
+        sockets.len() <= N,
+        "More sockets than the capacity N of select_readable_slice"
+    );
+
+    let mut sockets = sockets.iter_mut();
+
+    let futures: [_; N] = core::array::from_fn(|_| {
+        let socket = sockets.next();
+
+        async move {
+            match socket {
+                Some(socket) => socket.readable().await,
+                None => core::future::pending().await,
+            }
+        }
+    });
+
+    let (result, index) = select_array(futures).await;
+
+    (index, result)
+}
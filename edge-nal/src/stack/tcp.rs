@@ -90,6 +90,17 @@ pub trait TcpAccept {
     /// Accepts an incoming connection
     /// Returns the socket address of the remote peer, as well as the accepted socket.
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error>;
+
+    /// Return `true` if the provided error - as returned by `accept` - only means that the acceptor
+    /// is temporarily at capacity (i.e. out of socket buffers), so that the caller should back off
+    /// and retry later, rather than treat it as a hard failure.
+    ///
+    /// Allows servers to shed load portably. The default implementation treats all errors as hard failures.
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        let _ = error;
+
+        false
+    }
 }
 
 impl<T> TcpConnect for &T
@@ -170,6 +181,10 @@ where
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
         (*self).accept().await
     }
+
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        (*self).is_backpressure(error)
+    }
 }
 
 impl<T> TcpAccept for &mut T
@@ -186,4 +201,8 @@ where
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
         (**self).accept().await
     }
+
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        (**self).is_backpressure(error)
+    }
 }
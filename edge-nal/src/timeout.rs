@@ -16,7 +16,7 @@ use core::{
 use embassy_time::Duration;
 use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
 
-use crate::{Readable, TcpAccept, TcpConnect, TcpShutdown, TcpSplit};
+use crate::{Readable, TcpAccept, TcpConnect, TcpShutdown, TcpSplit, Writable};
 
 /// Error type for the `with_timeout` function and `WithTimeout` struct.
 #[derive(Debug)]
@@ -177,6 +177,15 @@ where
     }
 }
 
+impl<T> Writable for WithTimeout<T>
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.writable()).await
+    }
+}
+
 impl<T> TcpSplit for WithTimeout<T>
 where
     T: TcpSplit,
@@ -226,6 +235,13 @@ where
 
         Ok((addr, WithTimeout::new(self.1, socket)))
     }
+
+    fn is_backpressure(&self, error: &Self::Error) -> bool {
+        match error {
+            WithTimeoutError::Error(error) => self.0.is_backpressure(error),
+            WithTimeoutError::Timeout => false,
+        }
+    }
 }
 
 fn map_result<T, E>(
@@ -0,0 +1,38 @@
+use embassy_futures::select::select_array;
+
+use embedded_io_async::ErrorType;
+
+/// A trait for waiting until a socket can accept more data for sending.
+///
+/// This is the writing counterpart of `Readable`, useful for applying backpressure,
+/// i.e. when a writer task should park until there is free space in the transmit buffer.
+pub trait Writable: ErrorType {
+    async fn writable(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T> Writable for &mut T
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        (**self).writable().await
+    }
+}
+
+/// Wait until any of the provided sockets becomes writable.
+///
+/// Returns the index of the writable socket, along with the result of its `Writable::writable` call.
+/// See `select_readable` for the details.
+///
+/// Parameters:
+/// - `sockets`: The sockets to wait on
+pub async fn select_writable<W, const N: usize>(
+    sockets: &mut [W; N],
+) -> (usize, Result<(), W::Error>)
+where
+    W: Writable,
+{
+    let (result, index) = select_array(sockets.each_mut().map(Writable::writable)).await;
+
+    (index, result)
+}
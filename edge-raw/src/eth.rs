@@ -0,0 +1,148 @@
+use super::bytes::{BytesIn, BytesOut};
+
+use super::Error;
+
+/// The broadcast MAC address
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+#[allow(clippy::type_complexity)]
+pub fn decode(
+    frame: &[u8],
+    filter_ether_type: Option<u16>,
+) -> Result<Option<([u8; 6], [u8; 6], &[u8])>, Error> {
+    let data = EthFrameHeader::decode_with_payload(frame, filter_ether_type)?
+        .map(|(hdr, payload)| (hdr.src, hdr.dst, payload));
+
+    Ok(data)
+}
+
+pub fn encode<F>(
+    buf: &mut [u8],
+    src: [u8; 6],
+    dst: [u8; 6],
+    ether_type: u16,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    let hdr = EthFrameHeader::new(src, dst, ether_type);
+
+    hdr.encode_with_payload(buf, encoder)
+}
+
+/// Represents a parsed Ethernet II frame header
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EthFrameHeader {
+    /// Destination MAC address
+    pub dst: [u8; 6],
+    /// Source MAC address
+    pub src: [u8; 6],
+    /// EtherType of the payload
+    pub ether_type: u16,
+}
+
+impl EthFrameHeader {
+    pub const SIZE: usize = 14;
+    /// The minimum size of a frame, excluding the frame check sequence
+    pub const MIN_FRAME_SIZE: usize = 60;
+
+    pub const ETHER_TYPE_IPV4: u16 = 0x0800;
+
+    /// Create a new header instance
+    pub fn new(src: [u8; 6], dst: [u8; 6], ether_type: u16) -> Self {
+        Self {
+            dst,
+            src,
+            ether_type,
+        }
+    }
+
+    /// Decodes the header from a byte slice
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        Ok(Self {
+            dst: bytes.arr()?,
+            src: bytes.arr()?,
+            ether_type: u16::from_be_bytes(bytes.arr()?),
+        })
+    }
+
+    /// Encodes the header into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .push(&self.dst)?
+            .push(&self.src)?
+            .push(&u16::to_be_bytes(self.ether_type))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+
+    /// Encodes the header and the provided payload into the provided buf slice
+    ///
+    /// Frames shorter than `MIN_FRAME_SIZE` are padded with zeroes, as the length of the payload
+    /// is carried by the payload itself (i.e. the total length of an IP packet).
+    pub fn encode_with_payload<'o, F>(
+        &self,
+        buf: &'o mut [u8],
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        if buf.len() < Self::SIZE {
+            Err(Error::BufferOverflow)?;
+        }
+
+        let (hdr_buf, payload_buf) = buf.split_at_mut(Self::SIZE);
+
+        let hdr_len = self.encode(hdr_buf)?.len();
+        assert_eq!(Self::SIZE, hdr_len);
+
+        let payload_len = encoder(payload_buf)?;
+
+        let mut len = Self::SIZE + payload_len;
+
+        if len < Self::MIN_FRAME_SIZE {
+            if buf.len() < Self::MIN_FRAME_SIZE {
+                Err(Error::BufferOverflow)?;
+            }
+
+            buf[len..Self::MIN_FRAME_SIZE].fill(0);
+            len = Self::MIN_FRAME_SIZE;
+        }
+
+        Ok(&buf[..len])
+    }
+
+    /// Decodes the provided frame into a header and a payload slice
+    ///
+    /// The payload slice might have trailing padding bytes (see `encode_with_payload`).
+    pub fn decode_with_payload(
+        frame: &[u8],
+        filter_ether_type: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        let hdr = Self::decode(frame)?;
+
+        trace!(
+            "Ethernet header decoded, src={:?}, dst={:?}, ether_type={}",
+            hdr.src,
+            hdr.dst,
+            hdr.ether_type
+        );
+
+        if let Some(filter_ether_type) = filter_ether_type {
+            if filter_ether_type != hdr.ether_type {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some((hdr, &frame[Self::SIZE..])))
+    }
+}
@@ -81,11 +81,18 @@ impl<E> core::error::Error for Error<E> where E: core::error::Error {}
 ///
 /// When sending to a broadcast IP address (255.255.255.255), the broadcast MAC address (ff:ff:ff:ff:ff:ff)
 /// is automatically used regardless of the stored MAC address, ensuring proper broadcast behavior.
+///
+/// # Ethernet Framing
+///
+/// By default, the raw socket is expected to exchange IP packets, with the Ethernet header being
+/// added and stripped by the raw socket itself (i.e. a Linux `AF_PACKET` socket of type `SOCK_DGRAM`).
+/// For raw sockets exchanging whole Ethernet frames instead, use `set_eth_framing()`.
 pub struct RawSocket2Udp<T, const N: usize = 1500> {
     socket: T,
     filter_local: Option<SocketAddrV4>,
     filter_remote: Option<SocketAddrV4>,
     remote_mac: MacAddr,
+    local_mac: Option<MacAddr>,
 }
 
 impl<T, const N: usize> RawSocket2Udp<T, N> {
@@ -100,6 +107,7 @@ impl<T, const N: usize> RawSocket2Udp<T, N> {
             filter_local,
             filter_remote,
             remote_mac,
+            local_mac: None,
         }
     }
 
@@ -110,6 +118,16 @@ impl<T, const N: usize> RawSocket2Udp<T, N> {
     pub fn set_remote_mac(&mut self, remote_mac: MacAddr) {
         self.remote_mac = remote_mac;
     }
+
+    /// Set whether the raw socket exchanges whole Ethernet frames rather than IP packets.
+    ///
+    /// With `Some(local_mac)`, the outgoing packets are framed in Ethernet frames with `local_mac`
+    /// as their source MAC address, and the incoming frames which are not IPv4 frames are skipped.
+    /// This is for raw sockets operating at the link layer, i.e. a Linux `AF_PACKET` socket of type
+    /// `SOCK_RAW`, or an Ethernet driver.
+    pub fn set_eth_framing(&mut self, local_mac: Option<MacAddr>) {
+        self.local_mac = local_mac;
+    }
 }
 
 impl<T, const N: usize> ErrorType for RawSocket2Udp<T, N>
@@ -124,8 +142,9 @@ where
     T: RawReceive,
 {
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, _local, remote, remote_mac) = udp_receive::<_, N>(
+        let (len, _local, remote, remote_mac) = receive::<_, N>(
             &mut self.socket,
+            self.local_mac.is_some(),
             self.filter_local,
             self.filter_remote,
             buffer,
@@ -165,8 +184,9 @@ where
             self.remote_mac
         };
 
-        udp_send::<_, N>(
+        send::<_, N>(
             &mut self.socket,
+            self.local_mac,
             SocketAddr::V4(
                 self.filter_local
                     .unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)),
@@ -195,21 +215,71 @@ where
     fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
         let (receive, send) = self.socket.split();
 
-        (
-            RawSocket2Udp::new(
-                receive,
-                self.filter_local,
-                self.filter_remote,
-                self.remote_mac,
-            ),
-            RawSocket2Udp::new(send, self.filter_local, self.filter_remote, self.remote_mac),
-        )
+        let mut receive = RawSocket2Udp::new(
+            receive,
+            self.filter_local,
+            self.filter_remote,
+            self.remote_mac,
+        );
+        receive.set_eth_framing(self.local_mac);
+
+        let mut send =
+            RawSocket2Udp::new(send, self.filter_local, self.filter_remote, self.remote_mac);
+        send.set_eth_framing(self.local_mac);
+
+        (receive, send)
     }
 }
 
 /// Sends a UDP packet to a remote peer identified by its MAC address
 pub async fn udp_send<T: RawSend, const N: usize>(
+    socket: T,
+    local: SocketAddr,
+    remote: SocketAddr,
+    remote_mac: MacAddr,
+    data: &[u8],
+) -> Result<(), Error<T::Error>> {
+    send::<_, N>(socket, None, local, remote, remote_mac, data).await
+}
+
+/// Sends a UDP packet to a remote peer identified by its MAC address, framed in an Ethernet frame
+/// with `local_mac` as its source MAC address
+pub async fn eth_udp_send<T: RawSend, const N: usize>(
+    socket: T,
+    local_mac: MacAddr,
+    local: SocketAddr,
+    remote: SocketAddr,
+    remote_mac: MacAddr,
+    data: &[u8],
+) -> Result<(), Error<T::Error>> {
+    send::<_, N>(socket, Some(local_mac), local, remote, remote_mac, data).await
+}
+
+/// Receives a UDP packet from a remote peer
+pub async fn udp_receive<T: RawReceive, const N: usize>(
+    socket: T,
+    filter_local: Option<SocketAddrV4>,
+    filter_remote: Option<SocketAddrV4>,
+    buffer: &mut [u8],
+) -> Result<(usize, SocketAddr, SocketAddr, MacAddr), Error<T::Error>> {
+    receive::<_, N>(socket, false, filter_local, filter_remote, buffer).await
+}
+
+/// Receives a UDP packet from a remote peer, out of an Ethernet frame
+///
+/// The returned MAC address is the source MAC address of the frame.
+pub async fn eth_udp_receive<T: RawReceive, const N: usize>(
+    socket: T,
+    filter_local: Option<SocketAddrV4>,
+    filter_remote: Option<SocketAddrV4>,
+    buffer: &mut [u8],
+) -> Result<(usize, SocketAddr, SocketAddr, MacAddr), Error<T::Error>> {
+    receive::<_, N>(socket, true, filter_local, filter_remote, buffer).await
+}
+
+async fn send<T: RawSend, const N: usize>(
     mut socket: T,
+    local_mac: Option<MacAddr>,
     local: SocketAddr,
     remote: SocketAddr,
     remote_mac: MacAddr,
@@ -222,7 +292,7 @@ pub async fn udp_send<T: RawSend, const N: usize>(
     let mut buf = MaybeUninit::<[u8; N]>::uninit();
     let buf = unsafe { buf.assume_init_mut() };
 
-    let data = raw::ip_udp_encode(buf, local, remote, |buf| {
+    let encoder = |buf: &mut [u8]| {
         if data.len() <= buf.len() {
             buf[..data.len()].copy_from_slice(data);
 
@@ -230,14 +300,20 @@ pub async fn udp_send<T: RawSend, const N: usize>(
         } else {
             Err(raw::Error::BufferOverflow)
         }
-    })?;
+    };
+
+    let data = if let Some(local_mac) = local_mac {
+        raw::eth_ip_udp_encode(buf, local_mac, remote_mac, local, remote, encoder)?
+    } else {
+        raw::ip_udp_encode(buf, local, remote, encoder)?
+    };
 
     socket.send(remote_mac, data).await.map_err(Error::Io)
 }
 
-/// Receives a UDP packet from a remote peer
-pub async fn udp_receive<T: RawReceive, const N: usize>(
+async fn receive<T: RawReceive, const N: usize>(
     mut socket: T,
+    eth: bool,
     filter_local: Option<SocketAddrV4>,
     filter_remote: Option<SocketAddrV4>,
     buffer: &mut [u8],
@@ -248,8 +324,16 @@ pub async fn udp_receive<T: RawReceive, const N: usize>(
     let (len, local, remote, remote_mac) = loop {
         let (len, remote_mac) = socket.receive(buf).await.map_err(Error::Io)?;
 
-        match raw::ip_udp_decode(&buf[..len], filter_remote, filter_local) {
-            Ok(Some((remote, local, data))) => {
+        let decoded = if eth {
+            raw::eth_ip_udp_decode(&buf[..len], filter_remote, filter_local)
+        } else {
+            raw::ip_udp_decode(&buf[..len], filter_remote, filter_local).map(|decoded| {
+                decoded.map(|(remote, local, data)| (remote_mac, remote, local, data))
+            })
+        };
+
+        match decoded {
+            Ok(Some((remote_mac, remote, local, data))) => {
                 if data.len() > buffer.len() {
                     Err(Error::RawError(raw::Error::BufferOverflow))?;
                 }
@@ -271,3 +355,99 @@ pub async fn udp_receive<T: RawReceive, const N: usize>(
         remote_mac,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+    use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use core::pin::pin;
+
+    use edge_nal::{MacAddr, RawReceive, RawSend, UdpReceive, UdpSend};
+
+    use embedded_io_async::ErrorType;
+
+    use crate::eth::{self, EthFrameHeader};
+
+    use super::RawSocket2Udp;
+
+    const LOCAL_MAC: MacAddr = [2, 0, 0, 0, 0, 1];
+    const REMOTE_MAC: MacAddr = [2, 0, 0, 0, 0, 2];
+
+    /// A raw socket receiving a non-IPv4 frame and then the last frame sent to it
+    struct Loopback {
+        frame: [u8; 128],
+        len: usize,
+        arp_received: bool,
+    }
+
+    impl ErrorType for Loopback {
+        type Error = Infallible;
+    }
+
+    impl RawSend for Loopback {
+        async fn send(&mut self, _addr: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+            self.frame[..data.len()].copy_from_slice(data);
+            self.len = data.len();
+
+            Ok(())
+        }
+    }
+
+    impl RawReceive for Loopback {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+            if !core::mem::replace(&mut self.arp_received, true) {
+                let mut arp = self.frame;
+                arp[12..14].copy_from_slice(&0x0806_u16.to_be_bytes());
+
+                buffer[..self.len].copy_from_slice(&arp[..self.len]);
+            } else {
+                buffer[..self.len].copy_from_slice(&self.frame[..self.len]);
+            }
+
+            Ok((self.len, eth::BROADCAST))
+        }
+    }
+
+    #[test]
+    fn test_eth_framing() {
+        let local = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 10), 68);
+        let remote = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 67);
+
+        let mut socket: RawSocket2Udp<_> = RawSocket2Udp::new(
+            Loopback {
+                frame: [0; 128],
+                len: 0,
+                arp_received: false,
+            },
+            Some(local),
+            None,
+            REMOTE_MAC,
+        );
+        socket.set_eth_framing(Some(LOCAL_MAC));
+
+        embassy_futures::block_on(async {
+            pin!(socket.send(SocketAddr::V4(remote), b"hello"))
+                .await
+                .unwrap();
+
+            let frame = &socket.socket.frame[..socket.socket.len];
+
+            assert_eq!(frame.len(), EthFrameHeader::MIN_FRAME_SIZE);
+            assert_eq!(&frame[..6], &REMOTE_MAC);
+            assert_eq!(&frame[6..12], &LOCAL_MAC);
+
+            // Receive the frame back, as if sent by the peer: the ARP frame is skipped,
+            // and the MAC address of the sender is the source MAC address of the frame
+            socket.set_remote_mac(eth::BROADCAST);
+            socket.filter_local = Some(remote);
+
+            let mut buf = [0; 16];
+            let (len, from) = pin!(socket.receive(&mut buf)).await.unwrap();
+
+            assert_eq!(&buf[..len], b"hello");
+            assert_eq!(from, SocketAddr::V4(local));
+            assert_eq!(socket.remote_mac, LOCAL_MAC);
+            assert!(socket.socket.arp_received);
+        });
+    }
+}
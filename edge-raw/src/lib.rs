@@ -6,6 +6,7 @@
 
 use core::net::{Ipv4Addr, SocketAddrV4};
 
+use self::eth::EthFrameHeader;
 use self::udp::UdpPacketHeader;
 
 // This mod MUST go first, so that the others see its macros.
@@ -15,6 +16,7 @@ pub(crate) mod fmt;
 pub mod io;
 
 pub mod bytes;
+pub mod eth;
 pub mod ip;
 pub mod udp;
 
@@ -108,6 +110,50 @@ where
     })
 }
 
+/// Decodes an Ethernet frame and its IP and UDP payload
+///
+/// Returns the MAC address of the sender along with the decoded addresses and UDP payload.
+/// Frames which are not IPv4 frames, or which do not match the filters, are skipped by returning `Ok(None)`.
+#[allow(clippy::type_complexity)]
+pub fn eth_ip_udp_decode(
+    frame: &[u8],
+    filter_src: Option<SocketAddrV4>,
+    filter_dst: Option<SocketAddrV4>,
+) -> Result<Option<([u8; 6], SocketAddrV4, SocketAddrV4, &[u8])>, Error> {
+    if let Some((src_mac, _dst_mac, packet)) =
+        eth::decode(frame, Some(EthFrameHeader::ETHER_TYPE_IPV4))?
+    {
+        Ok(ip_udp_decode(packet, filter_src, filter_dst)?
+            .map(|(src, dst, data)| (src_mac, src, dst, data)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Encodes an Ethernet frame and its IP and UDP payload
+///
+/// Use `eth::BROADCAST` as `dst_mac` for peers which are not addressable by their MAC address yet,
+/// i.e. DHCP servers before the lease is acquired.
+pub fn eth_ip_udp_encode<F>(
+    buf: &mut [u8],
+    src_mac: [u8; 6],
+    dst_mac: [u8; 6],
+    src: SocketAddrV4,
+    dst: SocketAddrV4,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    eth::encode(
+        buf,
+        src_mac,
+        dst_mac,
+        EthFrameHeader::ETHER_TYPE_IPV4,
+        |buf| Ok(ip_udp_encode(buf, src, dst, encoder)?.len()),
+    )
+}
+
 pub fn checksum_accumulate(bytes: &[u8], checksum_word: usize) -> u32 {
     let mut bytes = BytesIn::new(bytes);
 
@@ -134,3 +180,123 @@ pub fn checksum_finish(mut sum: u32) -> u16 {
 
     !sum as u16
 }
+
+#[cfg(test)]
+mod test {
+    use core::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::eth::{self, EthFrameHeader};
+    use super::*;
+
+    const SRC_MAC: [u8; 6] = [2, 0, 0, 0, 0, 1];
+    const SRC: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 10), 68);
+    const DST: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::BROADCAST, 67);
+
+    /// Encode a frame to the broadcast MAC address with the provided payload
+    fn encode<'a>(buf: &'a mut [u8], payload: &[u8]) -> &'a [u8] {
+        unwrap!(eth_ip_udp_encode(
+            buf,
+            SRC_MAC,
+            eth::BROADCAST,
+            SRC,
+            DST,
+            |buf| {
+                buf[..payload.len()].copy_from_slice(payload);
+
+                Ok(payload.len())
+            }
+        ))
+    }
+
+    #[test]
+    fn test_eth_ip_udp_roundtrip() {
+        let mut buf = [0xaa; 128];
+        let frame = encode(&mut buf, b"hi");
+
+        // Frames are padded with zeroes to the minimum frame size
+        assert_eq!(frame.len(), EthFrameHeader::MIN_FRAME_SIZE);
+        assert!(frame[EthFrameHeader::SIZE + 20 + 8 + 2..]
+            .iter()
+            .all(|b| *b == 0));
+
+        let hdr = unwrap!(EthFrameHeader::decode(frame));
+        assert_eq!(hdr.dst, eth::BROADCAST);
+        assert_eq!(hdr.src, SRC_MAC);
+        assert_eq!(hdr.ether_type, EthFrameHeader::ETHER_TYPE_IPV4);
+
+        // The padding is not taken as part of the payload
+        assert_eq!(
+            unwrap!(eth_ip_udp_decode(frame, None, None)),
+            Some((SRC_MAC, SRC, DST, &b"hi"[..]))
+        );
+        assert_eq!(
+            unwrap!(eth_ip_udp_decode(frame, Some(SRC), Some(DST))),
+            Some((SRC_MAC, SRC, DST, &b"hi"[..]))
+        );
+
+        // Frames longer than the minimum frame size are not padded
+        let payload = [0x55; 64];
+
+        let mut buf = [0; 128];
+        let frame = encode(&mut buf, &payload);
+
+        assert_eq!(frame.len(), EthFrameHeader::SIZE + 20 + 8 + payload.len());
+        assert_eq!(
+            unwrap!(eth_ip_udp_decode(frame, None, None)),
+            Some((SRC_MAC, SRC, DST, &payload[..]))
+        );
+
+        // A buffer too small for a minimum size frame
+        let mut buf = [0; EthFrameHeader::MIN_FRAME_SIZE - 1];
+        assert_eq!(
+            eth_ip_udp_encode(&mut buf, SRC_MAC, eth::BROADCAST, SRC, DST, |_| Ok(0)),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn test_eth_ip_udp_filtering() {
+        let mut buf = [0; 128];
+        let len = encode(&mut buf, b"hi").len();
+
+        // Frames of other ether types (here ARP) are skipped
+        buf[12..14].copy_from_slice(&0x0806_u16.to_be_bytes());
+        assert_eq!(unwrap!(eth_ip_udp_decode(&buf[..len], None, None)), None);
+
+        assert_eq!(
+            unwrap!(eth::decode(&buf[..len], Some(0x0806))).map(|(src, _, _)| src),
+            Some(SRC_MAC)
+        );
+        assert_eq!(
+            unwrap!(eth::decode(
+                &buf[..len],
+                Some(EthFrameHeader::ETHER_TYPE_IPV4)
+            )),
+            None
+        );
+
+        // Frames not matching the address filters are skipped
+        let mut buf = [0; 128];
+        let frame = encode(&mut buf, b"hi");
+
+        let other = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 11), 68);
+        assert_eq!(unwrap!(eth_ip_udp_decode(frame, Some(other), None)), None);
+        assert_eq!(unwrap!(eth_ip_udp_decode(frame, None, Some(other))), None);
+    }
+
+    #[test]
+    fn test_eth_ip_udp_truncated() {
+        let mut buf = [0; 128];
+        let frame = encode(&mut buf, &[0x55; 64]);
+
+        // A frame shorter than its header
+        assert_eq!(
+            eth_ip_udp_decode(&frame[..EthFrameHeader::SIZE - 1], None, None),
+            Err(Error::DataUnderflow)
+        );
+
+        // A frame shorter than the IP packet it carries
+        assert!(eth_ip_udp_decode(&frame[..EthFrameHeader::SIZE + 20], None, None).is_err());
+        assert!(eth_ip_udp_decode(&frame[..frame.len() - 1], None, None).is_err());
+    }
+}
@@ -0,0 +1,755 @@
+//! The `permessage-deflate` extension (RFC 7692), compressing the payload of the data messages.
+//!
+//! The extension is negotiated with the `Sec-WebSocket-Extensions` header of the WebSocket handshake:
+//! - A client sends `CLIENT_OFFER` with its upgrade request, and passes the value of the header in the
+//!   upgrade response (if any) to `DeflateParams::accept_response`
+//! - A server passes the value of the header in the upgrade request (if any) to `DeflateParams::negotiate`,
+//!   and sends the value produced by `DeflateParams::serialize` with its upgrade response
+//!
+//! The messages are then compressed and decompressed with a `PerMessageDeflate` instance, i.e. with
+//! `io::send_message_deflate` and `io::recv_message_deflate`.
+//!
+//! Note that messages are always compressed without context takeover (i.e. each message is compressed
+//! on its own), with a simple greedy LZ77 matcher and the fixed Huffman codes, which is cheap on memory
+//! and CPU and still compresses repetitive payloads (i.e. JSON telemetry) well.
+
+use miniz_oxide::inflate::core::{
+    decompress, inflate_flags, DecompressorOxide, TINFL_LZ_DICT_SIZE,
+};
+use miniz_oxide::inflate::TINFLStatus;
+
+use super::Error;
+
+/// The name of the extension
+pub const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// The value of the `Sec-WebSocket-Extensions` header to be sent by a client with its upgrade request
+///
+/// As the messages are always compressed without context takeover, the client announces this upfront,
+/// so that the server does not need to keep the context for decompressing the messages of the client.
+pub const CLIENT_OFFER: &str = "permessage-deflate; client_no_context_takeover";
+
+/// The maximum length of the header value produced by `DeflateParams::serialize`
+pub const MAX_EXTENSION_LEN: usize = 128;
+
+const MIN_WINDOW_BITS: u8 = 8;
+const MAX_WINDOW_BITS: u8 = 15;
+
+const HASH_BITS: u32 = 11;
+
+const MIN_MATCH_LEN: usize = 3;
+const MAX_MATCH_LEN: usize = 258;
+
+const END_OF_BLOCK: u16 = 256;
+
+/// The tail of the sync flush, which is removed from the compressed messages
+const SYNC_FLUSH_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The negotiated parameters of the `permessage-deflate` extension
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeflateParams {
+    /// The server compresses each message without using the previous messages
+    pub server_no_context_takeover: bool,
+    /// The client compresses each message without using the previous messages
+    pub client_no_context_takeover: bool,
+    /// The maximum LZ77 window size (as a power of 2) used by the server for compression
+    pub server_max_window_bits: Option<u8>,
+    /// The maximum LZ77 window size (as a power of 2) used by the client for compression
+    pub client_max_window_bits: Option<u8>,
+}
+
+impl DeflateParams {
+    /// Negotiate the parameters of the extension as a server
+    ///
+    /// Returns the parameters of the first acceptable `permessage-deflate` offer, or `None` if the
+    /// client did not offer the extension (or none of its offers is acceptable), in which case
+    /// the messages should not be compressed.
+    ///
+    /// Parameters:
+    /// - `offers`: The value of the `Sec-WebSocket-Extensions` header of the upgrade request
+    pub fn negotiate(offers: &str) -> Option<Self> {
+        offers
+            .split(',')
+            .filter_map(|offer| Self::parse(offer, false))
+            .next()
+            .map(|offer| Self {
+                // Messages are always compressed without context takeover anyway
+                server_no_context_takeover: true,
+                client_no_context_takeover: offer.client_no_context_takeover,
+                server_max_window_bits: offer.server_max_window_bits,
+                // The decompressor always has a full window, so no need to limit the client
+                client_max_window_bits: None,
+            })
+    }
+
+    /// Accept the parameters of the extension as a client
+    ///
+    /// Returns an error if the server responded with parameters which are invalid or which were not offered,
+    /// in which case the client must fail the WebSocket connection.
+    ///
+    /// Parameters:
+    /// - `response`: The value of the `Sec-WebSocket-Extensions` header of the upgrade response
+    pub fn accept_response(response: &str) -> Result<Self, Error<()>> {
+        Self::parse(response, true).ok_or(Error::Invalid)
+    }
+
+    /// Serialize the parameters as the value of a `Sec-WebSocket-Extensions` header
+    pub fn serialize<'b>(&self, buf: &'b mut [u8; MAX_EXTENSION_LEN]) -> &'b str {
+        let mut len = 0;
+
+        let mut push = |s: &str| {
+            buf[len..len + s.len()].copy_from_slice(s.as_bytes());
+            len += s.len();
+        };
+
+        push(EXTENSION_NAME);
+
+        if self.server_no_context_takeover {
+            push("; server_no_context_takeover");
+        }
+
+        if self.client_no_context_takeover {
+            push("; client_no_context_takeover");
+        }
+
+        for (name, bits) in [
+            ("; server_max_window_bits=", self.server_max_window_bits),
+            ("; client_max_window_bits=", self.client_max_window_bits),
+        ] {
+            if let Some(bits) = bits {
+                push(name);
+
+                if bits >= 10 {
+                    push("1");
+                }
+
+                push(unwrap!(core::str::from_utf8(&[b'0' + bits % 10]).ok()));
+            }
+        }
+
+        unwrap!(core::str::from_utf8(&buf[..len]).ok())
+    }
+
+    /// Parse a single `permessage-deflate` offer or response
+    fn parse(extension: &str, response: bool) -> Option<Self> {
+        let mut params = extension.split(';').map(str::trim);
+
+        if !params.next()?.eq_ignore_ascii_case(EXTENSION_NAME) {
+            return None;
+        }
+
+        let mut this = Self::default();
+        let mut client_max_window_bits_seen = false;
+
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+
+            if name.eq_ignore_ascii_case("server_no_context_takeover") && value.is_none() {
+                if this.server_no_context_takeover {
+                    return None;
+                }
+
+                this.server_no_context_takeover = true;
+            } else if name.eq_ignore_ascii_case("client_no_context_takeover") && value.is_none() {
+                if this.client_no_context_takeover {
+                    return None;
+                }
+
+                this.client_no_context_takeover = true;
+            } else if name.eq_ignore_ascii_case("server_max_window_bits") {
+                if this.server_max_window_bits.is_some() {
+                    return None;
+                }
+
+                this.server_max_window_bits = Some(Self::parse_window_bits(value?)?);
+            } else if name.eq_ignore_ascii_case("client_max_window_bits") {
+                // `CLIENT_OFFER` does not contain the parameter, so the server must not respond with it
+                if response || client_max_window_bits_seen {
+                    return None;
+                }
+
+                client_max_window_bits_seen = true;
+
+                // In an offer, the parameter might come without a value, just announcing the support for it
+                this.client_max_window_bits = match value {
+                    Some(value) => Some(Self::parse_window_bits(value)?),
+                    None => None,
+                };
+            } else {
+                return None;
+            }
+        }
+
+        Some(this)
+    }
+
+    fn parse_window_bits(value: &str) -> Option<u8> {
+        // Leading zeroes are not allowed by the grammar of the parameter
+        if value.starts_with('0') {
+            return None;
+        }
+
+        value
+            .parse::<u8>()
+            .ok()
+            .filter(|bits| (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(bits))
+    }
+}
+
+/// The state of the `permessage-deflate` extension of one WebSocket connection
+///
+/// The state is relatively large (~ 51KB), as decompressing needs a 32KB window of previously
+/// decompressed data, so consider allocating it statically.
+pub struct PerMessageDeflate {
+    params: DeflateParams,
+    client: bool,
+    decompressor: DecompressorOxide,
+    dict: [u8; TINFL_LZ_DICT_SIZE],
+    dict_pos: usize,
+    hash_table: [u32; 1 << HASH_BITS],
+}
+
+impl PerMessageDeflate {
+    /// Create the state of the extension
+    ///
+    /// Parameters:
+    /// - `params`: The negotiated parameters of the extension
+    /// - `client`: `true` if the connection is operated by a client, `false` if by a server
+    pub fn new(params: DeflateParams, client: bool) -> Self {
+        Self {
+            params,
+            client,
+            decompressor: DecompressorOxide::new(),
+            dict: [0; TINFL_LZ_DICT_SIZE],
+            dict_pos: 0,
+            hash_table: [0; 1 << HASH_BITS],
+        }
+    }
+
+    /// Return the negotiated parameters of the extension
+    pub fn params(&self) -> &DeflateParams {
+        &self.params
+    }
+
+    /// Compress the payload of a message
+    ///
+    /// Returns the length of the compressed payload in `out`, or `None` if it does not fit in `out`,
+    /// in which case the message should be sent uncompressed instead.
+    pub fn compress(&mut self, payload: &[u8], out: &mut [u8]) -> Option<usize> {
+        let window_bits = if self.client {
+            self.params.client_max_window_bits
+        } else {
+            self.params.server_max_window_bits
+        };
+
+        let max_distance = 1_usize << window_bits.unwrap_or(MAX_WINDOW_BITS);
+
+        self.hash_table.fill(0);
+
+        let mut writer = BitWriter::new(out);
+
+        // A non-final block with the fixed Huffman codes
+        writer.put(0, 1)?;
+        writer.put(1, 2)?;
+
+        let mut pos = 0;
+
+        while pos < payload.len() {
+            if let Some((distance, len)) = self.find_match(payload, pos, max_distance) {
+                writer.put_length(len)?;
+                writer.put_distance(distance)?;
+
+                for skipped in pos + 1..pos + len {
+                    self.insert(payload, skipped);
+                }
+
+                pos += len;
+            } else {
+                writer.put_literal(payload[pos] as _)?;
+                pos += 1;
+            }
+        }
+
+        writer.put_literal(END_OF_BLOCK)?;
+
+        // The sync flush, i.e. an empty non-final stored block, whose `LEN` and `NLEN` fields
+        // (`SYNC_FLUSH_TAIL`) are removed
+        writer.put(0, 3)?;
+        writer.finish()
+    }
+
+    /// Decompress the payload of a message
+    ///
+    /// Returns the length of the decompressed payload in `out`, or `Error::MessageTooLarge` if it
+    /// does not fit in `out`, or `Error::Invalid` if the payload is not valid compressed data.
+    pub fn decompress(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize, Error<()>> {
+        let peer_no_context_takeover = if self.client {
+            self.params.server_no_context_takeover
+        } else {
+            self.params.client_no_context_takeover
+        };
+
+        if peer_no_context_takeover {
+            self.decompressor.init();
+            self.dict_pos = 0;
+        }
+
+        let mut len = 0;
+
+        for mut input in [payload, &SYNC_FLUSH_TAIL] {
+            loop {
+                let (status, consumed, produced) = decompress(
+                    &mut self.decompressor,
+                    input,
+                    &mut self.dict,
+                    self.dict_pos,
+                    inflate_flags::TINFL_FLAG_HAS_MORE_INPUT,
+                );
+
+                out.get_mut(len..len + produced)
+                    .ok_or(Error::MessageTooLarge)?
+                    .copy_from_slice(&self.dict[self.dict_pos..self.dict_pos + produced]);
+
+                len += produced;
+                input = &input[consumed..];
+                self.dict_pos = (self.dict_pos + produced) & (TINFL_LZ_DICT_SIZE - 1);
+
+                match status {
+                    TINFLStatus::NeedsMoreInput => break,
+                    TINFLStatus::HasMoreOutput => (),
+                    // The peer ended the stream with a final block; what follows is a new stream
+                    TINFLStatus::Done if input.is_empty() => {
+                        self.decompressor.init();
+                        break;
+                    }
+                    TINFLStatus::Done => self.decompressor.init(),
+                    _ => Err(Error::Invalid)?,
+                }
+            }
+        }
+
+        Ok(len)
+    }
+
+    fn find_match(
+        &mut self,
+        payload: &[u8],
+        pos: usize,
+        max_distance: usize,
+    ) -> Option<(usize, usize)> {
+        let candidate = self.insert(payload, pos)?;
+
+        let distance = pos - candidate;
+        if distance > max_distance
+            || payload[candidate..candidate + MIN_MATCH_LEN] != payload[pos..pos + MIN_MATCH_LEN]
+        {
+            return None;
+        }
+
+        let max_len = MAX_MATCH_LEN.min(payload.len() - pos);
+
+        let len = MIN_MATCH_LEN
+            + payload[candidate + MIN_MATCH_LEN..]
+                .iter()
+                .zip(&payload[pos + MIN_MATCH_LEN..pos + max_len])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+        Some((distance, len))
+    }
+
+    /// Insert the position in the hash table, returning the previous position with the same hash (if any)
+    fn insert(&mut self, payload: &[u8], pos: usize) -> Option<usize> {
+        let bytes = payload.get(pos..pos + MIN_MATCH_LEN)?;
+
+        let hash = (u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]).wrapping_mul(0x9e37_79b1)
+            >> (32 - HASH_BITS)) as usize;
+
+        let previous = core::mem::replace(&mut self.hash_table[hash], pos as u32 + 1);
+
+        (previous > 0).then(|| previous as usize - 1)
+    }
+}
+
+/// A writer of the LSB-first bit stream of `deflate`
+struct BitWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+    bits: u32,
+    bits_len: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut [u8]) -> Self {
+        Self {
+            out,
+            len: 0,
+            bits: 0,
+            bits_len: 0,
+        }
+    }
+
+    fn put(&mut self, value: u32, bits_len: u32) -> Option<()> {
+        self.bits |= value << self.bits_len;
+        self.bits_len += bits_len;
+
+        while self.bits_len >= 8 {
+            *self.out.get_mut(self.len)? = self.bits as u8;
+
+            self.len += 1;
+            self.bits >>= 8;
+            self.bits_len -= 8;
+        }
+
+        Some(())
+    }
+
+    /// Put a Huffman code, which - unlike the other values - is packed starting from its most significant bit
+    fn put_code(&mut self, code: u32, bits_len: u32) -> Option<()> {
+        self.put(code.reverse_bits() >> (32 - bits_len), bits_len)
+    }
+
+    fn put_literal(&mut self, literal: u16) -> Option<()> {
+        let literal = literal as u32;
+
+        match literal {
+            0..=143 => self.put_code(0x30 + literal, 8),
+            144..=255 => self.put_code(0x190 + literal - 144, 9),
+            256..=279 => self.put_code(literal - 256, 7),
+            _ => self.put_code(0xc0 + literal - 280, 8),
+        }
+    }
+
+    fn put_length(&mut self, len: usize) -> Option<()> {
+        let index = unwrap!(LENGTH_BASE.iter().rposition(|base| *base as usize <= len));
+
+        self.put_literal(257 + index as u16)?;
+        self.put(
+            (len - LENGTH_BASE[index] as usize) as _,
+            LENGTH_EXTRA[index] as _,
+        )
+    }
+
+    fn put_distance(&mut self, distance: usize) -> Option<()> {
+        let index = unwrap!(DIST_BASE
+            .iter()
+            .rposition(|base| *base as usize <= distance));
+
+        self.put_code(index as _, 5)?;
+        self.put(
+            (distance - DIST_BASE[index] as usize) as _,
+            DIST_EXTRA[index] as _,
+        )
+    }
+
+    /// Flush the remaining bits, padding them to a byte, and return the length of the output
+    fn finish(mut self) -> Option<usize> {
+        if self.bits_len > 0 {
+            self.put(0, 8 - self.bits_len)?;
+        }
+
+        Some(self.len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(
+        deflate: &mut PerMessageDeflate,
+        inflate: &mut PerMessageDeflate,
+        payload: &[u8],
+    ) {
+        let mut compressed = [0; 65536];
+        let mut decompressed = [0; 65536];
+
+        let len = unwrap!(deflate.compress(payload, &mut compressed));
+
+        assert!(!compressed[..len].ends_with(&SYNC_FLUSH_TAIL));
+
+        let len = unwrap!(inflate
+            .decompress(&compressed[..len], &mut decompressed)
+            .ok());
+
+        assert_eq!(&decompressed[..len], payload);
+    }
+
+    fn pair(params: DeflateParams) -> (PerMessageDeflate, PerMessageDeflate) {
+        (
+            PerMessageDeflate::new(params, false),
+            PerMessageDeflate::new(params, true),
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let params = unwrap!(DeflateParams::negotiate(CLIENT_OFFER));
+
+        let (mut server, mut client) = pair(params);
+
+        // Empty
+        round_trip(&mut server, &mut client, b"");
+
+        // Short
+        round_trip(&mut server, &mut client, b"Hello");
+
+        // Long and repetitive, compressing well
+        let mut long = [0; 4096];
+        for (index, byte) in long.iter_mut().enumerate() {
+            *byte = b"{\"temperature\": 21.5, \"humidity\": 40}"[index % 37];
+        }
+
+        let mut compressed = [0; 4096];
+        let len = unwrap!(server.compress(&long, &mut compressed));
+        assert!(len < long.len() / 10);
+
+        round_trip(&mut server, &mut client, &long);
+
+        // Larger than the 32KB window, with a pseudo-random (i.e. mostly incompressible) content
+        let mut large = [0; 40000];
+        let mut state = 0x1234_5678_u32;
+        for byte in large.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = (state % 16) as u8 + b'a';
+        }
+
+        round_trip(&mut server, &mut client, &large);
+
+        // And in the other direction
+        round_trip(&mut client, &mut server, &large);
+        round_trip(&mut client, &mut server, b"Hello");
+    }
+
+    #[test]
+    fn test_compress_overflow() {
+        let (mut server, _) = pair(DeflateParams::default());
+
+        let mut compressed = [0; 4];
+
+        assert!(server.compress(b"Hello, World!", &mut compressed).is_none());
+    }
+
+    #[test]
+    fn test_decompress_invalid() {
+        let (_, mut client) = pair(DeflateParams::default());
+
+        let mut decompressed = [0; 64];
+
+        // A block with the reserved block type
+        assert_eq!(
+            client.decompress(&[0xff, 0xff], &mut decompressed),
+            Err(Error::Invalid)
+        );
+
+        // Too large for the output buffer
+        let (mut server, mut client) = pair(DeflateParams::default());
+
+        let mut compressed = [0; 64];
+        let len = unwrap!(server.compress(&[b'a'; 100], &mut compressed));
+
+        assert_eq!(
+            client.decompress(&compressed[..len], &mut decompressed),
+            Err(Error::MessageTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_context_takeover() {
+        // The server did not agree to `server_no_context_takeover`, so its messages
+        // might refer to the data of its previous messages
+        let params = DeflateParams::default();
+
+        let (mut server, mut client) = pair(params);
+
+        round_trip(&mut server, &mut client, b"Hello, World!");
+
+        // A message consisting of a single match, copying the first 5 bytes of the previous message
+        let mut compressed = [0; 16];
+        let mut writer = BitWriter::new(&mut compressed);
+
+        unwrap!(writer.put(0, 1));
+        unwrap!(writer.put(1, 2));
+        unwrap!(writer.put_length(5));
+        unwrap!(writer.put_distance(13));
+        unwrap!(writer.put_literal(END_OF_BLOCK));
+        unwrap!(writer.put(0, 3));
+
+        let len = unwrap!(writer.finish());
+
+        let mut decompressed = [0; 16];
+        let len = unwrap!(client
+            .decompress(&compressed[..len], &mut decompressed)
+            .ok());
+
+        assert_eq!(&decompressed[..len], b"Hello");
+
+        // Messages compressed without context takeover still decompress fine after it
+        round_trip(&mut server, &mut client, b"Hello, World!");
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(DeflateParams::negotiate(""), None);
+        assert_eq!(DeflateParams::negotiate("x-webkit-deflate-frame"), None);
+
+        assert_eq!(
+            DeflateParams::negotiate("permessage-deflate"),
+            Some(DeflateParams {
+                server_no_context_takeover: true,
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            DeflateParams::negotiate(
+                "permessage-deflate; client_no_context_takeover; server_max_window_bits=10; client_max_window_bits"
+            ),
+            Some(DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: true,
+                server_max_window_bits: Some(10),
+                client_max_window_bits: None,
+            })
+        );
+
+        // The first acceptable offer wins
+        assert_eq!(
+            DeflateParams::negotiate(
+                "permessage-deflate; server_max_window_bits=7, permessage-deflate; server_max_window_bits=\"9\""
+            ),
+            Some(DeflateParams {
+                server_no_context_takeover: true,
+                server_max_window_bits: Some(9),
+                ..Default::default()
+            })
+        );
+
+        // Duplicate parameters
+        for offer in [
+            "permessage-deflate; server_no_context_takeover; server_no_context_takeover",
+            "permessage-deflate; client_no_context_takeover; client_no_context_takeover",
+            "permessage-deflate; server_max_window_bits=10; server_max_window_bits=10",
+            "permessage-deflate; client_max_window_bits; client_max_window_bits",
+        ] {
+            assert_eq!(DeflateParams::negotiate(offer), None, "{}", offer);
+        }
+
+        // Bad window bits
+        for offer in [
+            "permessage-deflate; server_max_window_bits=7",
+            "permessage-deflate; server_max_window_bits=16",
+            "permessage-deflate; server_max_window_bits=09",
+            "permessage-deflate; server_max_window_bits=abc",
+            "permessage-deflate; server_max_window_bits",
+            "permessage-deflate; client_max_window_bits=256",
+        ] {
+            assert_eq!(DeflateParams::negotiate(offer), None, "{}", offer);
+        }
+
+        // Unknown parameters, and known ones with unexpected values
+        for offer in [
+            "permessage-deflate; foo",
+            "permessage-deflate; server_no_context_takeover=1",
+        ] {
+            assert_eq!(DeflateParams::negotiate(offer), None, "{}", offer);
+        }
+    }
+
+    #[test]
+    fn test_accept_response() {
+        assert_eq!(
+            DeflateParams::accept_response(
+                "permessage-deflate; server_no_context_takeover; client_no_context_takeover"
+            ),
+            Ok(DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: true,
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            DeflateParams::accept_response("Permessage-Deflate; server_max_window_bits=15"),
+            Ok(DeflateParams {
+                server_max_window_bits: Some(15),
+                ..Default::default()
+            })
+        );
+
+        // Not offered by `CLIENT_OFFER`
+        assert_eq!(
+            DeflateParams::accept_response("permessage-deflate; client_max_window_bits=10"),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            DeflateParams::accept_response("permessage-deflate; client_max_window_bits"),
+            Err(Error::Invalid)
+        );
+
+        assert_eq!(
+            DeflateParams::accept_response(
+                "permessage-deflate; server_no_context_takeover; server_no_context_takeover"
+            ),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            DeflateParams::accept_response("permessage-deflate; server_max_window_bits=20"),
+            Err(Error::Invalid)
+        );
+        assert_eq!(DeflateParams::accept_response("foo"), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut buf = [0; MAX_EXTENSION_LEN];
+
+        assert_eq!(
+            DeflateParams::default().serialize(&mut buf),
+            "permessage-deflate"
+        );
+
+        let params = DeflateParams {
+            server_no_context_takeover: true,
+            client_no_context_takeover: true,
+            server_max_window_bits: Some(9),
+            client_max_window_bits: Some(15),
+        };
+
+        let serialized = params.serialize(&mut buf);
+
+        assert_eq!(
+            serialized,
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; server_max_window_bits=9; client_max_window_bits=15"
+        );
+
+        // What a server serializes after negotiating is accepted by a client
+        let params = unwrap!(DeflateParams::negotiate(CLIENT_OFFER));
+        let serialized = params.serialize(&mut buf);
+
+        assert_eq!(DeflateParams::accept_response(serialized), Ok(params));
+    }
+}
@@ -1,5 +1,7 @@
 use core::cmp::min;
 
+use embassy_time::{Duration, Instant, Timer};
+
 use embedded_io_async::{self, Read, ReadExactError, Write};
 
 use super::*;
@@ -16,6 +18,8 @@ where
             Self::Invalid => Error::Invalid,
             Self::BufferOverflow => Error::BufferOverflow,
             Self::InvalidLen => Error::InvalidLen,
+            Self::Timeout => Error::Timeout,
+            Self::MessageTooLarge => Error::MessageTooLarge,
             Self::Io(e) => Error::Io(e.kind()),
         }
     }
@@ -31,7 +35,26 @@ impl<E> From<ReadExactError<E>> for Error<E> {
 }
 
 impl FrameHeader {
-    pub async fn recv<R>(mut read: R) -> Result<Self, Error<R::Error>>
+    /// Receive a frame header
+    ///
+    /// Returns `Error::Invalid` for frames with any of the `RSV` bits set (see `FrameHeader::deserialize`).
+    pub async fn recv<R>(read: R) -> Result<Self, Error<R::Error>>
+    where
+        R: Read,
+    {
+        Self::recv_rsv(read, false).await
+    }
+
+    /// Same as `recv`, but for a connection which negotiated an extension using the `RSV1` bit
+    /// (see `FrameHeader::deserialize_with_extensions`).
+    pub async fn recv_with_extensions<R>(read: R) -> Result<Self, Error<R::Error>>
+    where
+        R: Read,
+    {
+        Self::recv_rsv(read, true).await
+    }
+
+    async fn recv_rsv<R>(mut read: R, rsv1_allowed: bool) -> Result<Self, Error<R::Error>>
     where
         R: Read,
     {
@@ -44,7 +67,7 @@ impl FrameHeader {
                 .await
                 .map_err(Error::from)?;
 
-            match FrameHeader::deserialize(&header_buf[..read_end]) {
+            match FrameHeader::deserialize_rsv(&header_buf[..read_end], rsv1_allowed) {
                 Ok((header, _)) => return Ok(header),
                 Err(Error::Incomplete(more)) => {
                     read_offset = read_end;
@@ -91,6 +114,27 @@ impl FrameHeader {
         }
     }
 
+    /// Read and discard the payload of the frame
+    pub async fn skip_payload<R>(&self, mut read: R) -> Result<(), Error<R::Error>>
+    where
+        R: Read,
+    {
+        let mut buf = [0_u8; 32];
+        let mut remaining = self.payload_len;
+
+        while remaining > 0 {
+            let len = min(buf.len() as u64, remaining) as usize;
+
+            read.read_exact(&mut buf[..len])
+                .await
+                .map_err(Error::from)?;
+
+            remaining -= len as u64;
+        }
+
+        Ok(())
+    }
+
     pub async fn send_payload<'a, W>(
         &'a self,
         mut write: W,
@@ -157,8 +201,728 @@ where
         frame_type,
         payload_len: frame_data_buf.len() as _,
         mask_key,
+        compressed: false,
     };
 
     header.send(&mut write).await?;
     header.send_payload(write, frame_data_buf).await
 }
+
+/// Receive a complete message, reassembling its fragments (if any) in `message_buf`
+///
+/// Control frames interleaved with the fragments are handled transparently:
+/// - `Ping` frames are answered with a `Pong` frame
+/// - `Pong` frames are passed to `keepalive` (if any)
+/// - A `Close` frame ends the reception, and is returned with its payload in `message_buf`
+///
+/// Returns the type of the message - `Text(false)`, `Binary(false)` or `Close` - and its length.
+/// Errors with `Error::MessageTooLarge` if the message does not fit in `message_buf`, in which case
+/// the connection cannot be used anymore, as the rest of the message was not read.
+///
+/// Parameters:
+/// - `io`: The connection
+/// - `mask_key`: The mask key of the `Pong` frames; should be `Some` when used by a client
+/// - `keepalive`: The keepalive of the connection, if any
+/// - `message_buf`: The buffer where the message is reassembled
+pub async fn recv_message<T>(
+    io: T,
+    mask_key: Option<u32>,
+    keepalive: Option<&mut Keepalive>,
+    message_buf: &mut [u8],
+) -> Result<(FrameType, usize), Error<T::Error>>
+where
+    T: Read + Write,
+{
+    let (message_type, len, _) = recv_frames(io, mask_key, keepalive, message_buf, false).await?;
+
+    Ok((message_type, len))
+}
+
+/// Same as `recv_message`, but for a connection which negotiated the `permessage-deflate` extension
+///
+/// The frames of the message are reassembled in `frame_buf`, and the message is then decompressed
+/// (if it was sent compressed) in `message_buf`.
+///
+/// Parameters:
+/// - `io`: The connection
+/// - `mask_key`: The mask key of the `Pong` frames; should be `Some` when used by a client
+/// - `keepalive`: The keepalive of the connection, if any
+/// - `deflate`: The state of the `permessage-deflate` extension of the connection
+/// - `message_buf`: The buffer where the message is decompressed
+/// - `frame_buf`: The buffer where the (compressed) message is reassembled
+#[cfg(feature = "deflate")]
+pub async fn recv_message_deflate<T>(
+    io: T,
+    mask_key: Option<u32>,
+    keepalive: Option<&mut Keepalive>,
+    deflate: &mut crate::deflate::PerMessageDeflate,
+    message_buf: &mut [u8],
+    frame_buf: &mut [u8],
+) -> Result<(FrameType, usize), Error<T::Error>>
+where
+    T: Read + Write,
+{
+    let (message_type, len, compressed) =
+        recv_frames(io, mask_key, keepalive, frame_buf, true).await?;
+
+    if !compressed {
+        let message = message_buf.get_mut(..len).ok_or(Error::MessageTooLarge)?;
+        message.copy_from_slice(&frame_buf[..len]);
+
+        return Ok((message_type, len));
+    }
+
+    let len = deflate
+        .decompress(&frame_buf[..len], message_buf)
+        .map_err(Error::recast)?;
+
+    if matches!(message_type, FrameType::Text(_))
+        && core::str::from_utf8(&message_buf[..len]).is_err()
+    {
+        return Err(Error::Invalid);
+    }
+
+    Ok((message_type, len))
+}
+
+/// Send a complete message over a connection which negotiated the `permessage-deflate` extension
+///
+/// The message is compressed in `frame_buf` and sent as a single frame. If the compressed message
+/// does not fit in `frame_buf` or is not smaller than the message itself, the message is sent uncompressed.
+///
+/// Parameters:
+/// - `write`: The writer of the connection
+/// - `frame_type`: The type of the message; should be `Text(false)` or `Binary(false)`
+/// - `mask_key`: The mask key of the frame; should be `Some` when used by a client
+/// - `deflate`: The state of the `permessage-deflate` extension of the connection
+/// - `message`: The message
+/// - `frame_buf`: The buffer where the message is compressed
+#[cfg(feature = "deflate")]
+pub async fn send_message_deflate<W>(
+    mut write: W,
+    frame_type: FrameType,
+    mask_key: Option<u32>,
+    deflate: &mut crate::deflate::PerMessageDeflate,
+    message: &[u8],
+    frame_buf: &mut [u8],
+) -> Result<(), Error<W::Error>>
+where
+    W: Write,
+{
+    if !matches!(
+        frame_type,
+        FrameType::Text(false) | FrameType::Binary(false)
+    ) {
+        return Err(Error::Invalid);
+    }
+
+    let compressed_len = deflate
+        .compress(message, frame_buf)
+        .filter(|len| *len < message.len());
+
+    let (payload, compressed) = match compressed_len {
+        Some(len) => (&frame_buf[..len], true),
+        None => (message, false),
+    };
+
+    let header = FrameHeader {
+        frame_type,
+        payload_len: payload.len() as _,
+        mask_key,
+        compressed,
+    };
+
+    header.send(&mut write).await?;
+    header.send_payload(write, payload).await
+}
+
+/// Receive the frames of a complete message, returning its type, its length and whether it is compressed
+async fn recv_frames<T>(
+    mut io: T,
+    mask_key: Option<u32>,
+    mut keepalive: Option<&mut Keepalive>,
+    message_buf: &mut [u8],
+    compression: bool,
+) -> Result<(FrameType, usize, bool), Error<T::Error>>
+where
+    T: Read + Write,
+{
+    let mut message_type = None;
+    let mut compressed = false;
+    let mut len = 0;
+
+    loop {
+        let header = FrameHeader::recv_rsv(&mut io, compression).await?;
+
+        // Only the first frame of a data message might be marked as compressed
+        if header.compressed
+            && (message_type.is_some()
+                || !matches!(header.frame_type, FrameType::Text(_) | FrameType::Binary(_)))
+        {
+            return Err(Error::Invalid);
+        }
+
+        match header.frame_type {
+            FrameType::Ping | FrameType::Pong | FrameType::Close => {
+                let mut control_buf = [0; MAX_CONTROL_PAYLOAD_LEN];
+
+                let payload = header
+                    .recv_payload(&mut io, &mut control_buf)
+                    .await
+                    .map_err(|e| match e {
+                        Error::BufferOverflow => Error::Invalid,
+                        other => other,
+                    })?;
+
+                match header.frame_type {
+                    FrameType::Ping => send(&mut io, FrameType::Pong, mask_key, payload).await?,
+                    FrameType::Pong => {
+                        if let Some(keepalive) = keepalive.as_mut() {
+                            keepalive.process(FrameType::Pong, payload);
+                        }
+                    }
+                    _ => {
+                        let message = message_buf
+                            .get_mut(..payload.len())
+                            .ok_or(Error::MessageTooLarge)?;
+                        message.copy_from_slice(payload);
+
+                        return Ok((FrameType::Close, payload.len(), false));
+                    }
+                }
+
+                continue;
+            }
+            FrameType::Text(_) | FrameType::Binary(_) if message_type.is_none() => {
+                compressed = header.compressed;
+                message_type = Some(match header.frame_type {
+                    FrameType::Text(_) => FrameType::Text(false),
+                    _ => FrameType::Binary(false),
+                });
+            }
+            FrameType::Continue(_) if message_type.is_some() => (),
+            _ => return Err(Error::Invalid),
+        }
+
+        if header.payload_len > (message_buf.len() - len) as u64 {
+            return Err(Error::MessageTooLarge);
+        }
+
+        len += header
+            .recv_payload(&mut io, &mut message_buf[len..])
+            .await?
+            .len();
+
+        if header.frame_type.is_final() {
+            let message_type = unwrap!(message_type);
+
+            // The text of a compressed message can only be validated once it is decompressed
+            if matches!(message_type, FrameType::Text(_))
+                && !compressed
+                && core::str::from_utf8(&message_buf[..len]).is_err()
+            {
+                return Err(Error::Invalid);
+            }
+
+            return Ok((message_type, len, compressed));
+        }
+    }
+}
+
+/// Close the connection with a status code and a reason, and wait for the peer to echo the `Close` frame
+///
+/// Any other frames received while waiting are discarded, as the connection is already closing.
+///
+/// Returns the status code of the `Close` frame of the peer, if it provided one.
+/// Returns `Error::Timeout` if the peer did not echo the `Close` frame within the timeout.
+///
+/// Parameters:
+/// - `io`: The connection
+/// - `mask_key`: The mask key of the `Close` frame; should be `Some` when used by a client
+/// - `code`: The status code (see `close_code`)
+/// - `reason`: The reason; up to 123 bytes
+/// - `timeout_ms`: The time to wait for the `Close` frame of the peer
+pub async fn close_with<T>(
+    mut io: T,
+    mask_key: Option<u32>,
+    code: u16,
+    reason: &str,
+    timeout_ms: u32,
+) -> Result<Option<u16>, Error<T::Error>>
+where
+    T: Read + Write,
+{
+    let mut payload_buf = [0; MAX_CONTROL_PAYLOAD_LEN];
+    let len = serialize_close(code, reason, &mut payload_buf).map_err(Error::recast)?;
+
+    send(&mut io, FrameType::Close, mask_key, &payload_buf[..len]).await?;
+    io.flush().await.map_err(Error::Io)?;
+
+    let wait_close = async {
+        loop {
+            let header = FrameHeader::recv(&mut io).await?;
+
+            if header.frame_type == FrameType::Close {
+                let payload = header
+                    .recv_payload(&mut io, &mut payload_buf)
+                    .await
+                    .map_err(|e| match e {
+                        Error::BufferOverflow => Error::Invalid,
+                        other => other,
+                    })?;
+
+                let close = deserialize_close(payload).map_err(Error::recast)?;
+
+                return Ok(close.map(|(code, _)| code));
+            }
+
+            header.skip_payload(&mut io).await?;
+        }
+    };
+
+    embassy_time::with_timeout(Duration::from_millis(timeout_ms as _), wait_close)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
+
+/// A ping/pong keepalive for a WebSocket connection
+///
+/// The keepalive sends a `Ping` frame every `interval_ms` and tracks the `Pong` frames coming back.
+/// If `max_unanswered` consecutive pings are not answered, the connection is considered dead:
+/// a `Close` frame is sent and `Error::Timeout` is returned.
+///
+/// Typical usage:
+/// - Wait for either `wait` or the next incoming frame (i.e. with `embassy_futures::select`)
+/// - When `wait` completes, call `ping`
+/// - Pass every received frame to `process`
+pub struct Keepalive {
+    interval: Duration,
+    max_unanswered: u32,
+    unanswered: u32,
+    sequence: u32,
+    next_ping: Instant,
+}
+
+impl Keepalive {
+    /// Create a new keepalive
+    ///
+    /// Parameters:
+    /// - `interval_ms`: The interval between two pings
+    /// - `max_unanswered`: The number of consecutive unanswered pings after which the connection is closed
+    pub fn new(interval_ms: u32, max_unanswered: u32) -> Self {
+        let interval = Duration::from_millis(interval_ms as _);
+
+        Self {
+            interval,
+            max_unanswered,
+            unanswered: 0,
+            sequence: 0,
+            next_ping: Instant::now() + interval,
+        }
+    }
+
+    /// Return the number of consecutive pings which were not answered yet
+    pub fn unanswered(&self) -> u32 {
+        self.unanswered
+    }
+
+    /// Wait until the next ping is due
+    pub async fn wait(&self) {
+        Timer::at(self.next_ping).await;
+    }
+
+    /// Send a `Ping` frame
+    ///
+    /// If `max_unanswered` pings were already sent without being answered, sends a `Close` frame instead
+    /// and returns `Error::Timeout`.
+    ///
+    /// Parameters:
+    /// - `write`: The writer of the connection
+    /// - `mask_key`: The mask key of the frame; should be `Some` when the keepalive is used by a client
+    pub async fn ping<W>(
+        &mut self,
+        mut write: W,
+        mask_key: Option<u32>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        W: Write,
+    {
+        if self.unanswered >= self.max_unanswered {
+            warn!(
+                "No pong received for {} pings, closing the connection",
+                self.unanswered
+            );
+
+            send(&mut write, FrameType::Close, mask_key, &[]).await?;
+
+            return Err(Error::Timeout);
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.unanswered += 1;
+        self.next_ping = Instant::now() + self.interval;
+
+        debug!("Sending ping {}", self.sequence);
+
+        send(
+            write,
+            FrameType::Ping,
+            mask_key,
+            &self.sequence.to_be_bytes(),
+        )
+        .await
+    }
+
+    /// Process a received frame
+    ///
+    /// Returns `true` if the frame is a `Pong` answering one of the pings sent by the keepalive.
+    ///
+    /// Parameters:
+    /// - `frame_type`: The type of the received frame
+    /// - `payload`: The (unmasked) payload of the received frame
+    pub fn process(&mut self, frame_type: FrameType, payload: &[u8]) -> bool {
+        if frame_type != FrameType::Pong {
+            return false;
+        }
+
+        let Ok(sequence) = payload.try_into().map(u32::from_be_bytes) else {
+            return false;
+        };
+
+        // The pong answers one of the outstanding pings
+        if self.sequence.wrapping_sub(sequence) < self.unanswered {
+            debug!("Received pong {}", sequence);
+
+            self.unanswered = 0;
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+
+    /// One end of an in-memory connection: reads the frames sent by the peer, and records the written ones
+    struct Duplex<'a> {
+        input: &'a [u8],
+        output: [u8; 256],
+        output_len: usize,
+    }
+
+    impl<'a> Duplex<'a> {
+        fn new(input: &'a [u8]) -> Self {
+            Self {
+                input,
+                output: [0; 256],
+                output_len: 0,
+            }
+        }
+
+        fn output(&self) -> &[u8] {
+            &self.output[..self.output_len]
+        }
+    }
+
+    impl ErrorType for Duplex<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for Duplex<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = min(buf.len(), self.input.len());
+            buf[..len].copy_from_slice(&self.input[..len]);
+
+            self.input = &self.input[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl Write for Duplex<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output[self.output_len..self.output_len + buf.len()].copy_from_slice(buf);
+            self.output_len += buf.len();
+
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Serialize frames as sent by a client, i.e. masked
+    fn frames(frames: &[(FrameType, &[u8])], out: &mut [u8; 256]) -> usize {
+        let mut duplex = Duplex::new(&[]);
+
+        for (frame_type, payload) in frames {
+            let sent = embassy_futures::block_on(send(
+                &mut duplex,
+                *frame_type,
+                Some(0x1234_5678),
+                payload,
+            ));
+
+            unwrap!(sent.ok());
+        }
+
+        let len = duplex.output_len;
+        out[..len].copy_from_slice(duplex.output());
+
+        len
+    }
+
+    fn recv_all(mut read: &[u8], frames: &mut [(FrameType, [u8; 32], usize)]) -> usize {
+        let mut count = 0;
+
+        while !read.is_empty() {
+            let (frame_type, payload, len) = &mut frames[count];
+            (*frame_type, *len) = unwrap!(embassy_futures::block_on(recv(&mut read, payload)).ok());
+
+            count += 1;
+        }
+
+        count
+    }
+
+    #[test]
+    fn test_recv_fragmented_message() {
+        let mut input = [0; 256];
+        let len = frames(
+            &[
+                (FrameType::Text(true), b"Hel"),
+                (FrameType::Ping, b"abc"),
+                (FrameType::Continue(false), b"lo, "),
+                (FrameType::Pong, b"xyz"),
+                (FrameType::Continue(true), b"World"),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+        let mut message = [0; 16];
+
+        let result = embassy_futures::block_on(recv_message(&mut io, None, None, &mut message));
+
+        assert_eq!(result, Ok((FrameType::Text(false), 12)));
+        assert_eq!(&message[..12], b"Hello, World");
+
+        // The ping interleaved with the fragments was answered
+        let mut output = [(FrameType::Close, [0; 32], 0); 4];
+        assert_eq!(recv_all(io.output(), &mut output), 1);
+
+        let (frame_type, payload, len) = &output[0];
+        assert_eq!(*frame_type, FrameType::Pong);
+        assert_eq!(&payload[..*len], b"abc");
+    }
+
+    #[test]
+    fn test_recv_message_errors() {
+        let mut input = [0; 256];
+
+        // Larger than the buffer
+        let len = frames(
+            &[
+                (FrameType::Binary(true), b"0123456789"),
+                (FrameType::Continue(true), b"0123456789"),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+        let mut message = [0; 16];
+
+        assert_eq!(
+            embassy_futures::block_on(recv_message(&mut io, None, None, &mut message)),
+            Err(Error::MessageTooLarge)
+        );
+
+        // Out of sequence frames
+        for sequence in [
+            &[(FrameType::Continue(true), &b"abc"[..])][..],
+            &[
+                (FrameType::Text(true), b"abc"),
+                (FrameType::Binary(false), b"abc"),
+            ],
+        ] {
+            let len = frames(sequence, &mut input);
+
+            let mut io = Duplex::new(&input[..len]);
+
+            assert_eq!(
+                embassy_futures::block_on(recv_message(&mut io, None, None, &mut message)),
+                Err(Error::Invalid)
+            );
+        }
+
+        // A text message which is not UTF-8
+        let len = frames(
+            &[
+                (FrameType::Text(true), &[0xc3]),
+                (FrameType::Continue(true), &[0x28]),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+
+        assert_eq!(
+            embassy_futures::block_on(recv_message(&mut io, None, None, &mut message)),
+            Err(Error::Invalid)
+        );
+
+        // A compressed frame, while no extension was negotiated
+        let len = frames(&[(FrameType::Text(false), b"abc")], &mut input);
+        input[0] |= 0x40;
+
+        let mut io = Duplex::new(&input[..len]);
+
+        assert_eq!(
+            embassy_futures::block_on(recv_message(&mut io, None, None, &mut message)),
+            Err(Error::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_recv_close() {
+        let mut input = [0; 256];
+        let len = frames(
+            &[
+                (FrameType::Text(true), b"Hel"),
+                (FrameType::Close, b"\x03\xe8Bye"),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+        let mut message = [0; 16];
+
+        let result = embassy_futures::block_on(recv_message(&mut io, None, None, &mut message));
+
+        // The close ends the reception of the fragmented message
+        assert_eq!(result, Ok((FrameType::Close, 5)));
+        assert_eq!(
+            deserialize_close(&message[..5]),
+            Ok(Some((close_code::NORMAL, "Bye")))
+        );
+    }
+
+    #[test]
+    fn test_close_with() {
+        let mut input = [0; 256];
+        let len = frames(
+            &[
+                (FrameType::Binary(false), b"late data"),
+                (FrameType::Close, b"\x03\xe9"),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+
+        let result =
+            embassy_futures::block_on(close_with(&mut io, None, close_code::NORMAL, "Bye", 1000));
+
+        // The data frame received while closing was discarded
+        assert_eq!(result, Ok(Some(close_code::GOING_AWAY)));
+
+        let mut output = [(FrameType::Text(false), [0; 32], 0); 4];
+        assert_eq!(recv_all(io.output(), &mut output), 1);
+
+        let (frame_type, payload, len) = &output[0];
+        assert_eq!(*frame_type, FrameType::Close);
+        assert_eq!(
+            deserialize_close(&payload[..*len]),
+            Ok(Some((close_code::NORMAL, "Bye")))
+        );
+    }
+
+    #[test]
+    fn test_keepalive() {
+        let mut keepalive = Keepalive::new(1000, 2);
+        let mut io = Duplex::new(&[]);
+
+        for _ in 0..2 {
+            unwrap!(embassy_futures::block_on(keepalive.ping(&mut io, None)).ok());
+        }
+
+        assert_eq!(keepalive.unanswered(), 2);
+
+        // Only a pong answering one of the pings counts
+        assert!(!keepalive.process(FrameType::Ping, &1_u32.to_be_bytes()));
+        assert!(!keepalive.process(FrameType::Pong, b"abc"));
+        assert!(!keepalive.process(FrameType::Pong, &3_u32.to_be_bytes()));
+        assert_eq!(keepalive.unanswered(), 2);
+
+        assert!(keepalive.process(FrameType::Pong, &1_u32.to_be_bytes()));
+        assert_eq!(keepalive.unanswered(), 0);
+
+        let mut output = [(FrameType::Close, [0; 32], 0); 4];
+        assert_eq!(recv_all(io.output(), &mut output), 2);
+
+        for (index, (frame_type, payload, len)) in output[..2].iter().enumerate() {
+            assert_eq!(*frame_type, FrameType::Ping);
+            assert_eq!(&payload[..*len], &(index as u32 + 1).to_be_bytes());
+        }
+
+        // Pings not answered anymore
+        let mut io = Duplex::new(&[]);
+
+        for _ in 0..2 {
+            unwrap!(embassy_futures::block_on(keepalive.ping(&mut io, None)).ok());
+        }
+
+        assert_eq!(
+            embassy_futures::block_on(keepalive.ping(&mut io, None)),
+            Err(Error::Timeout)
+        );
+
+        let mut output = [(FrameType::Close, [0; 32], 0); 4];
+        assert_eq!(recv_all(io.output(), &mut output), 3);
+        assert_eq!(output[2].0, FrameType::Close);
+    }
+
+    #[test]
+    fn test_keepalive_interleaved_with_message() {
+        let mut keepalive = Keepalive::new(1000, 3);
+        let mut ping = Duplex::new(&[]);
+
+        unwrap!(embassy_futures::block_on(keepalive.ping(&mut ping, None)).ok());
+
+        // The pong answering the ping arrives in the middle of a fragmented message
+        let mut input = [0; 256];
+        let len = frames(
+            &[
+                (FrameType::Binary(true), b"ab"),
+                (FrameType::Pong, &1_u32.to_be_bytes()),
+                (FrameType::Continue(true), b"cd"),
+            ],
+            &mut input,
+        );
+
+        let mut io = Duplex::new(&input[..len]);
+        let mut message = [0; 16];
+
+        let result = embassy_futures::block_on(recv_message(
+            &mut io,
+            None,
+            Some(&mut keepalive),
+            &mut message,
+        ));
+
+        assert_eq!(result, Ok((FrameType::Binary(false), 4)));
+        assert_eq!(&message[..4], b"abcd");
+        assert_eq!(keepalive.unanswered(), 0);
+
+        // Pongs are not answered
+        assert!(io.output().is_empty());
+    }
+}
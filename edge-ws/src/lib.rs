@@ -10,6 +10,8 @@ pub type Final = bool;
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
 
+#[cfg(feature = "deflate")]
+pub mod deflate;
 #[cfg(feature = "io")]
 pub mod io;
 
@@ -90,6 +92,8 @@ pub enum Error<E> {
     Invalid,
     BufferOverflow,
     InvalidLen,
+    Timeout,
+    MessageTooLarge,
     Io(E),
 }
 
@@ -100,6 +104,8 @@ impl Error<()> {
             Self::Invalid => Error::Invalid,
             Self::BufferOverflow => Error::BufferOverflow,
             Self::InvalidLen => Error::InvalidLen,
+            Self::Timeout => Error::Timeout,
+            Self::MessageTooLarge => Error::MessageTooLarge,
             Self::Io(_) => panic!(),
         }
     }
@@ -115,6 +121,8 @@ where
             Self::Invalid => write!(f, "Invalid"),
             Self::BufferOverflow => write!(f, "Buffer overflow"),
             Self::InvalidLen => write!(f, "Invalid length"),
+            Self::Timeout => write!(f, "Timeout"),
+            Self::MessageTooLarge => write!(f, "Message too large"),
             Self::Io(err) => write!(f, "IO error: {}", err),
         }
     }
@@ -131,6 +139,8 @@ where
             Self::Invalid => defmt::write!(f, "Invalid"),
             Self::BufferOverflow => defmt::write!(f, "Buffer overflow"),
             Self::InvalidLen => defmt::write!(f, "Invalid length"),
+            Self::Timeout => defmt::write!(f, "Timeout"),
+            Self::MessageTooLarge => defmt::write!(f, "Message too large"),
             Self::Io(err) => defmt::write!(f, "IO error: {}", err),
         }
     }
@@ -138,11 +148,90 @@ where
 
 impl<E> core::error::Error for Error<E> where E: core::error::Error {}
 
+/// The maximum payload length of a control (`Ping`, `Pong` or `Close`) frame
+pub const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// Close status codes (RFC 6455, section 7.4.1)
+pub mod close_code {
+    /// The purpose of the connection was fulfilled
+    pub const NORMAL: u16 = 1000;
+    /// The endpoint is going away (i.e. server shutdown)
+    pub const GOING_AWAY: u16 = 1001;
+    /// The endpoint received a frame violating the protocol
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    /// The endpoint received a type of data it cannot accept
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    /// The endpoint received data inconsistent with the type of the message (i.e. non-UTF-8 text)
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    /// The endpoint received a message violating its policy
+    pub const POLICY_VIOLATION: u16 = 1008;
+    /// The endpoint received a message too large to process
+    pub const MESSAGE_TOO_LARGE: u16 = 1009;
+    /// The client expected the server to negotiate an extension
+    pub const MANDATORY_EXTENSION: u16 = 1010;
+    /// The server encountered an unexpected condition
+    pub const INTERNAL_ERROR: u16 = 1011;
+}
+
+/// Serialize the payload of a `Close` frame, i.e. the status code and the UTF-8 reason
+///
+/// Returns `Error::Invalid` if the code is not one which can be sent in a `Close` frame
+/// (i.e. one of the reserved `1004`, `1005`, `1006` or `1015` codes), and `Error::InvalidLen`
+/// if the reason is longer than 123 bytes.
+pub fn serialize_close(
+    code: u16,
+    reason: &str,
+    buf: &mut [u8; MAX_CONTROL_PAYLOAD_LEN],
+) -> Result<usize, Error<()>> {
+    if !is_valid_close_code(code) {
+        return Err(Error::Invalid);
+    }
+
+    let len = 2 + reason.len();
+    if len > buf.len() {
+        return Err(Error::InvalidLen);
+    }
+
+    buf[..2].copy_from_slice(&code.to_be_bytes());
+    buf[2..len].copy_from_slice(reason.as_bytes());
+
+    Ok(len)
+}
+
+/// Deserialize the payload of a `Close` frame into the status code and the reason
+///
+/// Returns `None` if the payload is empty, i.e. the peer did not provide a status code.
+pub fn deserialize_close(payload: &[u8]) -> Result<Option<(u16, &str)>, Error<()>> {
+    match payload {
+        [] => Ok(None),
+        [hi, lo, reason @ ..] => {
+            let code = u16::from_be_bytes([*hi, *lo]);
+            if !is_valid_close_code(code) {
+                return Err(Error::Invalid);
+            }
+
+            let reason = core::str::from_utf8(reason).map_err(|_| Error::Invalid)?;
+
+            Ok(Some((code, reason)))
+        }
+        _ => Err(Error::Invalid),
+    }
+}
+
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
 #[derive(Clone, Debug)]
 pub struct FrameHeader {
     pub frame_type: FrameType,
     pub payload_len: u64,
     pub mask_key: Option<u32>,
+    /// The `RSV1` bit, marking the first frame of a compressed message
+    ///
+    /// Only valid when an extension like `permessage-deflate` was negotiated, so `deserialize`
+    /// rejects frames with this bit set, and only `deserialize_with_extensions` accepts them.
+    pub compressed: bool,
 }
 
 impl FrameHeader {
@@ -151,10 +240,25 @@ impl FrameHeader {
         frame_type: FrameType::Binary(false),
         payload_len: 65536,
         mask_key: Some(0),
+        compressed: false,
     }
     .serialized_len();
 
+    /// Deserialize a frame header
+    ///
+    /// Returns `Error::Invalid` for frames with any of the `RSV` bits set, as these are only valid
+    /// when an extension was negotiated (RFC 6455, section 5.2).
     pub fn deserialize(buf: &[u8]) -> Result<(Self, usize), Error<()>> {
+        Self::deserialize_rsv(buf, false)
+    }
+
+    /// Same as `deserialize`, but for a connection which negotiated an extension using the `RSV1` bit
+    /// (i.e. `permessage-deflate`), so frames with the `RSV1` bit set are accepted as `compressed`.
+    pub fn deserialize_with_extensions(buf: &[u8]) -> Result<(Self, usize), Error<()>> {
+        Self::deserialize_rsv(buf, true)
+    }
+
+    fn deserialize_rsv(buf: &[u8], rsv1_allowed: bool) -> Result<(Self, usize), Error<()>> {
         let mut expected_len = 2_usize;
 
         if buf.len() < expected_len {
@@ -162,7 +266,9 @@ impl FrameHeader {
         } else {
             let final_frame = buf[0] & 0x80 != 0;
 
-            let rsv = buf[0] & 0x70;
+            let compressed = buf[0] & 0x40 != 0;
+
+            let rsv = buf[0] & if rsv1_allowed { 0x30 } else { 0x70 };
             if rsv != 0 {
                 return Err(Error::Invalid);
             }
@@ -231,6 +337,7 @@ impl FrameHeader {
                 frame_type,
                 payload_len,
                 mask_key,
+                compressed,
             };
 
             Ok((frame_header, payload_offset))
@@ -261,6 +368,10 @@ impl FrameHeader {
             buf[0] |= 0x80;
         }
 
+        if self.compressed {
+            buf[0] |= 0x40;
+        }
+
         let opcode = match self.frame_type {
             FrameType::Text(_) => 1,
             FrameType::Binary(_) => 2,
@@ -352,3 +463,101 @@ impl defmt::Format for FrameHeader {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rsv_bits() {
+        // A final, compressed (`RSV1`) text frame
+        let frame = [0xc1, 0x05];
+
+        assert_eq!(
+            FrameHeader::deserialize(&frame).map(|(header, _)| header.compressed),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            FrameHeader::deserialize_with_extensions(&frame).map(|(header, _)| header.compressed),
+            Ok(true)
+        );
+
+        // `RSV2` and `RSV3` are never accepted
+        for frame in [[0xa1, 0x05], [0x91, 0x05]] {
+            assert!(FrameHeader::deserialize(&frame).is_err());
+            assert!(FrameHeader::deserialize_with_extensions(&frame).is_err());
+        }
+
+        let header = FrameHeader {
+            frame_type: FrameType::Text(false),
+            payload_len: 5,
+            mask_key: None,
+            compressed: true,
+        };
+
+        let mut buf = [0; FrameHeader::MAX_LEN];
+        let len = unwrap!(header.serialize(&mut buf).ok());
+
+        assert_eq!(&buf[..len], &frame);
+    }
+
+    #[test]
+    fn test_close() {
+        let mut buf = [0; MAX_CONTROL_PAYLOAD_LEN];
+
+        let len = unwrap!(serialize_close(close_code::NORMAL, "Bye", &mut buf).ok());
+        assert_eq!(&buf[..len], b"\x03\xe8Bye");
+        assert_eq!(
+            deserialize_close(&buf[..len]),
+            Ok(Some((close_code::NORMAL, "Bye")))
+        );
+
+        // Empty payload, i.e. no status code
+        assert_eq!(deserialize_close(&[]), Ok(None));
+
+        // A status code without a reason
+        assert_eq!(deserialize_close(&[0x03, 0xe9]), Ok(Some((1001, ""))));
+
+        // A one-byte payload cannot carry a status code
+        assert_eq!(deserialize_close(&[0x03]), Err(Error::Invalid));
+
+        // The reserved codes, which must not be sent in a `Close` frame
+        for code in [1004, 1005, 1006, 1015, 999, 2000, 5000] {
+            assert_eq!(
+                serialize_close(code, "", &mut buf),
+                Err(Error::Invalid),
+                "{}",
+                code
+            );
+            assert_eq!(
+                deserialize_close(&code.to_be_bytes()),
+                Err(Error::Invalid),
+                "{}",
+                code
+            );
+        }
+
+        // The reason can take up to 123 bytes
+        let reason = [b'a'; 124];
+        let reason = unwrap!(core::str::from_utf8(&reason).ok());
+
+        assert_eq!(
+            serialize_close(close_code::NORMAL, &reason[..123], &mut buf),
+            Ok(MAX_CONTROL_PAYLOAD_LEN)
+        );
+        assert_eq!(
+            serialize_close(close_code::NORMAL, reason, &mut buf),
+            Err(Error::InvalidLen)
+        );
+
+        // The reason must be UTF-8
+        assert_eq!(
+            deserialize_close(&[0x03, 0xe8, 0xc3, 0x28]),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            deserialize_close(&[0x03, 0xe8, 0xc3, 0xa9]),
+            Ok(Some((close_code::NORMAL, "\u{e9}")))
+        );
+    }
+}
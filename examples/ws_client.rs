@@ -76,6 +76,7 @@ where
             frame_type: FrameType::Text(false),
             payload_len: payload.len() as _,
             mask_key: rng_source.next_u32().into(),
+            compressed: false,
         };
 
         info!("Sending {header}, with payload \"{payload}\"");
@@ -111,6 +112,7 @@ where
         frame_type: FrameType::Close,
         payload_len: 0,
         mask_key: rng_source.next_u32().into(),
+        compressed: false,
     };
 
     info!("Closing");
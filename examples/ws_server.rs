@@ -2,7 +2,6 @@ use core::fmt::{Debug, Display};
 
 use edge_http::io::server::{Connection, DefaultServer, Handler};
 use edge_http::io::Error;
-use edge_http::ws::MAX_BASE64_KEY_RESPONSE_LEN;
 use edge_http::Method;
 use edge_nal::TcpBind;
 use edge_ws::{FrameHeader, FrameType};
@@ -77,18 +76,13 @@ impl Handler for WsHandler {
             conn.write_all(b"Initiate WS Upgrade request to switch this connection to WS")
                 .await?;
         } else {
-            let mut buf = [0_u8; MAX_BASE64_KEY_RESPONSE_LEN];
-            conn.initiate_ws_upgrade_response(&mut buf).await?;
-
-            conn.complete().await?;
+            let mut socket = conn.upgrade_to_ws().await?;
 
             info!("Connection upgraded to WS, starting a simple WS echo server now");
 
             // Now we have the TCP socket in a state where it can be operated as a WS connection
             // Run a simple WS echo server here
 
-            let mut socket = conn.unbind()?;
-
             let mut buf = [0_u8; 8192];
 
             loop {